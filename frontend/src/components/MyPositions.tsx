@@ -68,12 +68,14 @@ export default function MyPositions() {
   }, [positions, closePosition, refetch]);
 
   const handleRebalanceToggle = useCallback(async (positionMint: string, currentlyEnabled: boolean) => {
-    const newState = await toggleRebalance(positionMint, currentlyEnabled);
+    const pos = positions.find(p => p.positionMint === positionMint);
+    if (!pos) return;
+    const newState = await toggleRebalance(pos, currentlyEnabled);
     if (newState !== currentlyEnabled) {
       // Refetch positions to update rebalance state from chain
       setTimeout(() => refetch(), 1500);
     }
-  }, [toggleRebalance, refetch]);
+  }, [positions, toggleRebalance, refetch]);
 
   if (!mounted) return <PositionList positions={[]} loading={true} />;
 
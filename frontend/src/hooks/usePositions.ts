@@ -6,6 +6,7 @@ import { Connection, PublicKey } from "@solana/web3.js";
 import { PDAUtil, ORCA_WHIRLPOOL_PROGRAM_ID, PriceMath, PoolUtil } from "@orca-so/whirlpools-sdk";
 import { TOKEN_PROGRAM_ID, AccountLayout } from "@solana/spl-token";
 import type { Position } from "@/types/position";
+import { CONFIG_ENABLED_OFFSET, getConfigPDA } from "@/lib/poseidon";
 import BN from "bn.js";
 
 // Use RPC proxy in browser (full URL required by Connection class), env var for SSR/build
@@ -32,21 +33,28 @@ const STABLES = new Set([
 ]);
 
 const RAYDIUM_CLMM = new PublicKey("CAMMCzo5YL8w4VFF8KVHrK22GGUsp5VTaW7grrKgrWqK");
-const POSEIDON_PROGRAM = new PublicKey("HLsgAVzjjBaBR9QCLqV3vjC9LTnR2xtmtB77j1EJQBsZ");
 
-// Fetch wallet-level rebalance config (current on-chain program uses ["rebalance", owner] seeds)
-async function fetchRebalanceEnabled(wallet: PublicKey): Promise<boolean> {
+// Fetch each position's rebalance config ["rebalance", owner, position_mint];
+// Meteora configs are keyed by the position account
+async function fetchRebalanceEnabled(wallet: PublicKey, positions: Position[]): Promise<boolean[]> {
   try {
-    const [configPda] = PublicKey.findProgramAddressSync(
-      [Buffer.from("rebalance"), wallet.toBuffer()],
-      POSEIDON_PROGRAM,
-    );
-    const info = await connection.getAccountInfo(configPda);
-    if (!info || info.data.length < 41) return false;
-    // Current layout: disc(8) + owner(32) + enabled(1)
-    return info.data[40] === 1;
+    const pdas = positions.map(pos => {
+      const key = pos.positionMint ?? (pos.dex === "Meteora" ? pos.positionAddress : undefined);
+      return key ? getConfigPDA(wallet, new PublicKey(key)) : null;
+    });
+    const keys = pdas.filter((pda): pda is PublicKey => pda !== null);
+    const infos: (any | null)[] = [];
+    for (let i = 0; i < keys.length; i += 100) {
+      infos.push(...await connection.getMultipleAccountsInfo(keys.slice(i, i + 100)));
+    }
+    let next = 0;
+    return pdas.map(pda => {
+      if (!pda) return false;
+      const info = infos[next++];
+      return !!info && info.data.length > CONFIG_ENABLED_OFFSET && info.data[CONFIG_ENABLED_OFFSET] === 1;
+    });
   } catch {
-    return false;
+    return positions.map(() => false);
   }
 }
 const METEORA_DLMM = new PublicKey("LBUZKhRxPF3XUpBCjp4YzTKgLccjZhTSDM9YuVaPwxo");
@@ -199,12 +207,15 @@ async function fetchAllPositions(wallet: PublicKey, solPrice: number): Promise<P
     programId: TOKEN_PROGRAM_ID,
   });
 
-  // 2. Filter to NFTs (amount === 1) and collect mints
+  // 2. Filter to NFTs (amount === 1) and collect mints, with the account holding each
   const nftMints: PublicKey[] = [];
-  for (const { account } of tokenAccounts.value) {
+  const nftAccounts = new Map<string, string>();
+  for (const { pubkey, account } of tokenAccounts.value) {
     const parsed = AccountLayout.decode(account.data);
     if (Number(parsed.amount) === 1) {
-      nftMints.push(new PublicKey(parsed.mint));
+      const mint = new PublicKey(parsed.mint);
+      nftMints.push(mint);
+      nftAccounts.set(mint.toBase58(), pubkey.toBase58());
     }
   }
 
@@ -340,6 +351,7 @@ async function fetchAllPositions(wallet: PublicKey, solPrice: number): Promise<P
           id: pos.pda.toBase58(),
           positionMint: pos.mint.toBase58(),
           positionAddress: pos.pda.toBase58(),
+          positionTokenAccount: nftAccounts.get(pos.mint.toBase58()),
           poolAddress: pos.whirlpool.toBase58(),
           pair: `${sym(mintAStr)}/${sym(mintBStr)}`,
           dex: "Orca",
@@ -425,6 +437,7 @@ async function fetchAllPositions(wallet: PublicKey, solPrice: number): Promise<P
           id: pos.pda.toBase58(),
           positionMint: pos.mint.toBase58(),
           positionAddress: pos.pda.toBase58(),
+          positionTokenAccount: nftAccounts.get(pos.mint.toBase58()),
           poolAddress: pos.poolId.toBase58(),
           pair: `${sym(mintAStr)}/${sym(mintBStr)}`,
           dex: "Raydium",
@@ -588,11 +601,11 @@ async function fetchAllPositions(wallet: PublicKey, solPrice: number): Promise<P
     console.warn("Failed to fetch Meteora positions:", err);
   }
 
-  // Fetch wallet-level rebalance config from on-chain
-  const rebalanceEnabled = await fetchRebalanceEnabled(wallet);
-  for (const pos of positions) {
-    pos.autoRebalance = rebalanceEnabled;
-  }
+  // Fetch each position's rebalance config from on-chain
+  const rebalanceEnabled = await fetchRebalanceEnabled(wallet, positions);
+  positions.forEach((pos, i) => {
+    pos.autoRebalance = rebalanceEnabled[i];
+  });
 
   return positions;
 }
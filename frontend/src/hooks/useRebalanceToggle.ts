@@ -7,8 +7,9 @@ import {
   PublicKey,
   TransactionMessage,
   VersionedTransaction,
-  SystemProgram,
 } from "@solana/web3.js";
+import type { Position } from "@/types/position";
+import { Dex, PROTOCOL, disableRebalanceIx, enableRebalanceIx } from "@/lib/poseidon";
 
 function getRpcUrl() {
   if (typeof window !== "undefined") {
@@ -17,62 +18,41 @@ function getRpcUrl() {
   return process.env.HELIUS_RPC_URL || "https://mainnet.helius-rpc.com";
 }
 const connection = new Connection(getRpcUrl(), "confirmed");
-const POSEIDON_PROGRAM = new PublicKey("HLsgAVzjjBaBR9QCLqV3vjC9LTnR2xtmtB77j1EJQBsZ");
-
-// Anchor discriminators: sha256("global:<method>")[0..8]
-const ENABLE_DISC = Buffer.from([0x5e, 0xf7, 0x33, 0xa1, 0x8e, 0xb1, 0xeb, 0x0b]);
-const DISABLE_DISC = Buffer.from([0xaa, 0xce, 0x59, 0x40, 0x4a, 0x47, 0x5e, 0xd6]);
 
 export function useRebalanceToggle() {
   const { publicKey, signTransaction } = useWallet();
   const [toggling, setToggling] = useState<string | null>(null);
 
   const toggle = useCallback(
-    async (_positionMint: string, currentlyEnabled: boolean): Promise<boolean> => {
-      if (!publicKey || !signTransaction) return currentlyEnabled;
+    async (pos: Position, currentlyEnabled: boolean): Promise<boolean> => {
+      if (!publicKey || !signTransaction || !pos.positionMint) return currentlyEnabled;
+      if (!(pos.dex in PROTOCOL)) return currentlyEnabled;
 
-      setToggling("wallet");
+      setToggling(pos.positionMint);
       try {
-        // Current on-chain program: wallet-level PDA ["rebalance", owner]
-        const [configPda] = PublicKey.findProgramAddressSync(
-          [Buffer.from("rebalance"), publicKey.toBuffer()],
-          POSEIDON_PROGRAM,
-        );
-
-        let data: Buffer;
-        let keys: { pubkey: PublicKey; isSigner: boolean; isWritable: boolean }[];
-
-        if (currentlyEnabled) {
-          // disable_rebalance: accounts = [owner, config_pda]
-          data = DISABLE_DISC;
-          keys = [
-            { pubkey: publicKey, isSigner: true, isWritable: true },
-            { pubkey: configPda, isSigner: false, isWritable: true },
-          ];
-        } else {
-          // enable_rebalance: accounts = [owner, config_pda, system_program]
-          const args = Buffer.alloc(4);
-          args.writeUInt16LE(100, 0); // 1% max slippage
-          args.writeUInt16LE(50, 2);  // 0.5% min yield improvement
-          data = Buffer.concat([ENABLE_DISC, args]);
-          keys = [
-            { pubkey: publicKey, isSigner: true, isWritable: true },
-            { pubkey: configPda, isSigner: false, isWritable: true },
-            { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
-          ];
-        }
+        // Per-position config PDA ["rebalance", owner, position_mint]
+        const positionMint = new PublicKey(pos.positionMint);
+        const ix = currentlyEnabled
+          ? disableRebalanceIx(publicKey, positionMint)
+          : enableRebalanceIx(
+              publicKey,
+              {
+                dex: pos.dex as Dex,
+                positionMint,
+                positionTokenAccount: pos.positionTokenAccount
+                  ? new PublicKey(pos.positionTokenAccount)
+                  : undefined,
+                dexPosition: pos.positionAddress ? new PublicKey(pos.positionAddress) : undefined,
+              },
+              100, // 1% max slippage
+              50,  // 0.5% min yield improvement
+            );
 
         const { blockhash } = await connection.getLatestBlockhash();
         const message = new TransactionMessage({
           payerKey: publicKey,
           recentBlockhash: blockhash,
-          instructions: [
-            {
-              programId: POSEIDON_PROGRAM,
-              keys,
-              data,
-            },
-          ],
+          instructions: [ix],
         }).compileToV0Message();
 
         const tx = new VersionedTransaction(message);
@@ -0,0 +1,119 @@
+/**
+ * Poseidon rebalance program client
+ * Per-position config PDAs and the enable/disable instructions
+ */
+
+import { PublicKey, SystemProgram, TransactionInstruction } from "@solana/web3.js";
+
+export const POSEIDON_PROGRAM = new PublicKey("HLsgAVzjjBaBR9QCLqV3vjC9LTnR2xtmtB77j1EJQBsZ");
+
+// Protocol byte enable_rebalance takes
+export const PROTOCOL = { Orca: 1, Raydium: 2, Meteora: 3 } as const;
+export type Dex = keyof typeof PROTOCOL;
+
+// Precomputed: first 8 bytes of sha256("global:<name>")
+export const IX_ENABLE = Buffer.from([94, 247, 51, 161, 142, 177, 235, 11]);
+export const IX_DISABLE = Buffer.from([170, 206, 89, 64, 74, 71, 94, 214]);
+
+// RebalanceConfig: disc(8) + owner(32) + position_mint(32) + enabled(1) + ...
+export const CONFIG_ENABLED_OFFSET = 72;
+const CONFIG_MIN_LEN = 93;
+
+export interface RebalanceConfig {
+  owner: PublicKey;
+  positionMint: PublicKey;
+  enabled: boolean;
+  maxSlippageBps: number;
+  minYieldImprovementBps: number;
+  createdAt: number;
+  updatedAt: number;
+}
+
+/** A position to enable: Meteora positions pass the position account as the mint. */
+export interface RebalanceTarget {
+  dex: Dex;
+  positionMint: PublicKey;
+  /** Owner's token account holding the NFT (Orca/Raydium). */
+  positionTokenAccount?: PublicKey;
+  /** The DEX's position PDA for the NFT (Orca/Raydium). */
+  dexPosition?: PublicKey;
+}
+
+// ["rebalance", owner, position_mint]
+export function getConfigPDA(owner: PublicKey, positionMint: PublicKey): PublicKey {
+  return PublicKey.findProgramAddressSync(
+    [Buffer.from("rebalance"), owner.toBuffer(), positionMint.toBuffer()],
+    POSEIDON_PROGRAM,
+  )[0];
+}
+
+// ["program_config"]
+export function getProgramConfigPDA(): PublicKey {
+  return PublicKey.findProgramAddressSync([Buffer.from("program_config")], POSEIDON_PROGRAM)[0];
+}
+
+export function deserializeConfig(data: Buffer): RebalanceConfig | null {
+  if (data.length < CONFIG_MIN_LEN) return null;
+  let offset = 8; // skip discriminator
+  const owner = new PublicKey(data.subarray(offset, offset + 32));
+  offset += 32;
+  const positionMint = new PublicKey(data.subarray(offset, offset + 32));
+  offset += 32;
+  const enabled = data[offset] === 1;
+  offset += 1;
+  const maxSlippageBps = data.readUInt16LE(offset);
+  offset += 2;
+  const minYieldImprovementBps = data.readUInt16LE(offset);
+  offset += 2;
+  const createdAt = Number(data.readBigInt64LE(offset));
+  offset += 8;
+  const updatedAt = Number(data.readBigInt64LE(offset));
+  return { owner, positionMint, enabled, maxSlippageBps, minYieldImprovementBps, createdAt, updatedAt };
+}
+
+// enable_rebalance: accounts = [owner, config_pda, position_mint, system_program,
+//                               program_config_pda, (position_token_account, dex_position)]
+// data = disc + max_slippage_bps(2) + min_yield_bps(2) + protocol(1)
+export function enableRebalanceIx(
+  owner: PublicKey,
+  target: RebalanceTarget,
+  maxSlippageBps: number,
+  minYieldBps: number,
+): TransactionInstruction {
+  const data = Buffer.alloc(13);
+  IX_ENABLE.copy(data, 0);
+  data.writeUInt16LE(maxSlippageBps, 8);
+  data.writeUInt16LE(minYieldBps, 10);
+  data.writeUInt8(PROTOCOL[target.dex], 12);
+
+  const keys = [
+    { pubkey: owner, isSigner: true, isWritable: true },
+    { pubkey: getConfigPDA(owner, target.positionMint), isSigner: false, isWritable: true },
+    { pubkey: target.positionMint, isSigner: false, isWritable: false },
+    { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
+    { pubkey: getProgramConfigPDA(), isSigner: false, isWritable: false },
+  ];
+  if (target.dex !== "Meteora") {
+    if (!target.positionTokenAccount || !target.dexPosition) {
+      throw new Error(`${target.dex} positions need the NFT token account and position PDA`);
+    }
+    keys.push(
+      { pubkey: target.positionTokenAccount, isSigner: false, isWritable: false },
+      { pubkey: target.dexPosition, isSigner: false, isWritable: false },
+    );
+  }
+  return new TransactionInstruction({ keys, programId: POSEIDON_PROGRAM, data });
+}
+
+// disable_rebalance: accounts = [owner, config_pda, position_mint]
+export function disableRebalanceIx(owner: PublicKey, positionMint: PublicKey): TransactionInstruction {
+  return new TransactionInstruction({
+    keys: [
+      { pubkey: owner, isSigner: true, isWritable: true },
+      { pubkey: getConfigPDA(owner, positionMint), isSigner: false, isWritable: true },
+      { pubkey: positionMint, isSigner: false, isWritable: false },
+    ],
+    programId: POSEIDON_PROGRAM,
+    data: IX_DISABLE,
+  });
+}
@@ -40,18 +40,25 @@ function testComponentExistence() {
 function testPDA() {
   console.log("\n🔑 PDA Derivation");
   const owner = Keypair.generate().publicKey;
+  const positionMint = Keypair.generate().publicKey;
   const [pda, bump] = PublicKey.findProgramAddressSync(
-    [Buffer.from("rebalance"), owner.toBuffer()],
+    [Buffer.from("rebalance"), owner.toBuffer(), positionMint.toBuffer()],
     PROGRAM_ID
   );
   assert("PDA is valid PublicKey", pda instanceof PublicKey);
   assert("Bump is 0-255", bump >= 0 && bump <= 255);
   // Deterministic check
   const [pda2] = PublicKey.findProgramAddressSync(
-    [Buffer.from("rebalance"), owner.toBuffer()],
+    [Buffer.from("rebalance"), owner.toBuffer(), positionMint.toBuffer()],
     PROGRAM_ID
   );
   assert("PDA is deterministic", pda.equals(pda2));
+  // One config per position
+  const [other] = PublicKey.findProgramAddressSync(
+    [Buffer.from("rebalance"), owner.toBuffer(), Keypair.generate().publicKey.toBuffer()],
+    PROGRAM_ID
+  );
+  assert("PDA differs per position", !pda.equals(other));
 }
 
 // ── 3. Discriminator Computation ──
@@ -81,28 +88,33 @@ function testDiscriminators() {
 function testDeserialization() {
   console.log("\n📦 Account Deserialization");
 
-  const buf = Buffer.alloc(61);
+  const buf = Buffer.alloc(93);
   // discriminator (8 bytes) - arbitrary
   buf.writeUInt32LE(0xDEADBEEF, 0);
   buf.writeUInt32LE(0xCAFEBABE, 4);
   // owner pubkey (32 bytes at offset 8)
   const owner = Keypair.generate().publicKey;
   owner.toBuffer().copy(buf, 8);
+  // position_mint pubkey (32 bytes at offset 40)
+  const positionMint = Keypair.generate().publicKey;
+  positionMint.toBuffer().copy(buf, 40);
   // enabled
-  buf[40] = 1;
+  buf[72] = 1;
   // max_slippage_bps
-  buf.writeUInt16LE(100, 41);
+  buf.writeUInt16LE(100, 73);
   // min_yield_improvement_bps
-  buf.writeUInt16LE(50, 43);
+  buf.writeUInt16LE(50, 75);
   // created_at
-  buf.writeBigInt64LE(BigInt(1700000000), 45);
+  buf.writeBigInt64LE(BigInt(1700000000), 77);
   // updated_at
-  buf.writeBigInt64LE(BigInt(1700001000), 53);
+  buf.writeBigInt64LE(BigInt(1700001000), 85);
 
   // Replicate deserializeConfig logic inline
   let offset = 8;
   const dOwner = new PublicKey(buf.subarray(offset, offset + 32));
   offset += 32;
+  const dPositionMint = new PublicKey(buf.subarray(offset, offset + 32));
+  offset += 32;
   const enabled = buf[offset] === 1;
   offset += 1;
   const maxSlippageBps = buf.readUInt16LE(offset);
@@ -114,6 +126,7 @@ function testDeserialization() {
   const updatedAt = Number(buf.readBigInt64LE(offset));
 
   assert("owner matches", dOwner.equals(owner));
+  assert("positionMint matches", dPositionMint.equals(positionMint));
   assert("enabled = true", enabled === true);
   assert("maxSlippageBps = 100", maxSlippageBps === 100);
   assert("minYieldImprovementBps = 50", minYieldImprovementBps === 50);
@@ -122,29 +135,37 @@ function testDeserialization() {
 }
 
 // ── 5. Live Devnet PDA Read ──
+// Reads the config of the position in POSITION_MINT, enabled at (100, 50)
 async function testDevnetPDA() {
   console.log("\n🌐 Live Devnet PDA Read");
+  if (!process.env.POSITION_MINT) {
+    console.log("  ⚠️  POSITION_MINT not set, skipping");
+    return;
+  }
   try {
     const keypairPath = "C:/Users/chris/.config/solana/id.json";
     const secretKey = Uint8Array.from(JSON.parse(fs.readFileSync(keypairPath, "utf-8")));
     const keypair = Keypair.fromSecretKey(secretKey);
+    const positionMint = new PublicKey(process.env.POSITION_MINT);
     console.log(`  Wallet: ${keypair.publicKey.toBase58()}`);
 
     const [pda] = PublicKey.findProgramAddressSync(
-      [Buffer.from("rebalance"), keypair.publicKey.toBuffer()],
+      [Buffer.from("rebalance"), keypair.publicKey.toBuffer(), positionMint.toBuffer()],
       PROGRAM_ID
     );
     console.log(`  PDA: ${pda.toBase58()}`);
 
     const connection = new Connection("https://api.devnet.solana.com", "confirmed");
     const info = await connection.getAccountInfo(pda);
-    assert("PDA account exists on devnet", info !== null && info.data.length >= 61);
+    assert("PDA account exists on devnet", info !== null && info.data.length >= 93);
 
-    if (info && info.data.length >= 61) {
+    if (info && info.data.length >= 93) {
       const data = Buffer.from(info.data);
       let offset = 8;
       const owner = new PublicKey(data.subarray(offset, offset + 32));
       offset += 32;
+      const mint = new PublicKey(data.subarray(offset, offset + 32));
+      offset += 32;
       const enabled = data[offset] === 1;
       offset += 1;
       const maxSlippageBps = data.readUInt16LE(offset);
@@ -152,6 +173,7 @@ async function testDevnetPDA() {
       const minYieldImprovementBps = data.readUInt16LE(offset);
 
       assert("owner matches keypair", owner.equals(keypair.publicKey));
+      assert("positionMint matches", mint.equals(positionMint));
       assert("enabled = true", enabled === true, `got ${enabled}`);
       assert("maxSlippageBps = 100", maxSlippageBps === 100, `got ${maxSlippageBps}`);
       assert("minYieldImprovementBps = 50", minYieldImprovementBps === 50, `got ${minYieldImprovementBps}`);
@@ -16,6 +16,7 @@ export interface Position {
   // Close position data
   positionMint?: string;
   positionAddress?: string;
+  positionTokenAccount?: string;
   poolAddress?: string;
   txSignature?: string;
   autoRebalance?: boolean;
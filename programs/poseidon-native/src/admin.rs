@@ -0,0 +1,654 @@
+use poseidon_state::{
+    AccountState, DexProgram, OracleFeed, PoseidonError, ProgramConfig, Protocol,
+    ORACLE_FEED_SEED, PROGRAM_CONFIG_SEED,
+};
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    bpf_loader_upgradeable,
+    clock::Clock,
+    entrypoint::ProgramResult,
+    msg,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    sysvar::Sysvar,
+};
+
+use crate::{create_pda_account, resize_account};
+
+/// Load the singleton config, verifying address and program ownership.
+pub fn load_program_config(
+    program_id: &Pubkey,
+    account: &AccountInfo,
+) -> Result<ProgramConfig, ProgramError> {
+    if account.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    let config = ProgramConfig::unpack(&account.try_borrow_data()?)
+        .map_err(|_| PoseidonError::InvalidProgramConfig)?;
+    let expected_pda =
+        Pubkey::create_program_address(&[PROGRAM_CONFIG_SEED, &[config.bump]], program_id)?;
+    if *account.key != expected_pda {
+        return Err(PoseidonError::InvalidProgramConfig.into());
+    }
+    Ok(config)
+}
+
+pub fn process_initialize(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    data: &[u8],
+) -> ProgramResult {
+    // Accounts: [admin, program_config_pda, program_data, system_program]
+    let iter = &mut accounts.iter();
+    let admin = next_account_info(iter)?;
+    let config_account = next_account_info(iter)?;
+    let program_data = next_account_info(iter)?;
+    let system_program = next_account_info(iter)?;
+
+    if !admin.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    // Data: treasury(32) + deposit_fee_bps(2) + performance_fee_bps(2)
+    if data.len() < 36 {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    let treasury = Pubkey::try_from(&data[..32]).map_err(|_| ProgramError::InvalidInstructionData)?;
+    let deposit_fee_bps = u16::from_le_bytes([data[32], data[33]]);
+    let performance_fee_bps = u16::from_le_bytes([data[34], data[35]]);
+    if deposit_fee_bps > ProgramConfig::MAX_FEE_BPS || performance_fee_bps > ProgramConfig::MAX_FEE_BPS {
+        return Err(PoseidonError::InvalidFeeBps.into());
+    }
+
+    // Only the upgrade authority may bootstrap the admin
+    let (expected_program_data, _) =
+        Pubkey::find_program_address(&[program_id.as_ref()], &bpf_loader_upgradeable::id());
+    if *program_data.key != expected_program_data || *program_data.owner != bpf_loader_upgradeable::id() {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    if upgrade_authority(&program_data.try_borrow_data()?)? != Some(*admin.key) {
+        return Err(PoseidonError::NotUpgradeAuthority.into());
+    }
+
+    let (expected_pda, bump) = ProgramConfig::find_address(program_id);
+    if *config_account.key != expected_pda {
+        return Err(PoseidonError::InvalidProgramConfig.into());
+    }
+    if !config_account.data_is_empty() {
+        return Err(ProgramError::AccountAlreadyInitialized);
+    }
+
+    create_pda_account(
+        program_id,
+        admin,
+        config_account,
+        ProgramConfig::LEN,
+        system_program,
+        &[PROGRAM_CONFIG_SEED, &[bump]],
+    )?;
+
+    let config = ProgramConfig {
+        version: ProgramConfig::VERSION,
+        bump,
+        admin: *admin.key,
+        treasury,
+        deposit_fee_bps,
+        performance_fee_bps,
+        paused: false,
+        agents: Vec::new(),
+        slippage_cap_bps: ProgramConfig::DEFAULT_SLIPPAGE_CAP_BPS,
+        min_yield_cap_bps: ProgramConfig::DEFAULT_MIN_YIELD_CAP_BPS,
+        dex_programs: DexProgram::defaults(),
+        oracle_max_age_seconds: ProgramConfig::DEFAULT_ORACLE_MAX_AGE_SECONDS,
+        oracle_max_deviation_bps: ProgramConfig::DEFAULT_ORACLE_MAX_DEVIATION_BPS,
+    };
+    config.pack(&mut config_account.try_borrow_mut_data()?)?;
+
+    msg!("Program config initialized, admin {}", admin.key);
+    Ok(())
+}
+
+pub fn process_rotate_admin(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    // Accounts: [admin, program_config_pda, new_admin]
+    let iter = &mut accounts.iter();
+    let admin = next_account_info(iter)?;
+    let config_account = next_account_info(iter)?;
+    let new_admin = next_account_info(iter)?;
+
+    // The incoming admin co-signs so a typo can't brick the config
+    if !new_admin.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    let mut config = load_admin_config(program_id, admin, config_account)?;
+    require_current(&config)?;
+    config.admin = *new_admin.key;
+    config.pack(&mut config_account.try_borrow_mut_data()?)?;
+
+    msg!("Admin rotated from {} to {}", admin.key, new_admin.key);
+    Ok(())
+}
+
+pub fn process_add_agent(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    data: &[u8],
+) -> ProgramResult {
+    // Accounts: [admin, program_config_pda]
+    let iter = &mut accounts.iter();
+    let admin = next_account_info(iter)?;
+    let config_account = next_account_info(iter)?;
+
+    let agent = parse_pubkey(data)?;
+    let mut config = load_admin_config(program_id, admin, config_account)?;
+    require_current(&config)?;
+    if config.is_agent(&agent) {
+        msg!("Agent {} already authorized", agent);
+        return Err(PoseidonError::AgentAlreadyAuthorized.into());
+    }
+    if config.agents.len() >= ProgramConfig::MAX_AGENTS {
+        return Err(PoseidonError::TooManyAgents.into());
+    }
+    config.agents.push(agent);
+    config.pack(&mut config_account.try_borrow_mut_data()?)?;
+
+    msg!("Agent {} authorized", agent);
+    Ok(())
+}
+
+pub fn process_remove_agent(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    data: &[u8],
+) -> ProgramResult {
+    // Accounts: [admin, program_config_pda]
+    let iter = &mut accounts.iter();
+    let admin = next_account_info(iter)?;
+    let config_account = next_account_info(iter)?;
+
+    let agent = parse_pubkey(data)?;
+    let mut config = load_admin_config(program_id, admin, config_account)?;
+    require_current(&config)?;
+    let index = config
+        .agents
+        .iter()
+        .position(|a| *a == agent)
+        .ok_or_else(|| {
+            msg!("Agent {} not authorized", agent);
+            PoseidonError::AgentNotFound
+        })?;
+    config.agents.remove(index);
+    config.pack(&mut config_account.try_borrow_mut_data()?)?;
+
+    msg!("Agent {} removed", agent);
+    Ok(())
+}
+
+pub fn process_set_paused(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    data: &[u8],
+) -> ProgramResult {
+    // Accounts: [admin, program_config_pda]
+    let iter = &mut accounts.iter();
+    let admin = next_account_info(iter)?;
+    let config_account = next_account_info(iter)?;
+
+    // Data: paused(1), 0 or 1
+    let paused = match data.first() {
+        Some(0) => false,
+        Some(1) => true,
+        _ => return Err(ProgramError::InvalidInstructionData),
+    };
+
+    // Written in place so the switch works before an older layout is upgraded
+    load_admin_config(program_id, admin, config_account)?;
+    config_account.try_borrow_mut_data()?[ProgramConfig::PAUSED_OFFSET] = paused as u8;
+
+    msg!("Program {}", if paused { "paused" } else { "resumed" });
+    Ok(())
+}
+
+pub fn process_set_rebalance_bounds(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    data: &[u8],
+) -> ProgramResult {
+    // Accounts: [admin, program_config_pda, system_program]
+    let iter = &mut accounts.iter();
+    let admin = next_account_info(iter)?;
+    let config_account = next_account_info(iter)?;
+    let system_program = next_account_info(iter)?;
+
+    // Data: slippage_cap_bps(2) + min_yield_cap_bps(2)
+    if data.len() < 4 {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    let slippage_cap_bps = u16::from_le_bytes([data[0], data[1]]);
+    let min_yield_cap_bps = u16::from_le_bytes([data[2], data[3]]);
+    if slippage_cap_bps == 0
+        || slippage_cap_bps > ProgramConfig::MAX_BPS
+        || min_yield_cap_bps == 0
+        || min_yield_cap_bps > ProgramConfig::MAX_BPS
+    {
+        return Err(PoseidonError::InvalidBounds.into());
+    }
+
+    let mut config = load_admin_config(program_id, admin, config_account)?;
+    upgrade_program_config(&mut config, config_account, admin, system_program)?;
+    config.slippage_cap_bps = slippage_cap_bps;
+    config.min_yield_cap_bps = min_yield_cap_bps;
+    config.pack(&mut config_account.try_borrow_mut_data()?)?;
+
+    msg!(
+        "Rebalance bounds set: slippage <= {} bps, min yield <= {} bps",
+        slippage_cap_bps,
+        min_yield_cap_bps
+    );
+    Ok(())
+}
+
+pub fn process_set_oracle_bounds(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    data: &[u8],
+) -> ProgramResult {
+    // Accounts: [admin, program_config_pda, system_program]
+    let iter = &mut accounts.iter();
+    let admin = next_account_info(iter)?;
+    let config_account = next_account_info(iter)?;
+    let system_program = next_account_info(iter)?;
+
+    // Data: max_age_seconds(4) + max_deviation_bps(2)
+    if data.len() < 6 {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    let max_age_seconds = u32::from_le_bytes([data[0], data[1], data[2], data[3]]);
+    let max_deviation_bps = u16::from_le_bytes([data[4], data[5]]);
+    if max_age_seconds == 0
+        || max_age_seconds > ProgramConfig::MAX_ORACLE_AGE_SECONDS
+        || max_deviation_bps == 0
+        || max_deviation_bps > ProgramConfig::MAX_BPS
+    {
+        return Err(PoseidonError::InvalidBounds.into());
+    }
+
+    let mut config = load_admin_config(program_id, admin, config_account)?;
+    upgrade_program_config(&mut config, config_account, admin, system_program)?;
+    config.oracle_max_age_seconds = max_age_seconds;
+    config.oracle_max_deviation_bps = max_deviation_bps;
+    config.pack(&mut config_account.try_borrow_mut_data()?)?;
+
+    msg!(
+        "Oracle bounds set: age <= {}s, deviation <= {} bps",
+        max_age_seconds,
+        max_deviation_bps
+    );
+    Ok(())
+}
+
+/// Set the Pyth feed oracle accounts must quote for pools of a token pair,
+/// creating the pair's feed account on first use.
+pub fn process_set_oracle_feed(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    data: &[u8],
+) -> ProgramResult {
+    // Accounts: [admin, program_config_pda, oracle_feed_pda, system_program]
+    let iter = &mut accounts.iter();
+    let admin = next_account_info(iter)?;
+    let config_account = next_account_info(iter)?;
+    let feed_account = next_account_info(iter)?;
+    let system_program = next_account_info(iter)?;
+
+    // Data: mint_a(32) + mint_b(32) + feed_id(32)
+    if data.len() < 96 {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    let mint_a = parse_pubkey(&data[..32])?;
+    let mint_b = parse_pubkey(&data[32..64])?;
+    let feed_id: [u8; 32] = data[64..96].try_into().unwrap();
+
+    load_admin_config(program_id, admin, config_account)?;
+    let (expected_pda, bump) = OracleFeed::find_address(&mint_a, &mint_b, program_id);
+    if *feed_account.key != expected_pda {
+        return Err(PoseidonError::InvalidOracleFeed.into());
+    }
+    if feed_account.data_is_empty() {
+        create_pda_account(
+            program_id,
+            admin,
+            feed_account,
+            OracleFeed::LEN,
+            system_program,
+            &[ORACLE_FEED_SEED, mint_a.as_ref(), mint_b.as_ref(), &[bump]],
+        )?;
+    } else if feed_account.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    let feed = OracleFeed {
+        version: OracleFeed::VERSION,
+        bump,
+        mint_a,
+        mint_b,
+        feed_id,
+        updated_at: Clock::get()?.unix_timestamp,
+    };
+    feed.pack(&mut feed_account.try_borrow_mut_data()?)?;
+
+    msg!("Oracle feed for {} / {} set", mint_a, mint_b);
+    Ok(())
+}
+
+pub fn process_add_dex_program(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    data: &[u8],
+) -> ProgramResult {
+    // Accounts: [admin, program_config_pda, system_program]
+    let iter = &mut accounts.iter();
+    let admin = next_account_info(iter)?;
+    let config_account = next_account_info(iter)?;
+    let system_program = next_account_info(iter)?;
+
+    let dex = parse_dex_program(data)?;
+    let mut config = load_admin_config(program_id, admin, config_account)?;
+    upgrade_program_config(&mut config, config_account, admin, system_program)?;
+    if config.dex_programs.contains(&dex) {
+        return Err(PoseidonError::DexProgramAlreadyAccepted.into());
+    }
+    if config.dex_programs.len() >= ProgramConfig::MAX_DEX_PROGRAMS {
+        return Err(PoseidonError::TooManyDexPrograms.into());
+    }
+    config.dex_programs.push(dex);
+    config.pack(&mut config_account.try_borrow_mut_data()?)?;
+
+    msg!("DEX program {} accepted for {:?}", dex.program_id, dex.protocol);
+    Ok(())
+}
+
+pub fn process_remove_dex_program(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    data: &[u8],
+) -> ProgramResult {
+    // Accounts: [admin, program_config_pda, system_program]
+    let iter = &mut accounts.iter();
+    let admin = next_account_info(iter)?;
+    let config_account = next_account_info(iter)?;
+    let system_program = next_account_info(iter)?;
+
+    let dex = parse_dex_program(data)?;
+    let mut config = load_admin_config(program_id, admin, config_account)?;
+    upgrade_program_config(&mut config, config_account, admin, system_program)?;
+    let index = config
+        .dex_programs
+        .iter()
+        .position(|d| *d == dex)
+        .ok_or(PoseidonError::DexProgramNotFound)?;
+    config.dex_programs.remove(index);
+    config.pack(&mut config_account.try_borrow_mut_data()?)?;
+
+    msg!("DEX program {} removed for {:?}", dex.program_id, dex.protocol);
+    Ok(())
+}
+
+/// Load the program config and fail if the admin has paused agent actions.
+pub fn require_not_paused(
+    program_id: &Pubkey,
+    account: &AccountInfo,
+) -> Result<ProgramConfig, ProgramError> {
+    let config = load_program_config(program_id, account)?;
+    if config.paused {
+        return Err(PoseidonError::ProgramPaused.into());
+    }
+    Ok(config)
+}
+
+fn load_admin_config(
+    program_id: &Pubkey,
+    admin: &AccountInfo,
+    config_account: &AccountInfo,
+) -> Result<ProgramConfig, ProgramError> {
+    if !admin.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    let config = load_program_config(program_id, config_account)?;
+    if config.admin != *admin.key {
+        return Err(PoseidonError::Unauthorized.into());
+    }
+    Ok(config)
+}
+
+fn require_current(config: &ProgramConfig) -> ProgramResult {
+    if config.needs_migration() {
+        msg!("Program config is v{}; upgrade it with set_rebalance_bounds first", config.version);
+        return Err(PoseidonError::ConfigNeedsMigration.into());
+    }
+    Ok(())
+}
+
+/// Grow an older program config to the current layout and fill new fields
+/// with defaults. The admin pays the extra rent.
+fn upgrade_program_config<'a>(
+    config: &mut ProgramConfig,
+    config_account: &AccountInfo<'a>,
+    admin: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+) -> ProgramResult {
+    if config.needs_migration() {
+        resize_account(config_account, admin, system_program, ProgramConfig::LEN)?;
+        config.migrate();
+    }
+    Ok(())
+}
+
+// Data: protocol(1) + program_id(32)
+fn parse_dex_program(data: &[u8]) -> Result<DexProgram, ProgramError> {
+    if data.len() < 33 {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    Ok(DexProgram {
+        protocol: Protocol::try_from(data[0])?,
+        program_id: parse_pubkey(&data[1..])?,
+    })
+}
+
+fn parse_pubkey(data: &[u8]) -> Result<Pubkey, ProgramError> {
+    if data.len() < 32 {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    Pubkey::try_from(&data[..32]).map_err(|_| ProgramError::InvalidInstructionData)
+}
+
+// ProgramData layout: tag(4) + slot(8) + Option<authority>(1 + 32)
+fn upgrade_authority(data: &[u8]) -> Result<Option<Pubkey>, ProgramError> {
+    if data.len() < 45 || u32::from_le_bytes(data[..4].try_into().unwrap()) != 3 {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    if data[12] == 0 {
+        return Ok(None);
+    }
+    Pubkey::try_from(&data[13..45])
+        .map(Some)
+        .map_err(|_| ProgramError::InvalidAccountData)
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_program::system_program;
+
+    use super::*;
+    use crate::{
+        test_utils::*, IX_ADD_AGENT, IX_INITIALIZE_PROGRAM_CONFIG, IX_REMOVE_AGENT, IX_ROTATE_ADMIN,
+        IX_SET_PAUSED,
+    };
+
+    /// The program's ProgramData account with `authority` as upgrade authority.
+    fn program_data(authority: Pubkey) -> Account {
+        let (address, _) =
+            Pubkey::find_program_address(&[PROGRAM_ID.as_ref()], &bpf_loader_upgradeable::id());
+        let mut data = 3u32.to_le_bytes().to_vec();
+        data.extend_from_slice(&0u64.to_le_bytes()); // slot
+        data.push(1);
+        data.extend_from_slice(authority.as_ref());
+        Account::new(address, bpf_loader_upgradeable::id(), data)
+    }
+
+    fn initialize_accounts(admin: Pubkey, authority: Pubkey) -> Vec<Account> {
+        vec![
+            Account::signer(admin),
+            Account::empty(ProgramConfig::find_address(&PROGRAM_ID).0),
+            program_data(authority).readonly(),
+            Account::program(system_program::id()),
+        ]
+    }
+
+    fn initialize_args() -> Vec<u8> {
+        let mut args = Pubkey::new_unique().to_bytes().to_vec();
+        args.extend_from_slice(&50u16.to_le_bytes());
+        args.extend_from_slice(&1_000u16.to_le_bytes());
+        ix_data(IX_INITIALIZE_PROGRAM_CONFIG, &args)
+    }
+
+    #[test]
+    fn only_the_upgrade_authority_initializes() {
+        let authority = Pubkey::new_unique();
+        let mut accounts = initialize_accounts(Pubkey::new_unique(), authority);
+        assert_eq!(
+            process(&mut accounts, &initialize_args()),
+            Err(PoseidonError::NotUpgradeAuthority.into())
+        );
+
+        let mut accounts = initialize_accounts(authority, authority);
+        process(&mut accounts, &initialize_args()).unwrap();
+        let config: ProgramConfig = accounts[1].unpack();
+        assert_eq!(config.admin, authority);
+        assert_eq!(config.version, ProgramConfig::VERSION);
+        assert!(config.agents.is_empty());
+        assert_eq!(accounts[1].owner, PROGRAM_ID);
+
+        // A second initialize cannot take the admin over
+        assert_eq!(
+            process(&mut accounts, &initialize_args()),
+            Err(ProgramError::AccountAlreadyInitialized)
+        );
+    }
+
+    #[test]
+    fn rotate_admin_needs_both_admins_to_sign() {
+        let (admin, new_admin) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let data = ix_data(IX_ROTATE_ADMIN, &[]);
+
+        let mut accounts = vec![
+            Account::signer(admin),
+            program_config(admin, &[]),
+            Account::signer(new_admin).unsigned(),
+        ];
+        assert_eq!(process(&mut accounts, &data), Err(ProgramError::MissingRequiredSignature));
+
+        accounts[0].is_signer = false;
+        accounts[2].is_signer = true;
+        assert_eq!(process(&mut accounts, &data), Err(ProgramError::MissingRequiredSignature));
+
+        accounts[0].is_signer = true;
+        process(&mut accounts, &data).unwrap();
+        assert_eq!(accounts[1].unpack::<ProgramConfig>().admin, new_admin);
+
+        // The old admin is locked out
+        let mut accounts =
+            vec![Account::signer(admin), accounts.remove(1), Account::signer(Pubkey::new_unique())];
+        assert_eq!(process(&mut accounts, &data), Err(PoseidonError::Unauthorized.into()));
+    }
+
+    #[test]
+    fn only_the_admin_manages_agents() {
+        let (admin, agent) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let mut accounts = vec![Account::signer(Pubkey::new_unique()), program_config(admin, &[])];
+        let add = ix_data(IX_ADD_AGENT, agent.as_ref());
+        let remove = ix_data(IX_REMOVE_AGENT, agent.as_ref());
+
+        assert_eq!(process(&mut accounts, &add), Err(PoseidonError::Unauthorized.into()));
+        accounts[0] = Account::signer(admin).unsigned();
+        assert_eq!(process(&mut accounts, &add), Err(ProgramError::MissingRequiredSignature));
+
+        accounts[0].is_signer = true;
+        process(&mut accounts, &add).unwrap();
+        assert_eq!(accounts[1].unpack::<ProgramConfig>().agents, vec![agent]);
+        assert_eq!(process(&mut accounts, &add), Err(PoseidonError::AgentAlreadyAuthorized.into()));
+
+        process(&mut accounts, &remove).unwrap();
+        assert!(accounts[1].unpack::<ProgramConfig>().agents.is_empty());
+        assert_eq!(process(&mut accounts, &remove), Err(PoseidonError::AgentNotFound.into()));
+    }
+
+    #[test]
+    fn agent_list_is_capped() {
+        let admin = Pubkey::new_unique();
+        let agents: Vec<Pubkey> =
+            (0..ProgramConfig::MAX_AGENTS).map(|_| Pubkey::new_unique()).collect();
+        let mut accounts = vec![Account::signer(admin), program_config(admin, &agents)];
+        assert_eq!(
+            process(&mut accounts, &ix_data(IX_ADD_AGENT, Pubkey::new_unique().as_ref())),
+            Err(PoseidonError::TooManyAgents.into())
+        );
+    }
+
+    #[test]
+    fn agent_changes_wait_for_the_config_upgrade() {
+        let admin = Pubkey::new_unique();
+        let mut accounts = vec![Account::signer(admin), program_config(admin, &[])];
+        let mut config: ProgramConfig = accounts[1].unpack();
+        config.version = 3;
+        accounts[1] = Account::state(accounts[1].key, &config);
+        assert_eq!(
+            process(&mut accounts, &ix_data(IX_ADD_AGENT, Pubkey::new_unique().as_ref())),
+            Err(PoseidonError::ConfigNeedsMigration.into())
+        );
+    }
+
+    #[test]
+    fn only_the_admin_pauses() {
+        let admin = Pubkey::new_unique();
+        let mut accounts = vec![Account::signer(Pubkey::new_unique()), program_config(admin, &[])];
+        assert_eq!(
+            process(&mut accounts, &ix_data(IX_SET_PAUSED, &[1])),
+            Err(PoseidonError::Unauthorized.into())
+        );
+
+        accounts[0] = Account::signer(admin);
+        process(&mut accounts, &ix_data(IX_SET_PAUSED, &[1])).unwrap();
+        assert!(accounts[1].unpack::<ProgramConfig>().paused);
+        process(&mut accounts, &ix_data(IX_SET_PAUSED, &[0])).unwrap();
+        assert!(!accounts[1].unpack::<ProgramConfig>().paused);
+    }
+
+    #[test]
+    fn set_paused_takes_only_0_or_1() {
+        let admin = Pubkey::new_unique();
+        let mut accounts = vec![Account::signer(admin), program_config(admin, &[])];
+        for data in [&[2][..], &[255], &[]] {
+            assert_eq!(
+                process(&mut accounts, &ix_data(IX_SET_PAUSED, data)),
+                Err(ProgramError::InvalidInstructionData)
+            );
+        }
+    }
+
+    #[test]
+    fn set_paused_works_before_the_config_upgrade() {
+        let admin = Pubkey::new_unique();
+        let (address, bump) = ProgramConfig::find_address(&PROGRAM_ID);
+        let config = ProgramConfig { version: 1, bump, admin, ..ProgramConfig::default() };
+        let mut data = vec![0; ProgramConfig::LEN];
+        config.pack(&mut data).unwrap();
+        data.truncate(ProgramConfig::V1_LEN);
+        let mut accounts = vec![Account::signer(admin), Account::new(address, PROGRAM_ID, data)];
+
+        process(&mut accounts, &ix_data(IX_SET_PAUSED, &[1])).unwrap();
+        assert_eq!(accounts[1].data.len(), ProgramConfig::V1_LEN);
+        let config: ProgramConfig = accounts[1].unpack();
+        assert!(config.paused);
+        assert_eq!(config.version, 1);
+    }
+}
@@ -0,0 +1,363 @@
+use poseidon_state::{
+    AccountState, BatchProcessed, BatchResult, Event, PoseidonError, RebalanceConfig,
+};
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    entrypoint::ProgramResult,
+    msg,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    rent::Rent,
+    system_program,
+    sysvar::Sysvar,
+};
+
+use crate::{admin::require_not_paused, disable_position, enable_position, EnableParams};
+
+/// Enable many positions with the same settings. Each position succeeds or
+/// fails on its own; results are reported in a `BatchProcessed` event. A
+/// failed CPI aborts the whole transaction, so positions whose config the
+/// system program could not create or grow are failed before the call.
+pub fn process_enable_batch(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    data: &[u8],
+) -> ProgramResult {
+    // Accounts: [owner, system_program, program_config_pda,
+    //            (config_pda, position_mint[, position_token_account, dex_position])...]
+    // The bracketed pair is required per position for NFT protocols (Orca/Raydium).
+    let iter = &mut accounts.iter();
+    let owner = next_account_info(iter)?;
+    let system_program = next_account_info(iter)?;
+    let program_config_account = next_account_info(iter)?;
+
+    if !owner.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if !owner.is_writable || !system_program::check_id(system_program.key) {
+        return Err(ProgramError::InvalidArgument);
+    }
+    let program_config = require_not_paused(program_id, program_config_account)?;
+
+    // Data: position_mints(4 + 32 * n) + slippage(2) + min_yield(2) + protocol(1)
+    let (mints, rest) = parse_mints(data)?;
+    let params = EnableParams::parse(rest)?;
+    params.validate(&program_config)?;
+
+    let stride = if params.protocol.is_nft() { 4 } else { 2 };
+    let positions = position_accounts(iter.as_slice(), &mints, stride)?;
+
+    let mut results = Vec::with_capacity(mints.len());
+    for (mint, accounts) in mints.iter().zip(positions) {
+        let result = check_config_funding(owner, &accounts[0]).and_then(|()| {
+            enable_position(
+                program_id,
+                owner,
+                system_program,
+                &program_config,
+                &params,
+                &accounts[0],
+                &accounts[1],
+                accounts.get(2),
+                accounts.get(3),
+            )
+        });
+        results.push(batch_result(mint, result));
+    }
+
+    report(owner.key, BatchProcessed::ACTION_ENABLE, results);
+    Ok(())
+}
+
+/// Close many configs at once, refunding rent to the owner. Results are
+/// reported per position like `enable_batch`.
+pub fn process_disable_batch(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    data: &[u8],
+) -> ProgramResult {
+    // Accounts: [owner, (config_pda, position_mint)...]
+    let iter = &mut accounts.iter();
+    let owner = next_account_info(iter)?;
+
+    if !owner.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    // Data: position_mints(4 + 32 * n)
+    let (mints, _) = parse_mints(data)?;
+    let positions = position_accounts(iter.as_slice(), &mints, 2)?;
+
+    let mut results = Vec::with_capacity(mints.len());
+    for (mint, accounts) in mints.iter().zip(positions) {
+        let result = disable_position(program_id, owner, &accounts[0], &accounts[1]);
+        results.push(batch_result(mint, result));
+    }
+
+    report(owner.key, BatchProcessed::ACTION_DISABLE, results);
+    Ok(())
+}
+
+/// Check the system program will accept creating `config_account`, or
+/// topping it up to the current layout, with `owner` paying.
+fn check_config_funding(owner: &AccountInfo, config_account: &AccountInfo) -> ProgramResult {
+    if !config_account.is_writable {
+        msg!("Config {} is not writable", config_account.key);
+        return Err(ProgramError::InvalidArgument);
+    }
+    let rent = Rent::get()?.minimum_balance(RebalanceConfig::LEN);
+    let required = if config_account.data_is_empty() {
+        // create_account refuses an address that already holds lamports
+        if config_account.lamports() > 0 {
+            msg!("Config {} is already funded", config_account.key);
+            return Err(ProgramError::AccountAlreadyInitialized);
+        }
+        rent
+    } else {
+        rent.saturating_sub(config_account.lamports())
+    };
+    if owner.lamports() < required {
+        msg!("Owner needs {} lamports for config {}", required, config_account.key);
+        return Err(ProgramError::InsufficientFunds);
+    }
+    Ok(())
+}
+
+/// Parse a Borsh `Vec<Pubkey>` of position mints, returning the remaining data.
+fn parse_mints(data: &[u8]) -> Result<(Vec<Pubkey>, &[u8]), ProgramError> {
+    if data.len() < 4 {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    let count = u32::from_le_bytes([data[0], data[1], data[2], data[3]]) as usize;
+    if count == 0 || count > BatchProcessed::MAX_POSITIONS {
+        return Err(PoseidonError::InvalidBatchSize.into());
+    }
+    let end = 4 + 32 * count;
+    if data.len() < end {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    let mints = data[4..end]
+        .chunks_exact(32)
+        .map(|chunk| Pubkey::try_from(chunk).unwrap())
+        .collect();
+    Ok((mints, &data[end..]))
+}
+
+/// Split the per-position accounts into groups of `stride`, checking each
+/// group's position_mint (second account) against the mint list.
+fn position_accounts<'b, 'a>(
+    accounts: &'b [AccountInfo<'a>],
+    mints: &[Pubkey],
+    stride: usize,
+) -> Result<core::slice::ChunksExact<'b, AccountInfo<'a>>, ProgramError> {
+    if accounts.len() != mints.len() * stride {
+        msg!("Expected {} accounts per position", stride);
+        return Err(PoseidonError::InvalidPositionAccounts.into());
+    }
+    let groups = accounts.chunks_exact(stride);
+    for (mint, group) in mints.iter().zip(groups.clone()) {
+        if group[1].key != mint {
+            msg!("Account {} does not match position {}", group[1].key, mint);
+            return Err(PoseidonError::InvalidPositionAccounts.into());
+        }
+    }
+    Ok(groups)
+}
+
+fn batch_result(mint: &Pubkey, result: ProgramResult) -> BatchResult {
+    let code = match result {
+        Ok(()) => 0,
+        Err(e) => {
+            msg!("Position {} failed: {}", mint, e);
+            u64::from(e)
+        }
+    };
+    BatchResult { position_mint: *mint, code }
+}
+
+fn report(owner: &Pubkey, action: u8, results: Vec<BatchResult>) {
+    let succeeded = results.iter().filter(|r| r.code == 0).count();
+    msg!("Batch processed: {}/{} positions succeeded", succeeded, results.len());
+    BatchProcessed { owner: *owner, action, results }.emit();
+}
+
+#[cfg(test)]
+mod tests {
+    use poseidon_state::{position::ORCA_WHIRLPOOL_PROGRAM_ID, Protocol};
+
+    use super::*;
+    use crate::{test_utils::*, IX_DISABLE_BATCH, IX_ENABLE_BATCH};
+
+    /// An Orca position held by `owner`: config address, mint, NFT token
+    /// account and position PDA.
+    fn position(owner: Pubkey) -> [Account; 4] {
+        let mint_key = Pubkey::new_unique();
+        let config = RebalanceConfig::find_address(&owner, &mint_key, &PROGRAM_ID).0;
+        let seeds: &[&[u8]] = &[b"position", mint_key.as_ref()];
+        let dex_position = Pubkey::find_program_address(seeds, &ORCA_WHIRLPOOL_PROGRAM_ID).0;
+        [
+            Account::empty(config),
+            mint(mint_key, 1, 0).readonly(),
+            token_account(Pubkey::new_unique(), mint_key, owner, 1).readonly(),
+            Account::new(dex_position, ORCA_WHIRLPOOL_PROGRAM_ID, vec![0; 216]).readonly(),
+        ]
+    }
+
+    fn mint_list(mints: &[Pubkey]) -> Vec<u8> {
+        let mut data = (mints.len() as u32).to_le_bytes().to_vec();
+        for mint in mints {
+            data.extend_from_slice(mint.as_ref());
+        }
+        data
+    }
+
+    fn enable_batch(owner: Account, positions: &[[Account; 4]]) -> (Vec<Account>, Vec<u8>) {
+        let mut accounts = vec![
+            owner,
+            Account::program(system_program::id()),
+            program_config(Pubkey::new_unique(), &[]).readonly(),
+        ];
+        accounts.extend(positions.iter().flatten().cloned());
+        let mints: Vec<Pubkey> = positions.iter().map(|position| position[1].key).collect();
+        let mut args = mint_list(&mints);
+        args.extend_from_slice(&100u16.to_le_bytes());
+        args.extend_from_slice(&0u16.to_le_bytes());
+        args.push(Protocol::OrcaWhirlpool as u8);
+        (accounts, ix_data(IX_ENABLE_BATCH, &args))
+    }
+
+    fn code(error: impl Into<ProgramError>) -> u64 {
+        u64::from(error.into())
+    }
+
+    fn result(position: &[Account; 4], code: u64) -> BatchResult {
+        BatchResult { position_mint: position[1].key, code }
+    }
+
+    #[test]
+    fn enable_batch_skips_failing_positions() {
+        let owner = Pubkey::new_unique();
+        let held = position(owner);
+        let mut sold = position(owner);
+        sold[2] = token_account(Pubkey::new_unique(), sold[1].key, Pubkey::new_unique(), 1);
+        let mut prefunded = position(owner);
+        prefunded[0].lamports = 1;
+        // The system program would reject the config, failing the whole batch
+        let mut readonly = position(owner);
+        readonly[0].is_writable = false;
+
+        let positions = [held, sold, prefunded, readonly];
+        let (mut accounts, data) = enable_batch(Account::signer(owner), &positions);
+        process(&mut accounts, &data).unwrap();
+
+        let config: RebalanceConfig = accounts[3].unpack();
+        assert!(config.enabled);
+        assert_eq!(config.protocol, Protocol::OrcaWhirlpool);
+        for index in [7, 11, 15] {
+            assert!(accounts[index].data.is_empty());
+        }
+        assert!(emitted(&BatchProcessed {
+            owner,
+            action: BatchProcessed::ACTION_ENABLE,
+            results: vec![
+                result(&positions[0], 0),
+                result(&positions[1], code(PoseidonError::PositionNotOwned)),
+                result(&positions[2], code(ProgramError::AccountAlreadyInitialized)),
+                result(&positions[3], code(ProgramError::InvalidArgument)),
+            ],
+        }));
+    }
+
+    #[test]
+    fn enable_batch_fails_positions_the_owner_cannot_fund() {
+        let owner = Pubkey::new_unique();
+        let rent = Rent::default().minimum_balance(RebalanceConfig::LEN);
+        let positions = [position(owner), position(owner)];
+        let payer = Account { lamports: rent + 1, ..Account::signer(owner) };
+        let (mut accounts, data) = enable_batch(payer, &positions);
+        process(&mut accounts, &data).unwrap();
+
+        assert_eq!(accounts[0].lamports, 1);
+        assert!(!accounts[3].data.is_empty());
+        assert!(accounts[7].data.is_empty());
+        assert!(emitted(&BatchProcessed {
+            owner,
+            action: BatchProcessed::ACTION_ENABLE,
+            results: vec![
+                result(&positions[0], 0),
+                result(&positions[1], code(ProgramError::InsufficientFunds)),
+            ],
+        }));
+    }
+
+    #[test]
+    fn enable_batch_checks_the_shared_accounts() {
+        let owner = Pubkey::new_unique();
+        let positions = [position(owner)];
+
+        let (mut accounts, data) = enable_batch(Account::signer(owner).unsigned(), &positions);
+        assert_eq!(process(&mut accounts, &data), Err(ProgramError::MissingRequiredSignature));
+
+        let (mut accounts, data) = enable_batch(Account::signer(owner).readonly(), &positions);
+        assert_eq!(process(&mut accounts, &data), Err(ProgramError::InvalidArgument));
+
+        let (mut accounts, data) = enable_batch(Account::signer(owner), &positions);
+        accounts[1] = Account::program(Pubkey::new_unique());
+        assert_eq!(process(&mut accounts, &data), Err(ProgramError::InvalidArgument));
+
+        let (mut accounts, data) = enable_batch(Account::signer(owner), &positions);
+        accounts[2] = paused(accounts[2].clone());
+        assert_eq!(process(&mut accounts, &data), Err(PoseidonError::ProgramPaused.into()));
+
+        // Every position needs its four accounts, in mint order
+        let (mut accounts, data) = enable_batch(Account::signer(owner), &positions);
+        accounts.pop();
+        let invalid_accounts = Err(PoseidonError::InvalidPositionAccounts.into());
+        assert_eq!(process(&mut accounts, &data), invalid_accounts);
+        let (mut accounts, data) = enable_batch(Account::signer(owner), &positions);
+        accounts[4].key = Pubkey::new_unique();
+        assert_eq!(process(&mut accounts, &data), invalid_accounts);
+    }
+
+    #[test]
+    fn batch_size_is_bounded() {
+        let owner = Pubkey::new_unique();
+        for count in [0, BatchProcessed::MAX_POSITIONS + 1] {
+            let positions: Vec<[Account; 4]> = (0..count).map(|_| position(owner)).collect();
+            let (mut accounts, data) = enable_batch(Account::signer(owner), &positions);
+            assert_eq!(process(&mut accounts, &data), Err(PoseidonError::InvalidBatchSize.into()));
+        }
+    }
+
+    #[test]
+    fn disable_batch_skips_configs_the_owner_does_not_have() {
+        let owner = Pubkey::new_unique();
+        let [_, mint_a, ..] = position(owner);
+        let [_, mint_b, ..] = position(owner);
+        let config = rebalance_config(owner, mint_a.key, Protocol::OrcaWhirlpool);
+        // Someone else's config for the second position
+        let other = rebalance_config(Pubkey::new_unique(), mint_b.key, Protocol::OrcaWhirlpool);
+        let rent = config.lamports;
+
+        let mints = [mint_a.key, mint_b.key];
+        let data = ix_data(IX_DISABLE_BATCH, &mint_list(&mints));
+        let mut accounts = vec![Account::signer(owner), config, mint_a, other, mint_b];
+        let balance = accounts[0].lamports;
+        process(&mut accounts, &data).unwrap();
+
+        assert!(accounts[1].is_closed());
+        assert_eq!(accounts[0].lamports, balance + rent);
+        assert!(!accounts[3].is_closed());
+        assert!(emitted(&BatchProcessed {
+            owner,
+            action: BatchProcessed::ACTION_DISABLE,
+            results: vec![
+                BatchResult { position_mint: mints[0], code: 0 },
+                BatchResult {
+                    position_mint: mints[1],
+                    code: code(PoseidonError::InvalidConfigAddress),
+                },
+            ],
+        }));
+    }
+}
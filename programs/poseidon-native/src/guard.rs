@@ -0,0 +1,399 @@
+use poseidon_state::{
+    mint_decimals, pool_price, position_pool, token_balance, verify_position_owner, AccountState,
+    OracleFeed, OraclePrice, PoseidonError, PositionRange, ProgramConfig, Protocol, SlippageGuard,
+    GUARD_SEED,
+};
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    clock::Clock,
+    entrypoint::ProgramResult,
+    msg,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    sysvar::{
+        instructions::{load_current_index_checked, load_instruction_at_checked},
+        Sysvar,
+    },
+};
+
+use crate::{
+    admin::{load_program_config, require_not_paused},
+    create_pda_account, load_active_config, load_config, profile, resize_account,
+    IX_BEGIN_REBALANCE, IX_END_REBALANCE,
+};
+
+/// Snapshot the owner's holdings of the pool's two tokens, in their wallet
+/// and in the old and new positions, and the oracle price before the
+/// agent's DEX calls. The same transaction must end with `end_rebalance`
+/// for this guard.
+pub fn process_begin_rebalance(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    // Accounts: [agent, config_pda, guard_pda, system_program, program_config_pda, pool,
+    //            dex_position, new_dex_position, owner_token_a, mint_a, owner_token_b, mint_b,
+    //            oracle, oracle_feed_pda, instructions_sysvar]
+    // new_dex_position is the position account the DEX instructions open or
+    // fund, and need not exist yet. mint_a/b are the pool's mints and
+    // owner_token_a/b the owner's associated token accounts for them; oracle
+    // is a Pyth price update for the feed oracle_feed_pda sets for the pair.
+    let iter = &mut accounts.iter();
+    let agent = next_account_info(iter)?;
+    let config_account = next_account_info(iter)?;
+    let guard_account = next_account_info(iter)?;
+    let system_program = next_account_info(iter)?;
+    let program_config_account = next_account_info(iter)?;
+    let pool = next_account_info(iter)?;
+    let dex_position = next_account_info(iter)?;
+    let new_dex_position = next_account_info(iter)?;
+    let token_a = next_account_info(iter)?;
+    let mint_a = next_account_info(iter)?;
+    let token_b = next_account_info(iter)?;
+    let mint_b = next_account_info(iter)?;
+    let oracle = next_account_info(iter)?;
+    let oracle_feed = next_account_info(iter)?;
+    let instructions_sysvar = next_account_info(iter)?;
+
+    if !agent.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    let program_config = require_not_paused(program_id, program_config_account)?;
+    if !program_config.is_agent(agent.key) {
+        msg!("Unauthorized agent {}", agent.key);
+        return Err(PoseidonError::UnauthorizedAgent.into());
+    }
+    let config = load_active_config(program_id, config_account)?;
+    if !config.protocol.is_nft() {
+        msg!("{:?} positions cannot be valued by the guard", config.protocol);
+        return Err(PoseidonError::GuardProtocolUnsupported.into());
+    }
+    pool_mints(
+        &program_config,
+        config.protocol,
+        &config.position_mint,
+        (pool, dex_position),
+        (mint_a, mint_b),
+    )?;
+    let mut positions = vec![PositionRange::load(config.protocol, dex_position)?];
+    if new_dex_position.key != dex_position.key {
+        if !new_dex_position.data_is_empty() && new_dex_position.owner != pool.owner {
+            return Err(PoseidonError::InvalidPositionAccount.into());
+        }
+        positions.extend(guarded_range(config.protocol, new_dex_position, pool.key)?);
+    }
+
+    // Derive guard PDA: ["slippage_guard", config]
+    let (expected_pda, bump) = SlippageGuard::find_address(config_account.key, program_id);
+    if *guard_account.key != expected_pda {
+        return Err(PoseidonError::InvalidGuardAccount.into());
+    }
+    require_end_instruction(program_id, guard_account.key, instructions_sysvar)?;
+
+    let mut guard = if guard_account.data_is_empty() {
+        create_pda_account(
+            program_id,
+            agent,
+            guard_account,
+            SlippageGuard::LEN,
+            system_program,
+            &[GUARD_SEED, config_account.key.as_ref(), &[bump]],
+        )?;
+        SlippageGuard {
+            version: SlippageGuard::VERSION,
+            bump,
+            config: *config_account.key,
+            ..SlippageGuard::default()
+        }
+    } else {
+        // Guards from before positions were valued are grown in place
+        if guard_account.data_len() < SlippageGuard::LEN {
+            resize_account(guard_account, agent, system_program, SlippageGuard::LEN)?;
+        }
+        let mut guard = load_guard(program_id, config_account.key, guard_account)?;
+        guard.version = SlippageGuard::VERSION;
+        guard
+    };
+
+    let feed = load_oracle_feed(program_id, oracle_feed, mint_a.key, mint_b.key)?;
+    let price = OraclePrice::load(oracle, &feed.feed_id)?;
+    let now = Clock::get()?.unix_timestamp;
+    price.check_fresh(now, program_config.oracle_max_age())?;
+    guard.snapshot(&config.owner, (token_a, mint_a), (token_b, mint_b), &price, &positions, now)?;
+    guard.pool = *pool.key;
+    guard.position = *dex_position.key;
+    guard.new_position = *new_dex_position.key;
+    guard.pack(&mut guard_account.try_borrow_mut_data()?)?;
+
+    msg!(
+        "Rebalance guard set for position {}: {} / {} at {}e{}",
+        config.position_mint,
+        guard.amount_a,
+        guard.amount_b,
+        guard.price,
+        guard.expo
+    );
+    Ok(())
+}
+
+/// Value the owner's holdings again at the snapshot price and fail the
+/// transaction if the loss exceeds the config's effective `max_slippage_bps`.
+pub fn process_end_rebalance(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    // Accounts: [agent, config_pda, guard_pda, program_config_pda, owner_token_a,
+    //            owner_token_b, dex_position, new_dex_position, new_position_mint,
+    //            new_position_token_account, (user_profile_pda)]
+    // The positions are the ones begin_rebalance valued. Once opened, the new
+    // position must be held by the owner: new_position_mint is its NFT and
+    // new_position_token_account the owner's account holding it.
+    // user_profile_pda is required when the config inherits any setting.
+    let iter = &mut accounts.iter();
+    let agent = next_account_info(iter)?;
+    let config_account = next_account_info(iter)?;
+    let guard_account = next_account_info(iter)?;
+    let program_config_account = next_account_info(iter)?;
+    let token_a = next_account_info(iter)?;
+    let token_b = next_account_info(iter)?;
+    let dex_position = next_account_info(iter)?;
+    let new_dex_position = next_account_info(iter)?;
+    let new_position_mint = next_account_info(iter)?;
+    let new_position_token_account = next_account_info(iter)?;
+    let profile_account = next_account_info(iter).ok();
+
+    if !agent.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    let program_config = load_program_config(program_id, program_config_account)?;
+    if !program_config.is_agent(agent.key) {
+        msg!("Unauthorized agent {}", agent.key);
+        return Err(PoseidonError::UnauthorizedAgent.into());
+    }
+    let config = load_config(program_id, config_account)?;
+
+    let mut guard = load_guard(program_id, config_account.key, guard_account)?;
+    if !guard.active {
+        return Err(PoseidonError::GuardNotActive.into());
+    }
+    if *token_a.key != guard.token_a || *token_b.key != guard.token_b {
+        return Err(PoseidonError::InvalidTokenAccount.into());
+    }
+    if *dex_position.key != guard.position || *new_dex_position.key != guard.new_position {
+        return Err(PoseidonError::InvalidPositionAccount.into());
+    }
+    if !dex_position.data_is_empty()
+        && !program_config.accepts_dex(config.protocol, dex_position.owner)
+    {
+        return Err(PoseidonError::DexProgramNotAccepted.into());
+    }
+    let mut positions: Vec<PositionRange> =
+        guarded_range(config.protocol, dex_position, &guard.pool)?.into_iter().collect();
+    if new_dex_position.key != dex_position.key && !new_dex_position.data_is_empty() {
+        let pool = position_pool(
+            &program_config,
+            config.protocol,
+            new_position_mint.key,
+            new_dex_position,
+        )?;
+        if pool != guard.pool {
+            return Err(PoseidonError::InvalidPoolAccount.into());
+        }
+        verify_position_owner(
+            &config.owner,
+            config.protocol,
+            new_position_mint,
+            Some(new_position_token_account),
+        )?;
+        positions.extend(guarded_range(config.protocol, new_dex_position, &guard.pool)?);
+    }
+
+    let profile = match profile_account {
+        Some(account) => Some(profile::load_profile(program_id, &config.owner, account)?),
+        None => None,
+    };
+    let settings = config.effective(profile.as_ref())?;
+
+    let wallet = (
+        token_balance(&config.owner, token_a, None)?,
+        token_balance(&config.owner, token_b, None)?,
+    );
+    let (amount_a, amount_b) = guard.holdings(wallet, &positions)?;
+    let loss_bps = guard.loss_bps(amount_a, amount_b)?;
+    if loss_bps > u64::from(settings.max_slippage_bps) {
+        msg!(
+            "Position {} lost {} bps of value, max {} bps",
+            config.position_mint,
+            loss_bps,
+            settings.max_slippage_bps
+        );
+        return Err(PoseidonError::SlippageExceeded.into());
+    }
+
+    guard.active = false;
+    guard.pack(&mut guard_account.try_borrow_mut_data()?)?;
+
+    msg!("Rebalance guard passed for position {}: {} bps lost", config.position_mint, loss_bps);
+    Ok(())
+}
+
+/// Accounts `check_pool_price` reads.
+pub struct PriceAccounts<'a, 'b> {
+    pub oracle: &'a AccountInfo<'b>,
+    pub oracle_feed: &'a AccountInfo<'b>,
+    pub pool: &'a AccountInfo<'b>,
+    pub dex_position: &'a AccountInfo<'b>,
+    pub mint_a: &'a AccountInfo<'b>,
+    pub mint_b: &'a AccountInfo<'b>,
+}
+
+/// Refuse to act on a stale oracle price, on a price from another feed than
+/// the pair's, or on a pool whose price has moved outside the configured band
+/// around it. Returns the Q64.64 square root of the oracle price, in token B
+/// base units per token A base unit, and the pool's deviation from it in bps.
+pub fn check_pool_price(
+    program_id: &Pubkey,
+    program_config: &ProgramConfig,
+    protocol: Protocol,
+    position_mint: &Pubkey,
+    accounts: PriceAccounts,
+) -> Result<(u128, u64), ProgramError> {
+    let PriceAccounts { oracle, oracle_feed, pool, dex_position, mint_a, mint_b } = accounts;
+
+    let price =
+        pool_mints(program_config, protocol, position_mint, (pool, dex_position), (mint_a, mint_b))?;
+
+    let feed = load_oracle_feed(program_id, oracle_feed, mint_a.key, mint_b.key)?;
+    let oracle_price = OraclePrice::load(oracle, &feed.feed_id)?;
+    oracle_price.check_fresh(Clock::get()?.unix_timestamp, program_config.oracle_max_age())?;
+    let (decimals_a, decimals_b) = (mint_decimals(mint_a)?, mint_decimals(mint_b)?);
+    let deviation_bps = oracle_price.deviation_bps(price, decimals_a, decimals_b);
+    if deviation_bps > u64::from(program_config.oracle_max_deviation()) {
+        msg!(
+            "Pool {} is {} bps from the oracle price, max {} bps",
+            pool.key,
+            deviation_bps,
+            program_config.oracle_max_deviation()
+        );
+        return Err(PoseidonError::PriceDeviationExceeded.into());
+    }
+    Ok((oracle_price.sqrt_price_x64(decimals_a, decimals_b)?, deviation_bps))
+}
+
+/// Check `pool` is the position's pool and `mints` its two mints, in order.
+/// Returns the pool price.
+fn pool_mints(
+    program_config: &ProgramConfig,
+    protocol: Protocol,
+    position_mint: &Pubkey,
+    (pool, dex_position): (&AccountInfo, &AccountInfo),
+    (mint_a, mint_b): (&AccountInfo, &AccountInfo),
+) -> Result<f64, ProgramError> {
+    let expected_pool = position_pool(program_config, protocol, position_mint, dex_position)?;
+    if *pool.key != expected_pool || pool.owner != dex_position.owner {
+        return Err(PoseidonError::InvalidPoolAccount.into());
+    }
+    let (price, pool_mint_a, pool_mint_b) = pool_price(protocol, pool)?;
+    if *mint_a.key != pool_mint_a || *mint_b.key != pool_mint_b {
+        msg!("Mints must be the pool's: {} / {}", pool_mint_a, pool_mint_b);
+        return Err(PoseidonError::InvalidTokenAccount.into());
+    }
+    Ok(price)
+}
+
+/// Range of a position the guard values, or `None` while it does not exist:
+/// not yet opened, or closed by the DEX. It must be in `pool`.
+fn guarded_range(
+    protocol: Protocol,
+    position: &AccountInfo,
+    pool: &Pubkey,
+) -> Result<Option<PositionRange>, ProgramError> {
+    if position.data_is_empty() {
+        return Ok(None);
+    }
+    let range = PositionRange::load(protocol, position)?;
+    if range.pool != *pool {
+        msg!("Position {} is not in pool {}", position.key, pool);
+        return Err(PoseidonError::InvalidPositionAccount.into());
+    }
+    Ok(Some(range))
+}
+
+/// Load the feed the admin set for a token pair, verifying its address.
+pub fn load_oracle_feed(
+    program_id: &Pubkey,
+    account: &AccountInfo,
+    mint_a: &Pubkey,
+    mint_b: &Pubkey,
+) -> Result<OracleFeed, ProgramError> {
+    if account.owner != program_id {
+        msg!("No oracle feed set for {} / {}", mint_a, mint_b);
+        return Err(PoseidonError::InvalidOracleFeed.into());
+    }
+    let feed = OracleFeed::unpack(&account.try_borrow_data()?)
+        .map_err(|_| PoseidonError::InvalidOracleFeed)?;
+    let (expected_pda, _) = OracleFeed::find_address(mint_a, mint_b, program_id);
+    if *account.key != expected_pda || feed.mint_a != *mint_a || feed.mint_b != *mint_b {
+        return Err(PoseidonError::InvalidOracleFeed.into());
+    }
+    Ok(feed)
+}
+
+fn load_guard(
+    program_id: &Pubkey,
+    config: &Pubkey,
+    account: &AccountInfo,
+) -> Result<SlippageGuard, ProgramError> {
+    if account.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    let guard = SlippageGuard::unpack(&account.try_borrow_data()?)
+        .map_err(|_| PoseidonError::InvalidGuardAccount)?;
+    if guard.config != *config {
+        return Err(PoseidonError::InvalidGuardAccount.into());
+    }
+    Ok(guard)
+}
+
+/// Require a `begin_rebalance` for the config's guard before the first DEX
+/// instruction of `dex_span` and an `end_rebalance` after the last, so the
+/// agent's DEX calls are always checked for slippage.
+pub fn require_guard_around(
+    program_id: &Pubkey,
+    config: &Pubkey,
+    instructions_sysvar: &AccountInfo,
+    (first, last): (usize, usize),
+) -> ProgramResult {
+    let (guard, _) = SlippageGuard::find_address(config, program_id);
+    let (mut begun, mut ended) = (false, false);
+    let mut index = 0;
+    while let Ok(ix) = load_instruction_at_checked(index, instructions_sysvar) {
+        if ix.program_id == *program_id
+            && ix.accounts.get(2).is_some_and(|meta| meta.pubkey == guard)
+        {
+            let disc = ix.data.get(..8);
+            begun |= index < first && disc == Some(&IX_BEGIN_REBALANCE[..]);
+            ended |= index > last && disc == Some(&IX_END_REBALANCE[..]);
+        }
+        index += 1;
+    }
+    if !begun || !ended {
+        return Err(PoseidonError::RebalanceNotGuarded.into());
+    }
+    Ok(())
+}
+
+/// Require an `end_rebalance` for `guard` after the current instruction, so a
+/// snapshot can never be left unchecked.
+fn require_end_instruction(
+    program_id: &Pubkey,
+    guard: &Pubkey,
+    instructions_sysvar: &AccountInfo,
+) -> ProgramResult {
+    let current = load_current_index_checked(instructions_sysvar)
+        .map_err(|_| PoseidonError::InvalidInstructionsSysvar)?;
+    let mut index = usize::from(current) + 1;
+    while let Ok(ix) = load_instruction_at_checked(index, instructions_sysvar) {
+        if ix.program_id == *program_id
+            && ix.data.get(..8) == Some(&IX_END_REBALANCE[..])
+            && ix.accounts.get(2).is_some_and(|meta| meta.pubkey == *guard)
+        {
+            return Ok(());
+        }
+        index += 1;
+    }
+    Err(PoseidonError::EndRebalanceMissing.into())
+}
@@ -0,0 +1,221 @@
+use poseidon_state::{
+    AccountState, ConfigMigrated, Event, LegacyRebalanceConfig, PoseidonError, Protocol,
+    RebalanceConfig, REBALANCE_SEED,
+};
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    clock::Clock,
+    entrypoint::ProgramResult,
+    msg,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    sysvar::Sysvar,
+};
+
+use crate::{close_account, create_pda_account};
+
+/// Split a legacy owner-level config into per-position configs, one for each
+/// position mint passed in, then close the legacy account. Holding the mints
+/// is not checked here, so the new configs keep `Protocol::Unknown` and agent
+/// actions refuse them until the owner proves it with `enable_rebalance`.
+pub fn process_migrate_legacy(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    // Accounts: [owner, legacy_config_pda, system_program, (position_mint, config_pda)...]
+    let iter = &mut accounts.iter();
+    let owner = next_account_info(iter)?;
+    let legacy_account = next_account_info(iter)?;
+    let system_program = next_account_info(iter)?;
+    let positions = iter.as_slice();
+    let pairs = positions.chunks_exact(2);
+
+    if !owner.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    if positions.is_empty() || !pairs.remainder().is_empty() {
+        return Err(PoseidonError::InvalidPositionAccounts.into());
+    }
+
+    // Verify legacy PDA: ["rebalance", owner]
+    let (expected_pda, _bump) = LegacyRebalanceConfig::find_address(owner.key, program_id);
+    if *legacy_account.key != expected_pda {
+        return Err(PoseidonError::InvalidLegacyConfig.into());
+    }
+    if legacy_account.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    let legacy_data = legacy_account.try_borrow_data()?;
+    if legacy_data.len() != LegacyRebalanceConfig::LEN {
+        return Err(PoseidonError::InvalidLegacyConfig.into());
+    }
+    let legacy = LegacyRebalanceConfig::unpack(&legacy_data)
+        .map_err(|_| PoseidonError::InvalidLegacyConfig)?;
+    drop(legacy_data);
+    if legacy.owner != *owner.key {
+        return Err(PoseidonError::ConfigOwnerMismatch.into());
+    }
+
+    let now = Clock::get()?.unix_timestamp;
+
+    for pair in pairs {
+        let position_mint = &pair[0];
+        let config_account = &pair[1];
+
+        let (expected_pda, bump) =
+            RebalanceConfig::find_address(owner.key, position_mint.key, program_id);
+        if *config_account.key != expected_pda {
+            msg!("Invalid PDA for position {}", position_mint.key);
+            return Err(PoseidonError::InvalidConfigAddress.into());
+        }
+        if !config_account.data_is_empty() {
+            msg!("Config already exists for position {}", position_mint.key);
+            return Err(PoseidonError::ConfigAlreadyExists.into());
+        }
+
+        create_pda_account(
+            program_id,
+            owner,
+            config_account,
+            RebalanceConfig::LEN,
+            system_program,
+            &[REBALANCE_SEED, owner.key.as_ref(), position_mint.key.as_ref(), &[bump]],
+        )?;
+        let config = RebalanceConfig {
+            owner: *owner.key,
+            position_mint: *position_mint.key,
+            enabled: legacy.enabled,
+            max_slippage_bps: legacy.max_slippage_bps,
+            min_yield_improvement_bps: legacy.min_yield_improvement_bps,
+            protocol: Protocol::Unknown,
+            created_at: legacy.created_at,
+            updated_at: now,
+            version: RebalanceConfig::VERSION,
+            bump,
+            ..RebalanceConfig::default()
+        };
+        config.pack(&mut config_account.try_borrow_mut_data()?)?;
+
+        ConfigMigrated::new(*config_account.key, &config, ConfigMigrated::FROM_LEGACY, now).emit();
+    }
+
+    // Close legacy account — refund rent to owner
+    close_account(legacy_account, owner)?;
+
+    msg!("Legacy config for {} migrated to {} positions", owner.key, positions.len() / 2);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_program::system_program;
+
+    use super::*;
+    use crate::{test_utils::*, IX_MIGRATE_LEGACY_CONFIG};
+
+    fn legacy_config(owner: Pubkey) -> Account {
+        let legacy = LegacyRebalanceConfig {
+            owner,
+            enabled: true,
+            max_slippage_bps: 80,
+            min_yield_improvement_bps: 20,
+            created_at: NOW - 1_000,
+            updated_at: NOW - 1_000,
+        };
+        Account::state(LegacyRebalanceConfig::find_address(&owner, &PROGRAM_ID).0, &legacy)
+    }
+
+    /// Accounts splitting `owner`'s legacy config into configs for `mints`.
+    fn migrate_accounts(owner: Pubkey, mints: &[Pubkey]) -> Vec<Account> {
+        let mut accounts = vec![
+            Account::signer(owner),
+            legacy_config(owner),
+            Account::program(system_program::id()),
+        ];
+        for mint in mints {
+            let config = RebalanceConfig::find_address(&owner, mint, &PROGRAM_ID).0;
+            accounts.push(Account::new(*mint, Pubkey::new_unique(), Vec::new()).readonly());
+            accounts.push(Account::empty(config));
+        }
+        accounts
+    }
+
+    #[test]
+    fn legacy_config_splits_into_unverified_per_position_configs() {
+        let owner = Pubkey::new_unique();
+        let mints = [Pubkey::new_unique(), Pubkey::new_unique()];
+        let mut accounts = migrate_accounts(owner, &mints);
+        let (balance, legacy_rent) = (accounts[0].lamports, accounts[1].lamports);
+        process(&mut accounts, &ix_data(IX_MIGRATE_LEGACY_CONFIG, &[])).unwrap();
+
+        assert!(accounts[1].is_closed());
+        for (mint, account) in mints.iter().zip(accounts[3..].chunks(2).map(|pair| &pair[1])) {
+            let config: RebalanceConfig = account.unpack();
+            assert_eq!((config.owner, config.position_mint), (owner, *mint));
+            assert!(config.enabled);
+            assert_eq!((config.max_slippage_bps, config.min_yield_improvement_bps), (80, 20));
+            assert_eq!(config.created_at, NOW - 1_000);
+            assert_eq!(config.version, RebalanceConfig::VERSION);
+            // Agents refuse it until the owner proves they hold the position
+            assert_eq!(config.protocol, Protocol::Unknown);
+            let event = ConfigMigrated::new(account.key, &config, ConfigMigrated::FROM_LEGACY, NOW);
+            assert!(emitted(&event));
+        }
+        // The owner pays for the new configs and gets the legacy rent back
+        let rent = 2 * accounts[4].lamports;
+        assert_eq!(accounts[0].lamports, balance + legacy_rent - rent);
+    }
+
+    #[test]
+    fn only_the_owner_splits_their_legacy_config() {
+        let owner = Pubkey::new_unique();
+        let mut accounts = migrate_accounts(owner, &[Pubkey::new_unique()]);
+        accounts[0].is_signer = false;
+        assert_eq!(
+            process(&mut accounts, &ix_data(IX_MIGRATE_LEGACY_CONFIG, &[])),
+            Err(ProgramError::MissingRequiredSignature)
+        );
+
+        // Someone else's legacy config is not at their own legacy address
+        accounts[0] = Account::signer(Pubkey::new_unique());
+        assert_eq!(
+            process(&mut accounts, &ix_data(IX_MIGRATE_LEGACY_CONFIG, &[])),
+            Err(PoseidonError::InvalidLegacyConfig.into())
+        );
+    }
+
+    #[test]
+    fn split_needs_mint_and_config_pairs() {
+        let owner = Pubkey::new_unique();
+        for mints in [&[][..], &[Pubkey::new_unique()]] {
+            let mut accounts = migrate_accounts(owner, mints);
+            if !mints.is_empty() {
+                accounts.pop();
+            }
+            assert_eq!(
+                process(&mut accounts, &ix_data(IX_MIGRATE_LEGACY_CONFIG, &[])),
+                Err(PoseidonError::InvalidPositionAccounts.into())
+            );
+        }
+    }
+
+    #[test]
+    fn split_refuses_positions_that_already_have_a_config() {
+        let owner = Pubkey::new_unique();
+        let mints = [Pubkey::new_unique(), Pubkey::new_unique()];
+        let mut accounts = migrate_accounts(owner, &mints);
+        accounts[6] = rebalance_config(owner, mints[1], Protocol::OrcaWhirlpool);
+        assert_eq!(
+            process(&mut accounts, &ix_data(IX_MIGRATE_LEGACY_CONFIG, &[])),
+            Err(PoseidonError::ConfigAlreadyExists.into())
+        );
+        assert!(!accounts[1].is_closed());
+        assert!(accounts[4].data.is_empty());
+
+        // A config at the wrong address is refused too
+        let mut accounts = migrate_accounts(owner, &mints);
+        accounts[4].key = Pubkey::new_unique();
+        assert_eq!(
+            process(&mut accounts, &ix_data(IX_MIGRATE_LEGACY_CONFIG, &[])),
+            Err(PoseidonError::InvalidConfigAddress.into())
+        );
+    }
+}
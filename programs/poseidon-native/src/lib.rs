@@ -1,10 +1,17 @@
+use poseidon_state::{
+    is_position_closed, pool_current_tick, verify_dex_position, verify_position_owner,
+    verify_rebalance_instructions, AccountState, ConfigMigrated, ConfigUpdate, Event,
+    HistoryEntry, PoseidonError, PositionRange, ProgramConfig, Protocol, RebalanceConfig,
+    RebalanceDisabled, RebalanceEnabled, RebalanceExecuted, RebalanceHistory, RebalanceRecord,
+    StrategyKind, VaultState, HISTORY_SEED, RECORD_SEED, REBALANCE_SEED,
+};
 use solana_program::{
     account_info::{next_account_info, AccountInfo},
     clock::Clock,
     entrypoint,
     entrypoint::ProgramResult,
     msg,
-    program::invoke_signed,
+    program::{invoke, invoke_signed},
     program_error::ProgramError,
     pubkey::Pubkey,
     rent::Rent,
@@ -12,18 +19,52 @@ use solana_program::{
     sysvar::Sysvar,
 };
 
+mod admin;
+mod batch;
+mod guard;
+mod legacy;
+mod profile;
+mod raydium;
+mod transfer;
+mod vault;
+mod whirlpool;
+#[cfg(test)]
+mod test_utils;
+
+use admin::require_not_paused;
+
 entrypoint!(process_instruction);
 
 // Instruction discriminators: sha256("global:<name>")[0..8]
 const IX_ENABLE: [u8; 8] = [94, 247, 51, 161, 142, 177, 235, 11];
 const IX_DISABLE: [u8; 8] = [170, 206, 89, 64, 74, 71, 94, 214];
-
-// Account discriminator: sha256("account:RebalanceConfig")[0..8]
-const ACCOUNT_DISC: [u8; 8] = [111, 187, 136, 118, 41, 244, 175, 141];
-
-// Per-position layout: disc(8) + owner(32) + position_mint(32) + enabled(1) + max_slippage_bps(2) + min_yield_bps(2) + created_at(8) + updated_at(8) = 93
-const ACCOUNT_SIZE: usize = 8 + 32 + 32 + 1 + 2 + 2 + 8 + 8;
-const SEED: &[u8] = b"rebalance";
+const IX_EXECUTE: [u8; 8] = [36, 232, 110, 192, 96, 226, 100, 120];
+const IX_INITIALIZE_PROGRAM_CONFIG: [u8; 8] = [6, 131, 61, 237, 40, 110, 83, 124];
+const IX_ROTATE_ADMIN: [u8; 8] = [123, 96, 122, 175, 190, 137, 229, 207];
+const IX_ADD_AGENT: [u8; 8] = [214, 206, 14, 110, 178, 131, 218, 45];
+const IX_REMOVE_AGENT: [u8; 8] = [126, 25, 90, 199, 104, 237, 225, 130];
+const IX_SET_PAUSED: [u8; 8] = [91, 60, 125, 192, 176, 225, 166, 218];
+const IX_MIGRATE_CONFIG: [u8; 8] = [92, 131, 58, 105, 210, 154, 224, 193];
+const IX_MIGRATE_LEGACY_CONFIG: [u8; 8] = [156, 118, 78, 107, 165, 46, 9, 120];
+const IX_SET_REBALANCE_BOUNDS: [u8; 8] = [151, 115, 97, 250, 46, 152, 148, 142];
+const IX_SET_ENABLED: [u8; 8] = [108, 151, 239, 151, 181, 233, 110, 123];
+const IX_UPDATE_CONFIG: [u8; 8] = [29, 158, 252, 191, 10, 83, 219, 99];
+const IX_ADD_DEX_PROGRAM: [u8; 8] = [93, 84, 194, 23, 226, 195, 215, 195];
+const IX_REMOVE_DEX_PROGRAM: [u8; 8] = [197, 251, 154, 78, 131, 57, 238, 225];
+const IX_TRANSFER_CONFIG: [u8; 8] = [4, 35, 144, 203, 195, 101, 27, 241];
+const IX_FLAG_STALE_CONFIG: [u8; 8] = [56, 159, 167, 149, 147, 102, 194, 253];
+const IX_REAP_CONFIG: [u8; 8] = [55, 90, 109, 55, 119, 234, 135, 47];
+const IX_ENABLE_BATCH: [u8; 8] = [241, 86, 152, 75, 68, 115, 239, 186];
+const IX_DISABLE_BATCH: [u8; 8] = [161, 230, 185, 188, 17, 189, 81, 49];
+const IX_SET_USER_PROFILE: [u8; 8] = [30, 244, 193, 238, 108, 66, 220, 108];
+const IX_CLOSE_USER_PROFILE: [u8; 8] = [242, 80, 248, 79, 81, 251, 65, 113];
+const IX_BEGIN_REBALANCE: [u8; 8] = [231, 83, 87, 166, 123, 155, 58, 182];
+const IX_END_REBALANCE: [u8; 8] = [25, 48, 248, 51, 168, 104, 93, 202];
+const IX_SET_ORACLE_BOUNDS: [u8; 8] = [32, 210, 184, 229, 86, 80, 21, 130];
+const IX_DEPOSIT_POSITION: [u8; 8] = [54, 60, 131, 57, 173, 187, 233, 96];
+const IX_WITHDRAW_POSITION: [u8; 8] = [254, 30, 169, 94, 33, 171, 39, 104];
+const IX_VAULT_REBALANCE: [u8; 8] = [148, 212, 197, 63, 2, 120, 102, 133];
+const IX_SET_ORACLE_FEED: [u8; 8] = [115, 216, 56, 6, 24, 198, 170, 38];
 
 pub fn process_instruction(
     program_id: &Pubkey,
@@ -40,6 +81,60 @@ pub fn process_instruction(
         process_enable(program_id, accounts, &instruction_data[8..])
     } else if disc == IX_DISABLE {
         process_disable(program_id, accounts)
+    } else if disc == IX_EXECUTE {
+        process_execute(program_id, accounts, &instruction_data[8..])
+    } else if disc == IX_INITIALIZE_PROGRAM_CONFIG {
+        admin::process_initialize(program_id, accounts, &instruction_data[8..])
+    } else if disc == IX_ROTATE_ADMIN {
+        admin::process_rotate_admin(program_id, accounts)
+    } else if disc == IX_ADD_AGENT {
+        admin::process_add_agent(program_id, accounts, &instruction_data[8..])
+    } else if disc == IX_REMOVE_AGENT {
+        admin::process_remove_agent(program_id, accounts, &instruction_data[8..])
+    } else if disc == IX_SET_PAUSED {
+        admin::process_set_paused(program_id, accounts, &instruction_data[8..])
+    } else if disc == IX_MIGRATE_CONFIG {
+        process_migrate_config(program_id, accounts)
+    } else if disc == IX_MIGRATE_LEGACY_CONFIG {
+        legacy::process_migrate_legacy(program_id, accounts)
+    } else if disc == IX_SET_REBALANCE_BOUNDS {
+        admin::process_set_rebalance_bounds(program_id, accounts, &instruction_data[8..])
+    } else if disc == IX_SET_ENABLED {
+        process_set_enabled(program_id, accounts, &instruction_data[8..])
+    } else if disc == IX_UPDATE_CONFIG {
+        process_update_config(program_id, accounts, &instruction_data[8..])
+    } else if disc == IX_ADD_DEX_PROGRAM {
+        admin::process_add_dex_program(program_id, accounts, &instruction_data[8..])
+    } else if disc == IX_REMOVE_DEX_PROGRAM {
+        admin::process_remove_dex_program(program_id, accounts, &instruction_data[8..])
+    } else if disc == IX_TRANSFER_CONFIG {
+        transfer::process_transfer_config(program_id, accounts)
+    } else if disc == IX_FLAG_STALE_CONFIG {
+        transfer::process_flag_stale_config(program_id, accounts)
+    } else if disc == IX_REAP_CONFIG {
+        process_reap_config(program_id, accounts)
+    } else if disc == IX_ENABLE_BATCH {
+        batch::process_enable_batch(program_id, accounts, &instruction_data[8..])
+    } else if disc == IX_DISABLE_BATCH {
+        batch::process_disable_batch(program_id, accounts, &instruction_data[8..])
+    } else if disc == IX_SET_USER_PROFILE {
+        profile::process_set_user_profile(program_id, accounts, &instruction_data[8..])
+    } else if disc == IX_CLOSE_USER_PROFILE {
+        profile::process_close_user_profile(program_id, accounts)
+    } else if disc == IX_BEGIN_REBALANCE {
+        guard::process_begin_rebalance(program_id, accounts)
+    } else if disc == IX_END_REBALANCE {
+        guard::process_end_rebalance(program_id, accounts)
+    } else if disc == IX_SET_ORACLE_BOUNDS {
+        admin::process_set_oracle_bounds(program_id, accounts, &instruction_data[8..])
+    } else if disc == IX_DEPOSIT_POSITION {
+        vault::process_deposit_position(program_id, accounts)
+    } else if disc == IX_WITHDRAW_POSITION {
+        vault::process_withdraw_position(program_id, accounts)
+    } else if disc == IX_VAULT_REBALANCE {
+        vault::process_vault_rebalance(program_id, accounts, &instruction_data[8..])
+    } else if disc == IX_SET_ORACLE_FEED {
+        admin::process_set_oracle_feed(program_id, accounts, &instruction_data[8..])
     } else {
         Err(ProgramError::InvalidInstructionData)
     }
@@ -50,89 +145,143 @@ fn process_enable(
     accounts: &[AccountInfo],
     data: &[u8],
 ) -> ProgramResult {
-    // Accounts: [owner, config_pda, position_mint, system_program]
+    // Accounts: [owner, config_pda, position_mint, system_program, program_config_pda,
+    //            (position_token_account, dex_position)]
+    // The trailing pair is required for NFT positions (Orca/Raydium).
     let iter = &mut accounts.iter();
     let owner = next_account_info(iter)?;
     let config_account = next_account_info(iter)?;
     let position_mint = next_account_info(iter)?;
     let system_program = next_account_info(iter)?;
+    let program_config_account = next_account_info(iter)?;
+    let position_token_account = next_account_info(iter).ok();
+    let dex_position = next_account_info(iter).ok();
 
     if !owner.is_signer {
         return Err(ProgramError::MissingRequiredSignature);
     }
+    let program_config = require_not_paused(program_id, program_config_account)?;
 
-    if data.len() < 4 {
-        return Err(ProgramError::InvalidInstructionData);
+    // Data: slippage(2) + min_yield(2) + protocol(1)
+    let params = EnableParams::parse(data)?;
+    params.validate(&program_config)?;
+
+    enable_position(
+        program_id,
+        owner,
+        system_program,
+        &program_config,
+        &params,
+        config_account,
+        position_mint,
+        position_token_account,
+        dex_position,
+    )
+}
+
+/// Settings applied by `enable_rebalance` and `enable_batch`.
+struct EnableParams {
+    max_slippage_bps: u16,
+    min_yield_bps: u16,
+    protocol: Protocol,
+}
+
+impl EnableParams {
+    fn parse(data: &[u8]) -> Result<Self, ProgramError> {
+        if data.len() < 5 {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+        Ok(Self {
+            max_slippage_bps: u16::from_le_bytes([data[0], data[1]]),
+            min_yield_bps: u16::from_le_bytes([data[2], data[3]]),
+            protocol: Protocol::try_from(data[4])?,
+        })
     }
-    let max_slippage_bps = u16::from_le_bytes([data[0], data[1]]);
-    let min_yield_bps = u16::from_le_bytes([data[2], data[3]]);
+
+    fn validate(&self, program_config: &ProgramConfig) -> ProgramResult {
+        if let Err(e) = program_config.validate_params(self.max_slippage_bps, self.min_yield_bps) {
+            msg!(
+                "Rejected slippage={} min_yield={} (caps {} / {} bps)",
+                self.max_slippage_bps,
+                self.min_yield_bps,
+                program_config.slippage_cap(),
+                program_config.min_yield_cap()
+            );
+            return Err(e.into());
+        }
+        Ok(())
+    }
+}
+
+/// Create or re-enable one position's config. Signer, pause and parameter
+/// checks are the caller's.
+#[allow(clippy::too_many_arguments)]
+fn enable_position<'a>(
+    program_id: &Pubkey,
+    owner: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+    program_config: &ProgramConfig,
+    params: &EnableParams,
+    config_account: &AccountInfo<'a>,
+    position_mint: &AccountInfo<'a>,
+    position_token_account: Option<&AccountInfo<'a>>,
+    dex_position: Option<&AccountInfo<'a>>,
+) -> ProgramResult {
+    verify_dex_position(program_config, params.protocol, position_mint, dex_position)?;
+    verify_position_owner(owner.key, params.protocol, position_mint, position_token_account)?;
 
     // Derive per-position PDA: ["rebalance", owner, position_mint]
-    let (expected_pda, bump) = Pubkey::find_program_address(
-        &[SEED, owner.key.as_ref(), position_mint.key.as_ref()],
-        program_id,
-    );
+    let (expected_pda, bump) = RebalanceConfig::find_address(owner.key, position_mint.key, program_id);
     if *config_account.key != expected_pda {
-        msg!("Invalid PDA");
-        return Err(ProgramError::InvalidSeeds);
+        return Err(PoseidonError::InvalidConfigAddress.into());
     }
 
-    let signer_seeds: &[&[u8]] = &[SEED, owner.key.as_ref(), position_mint.key.as_ref(), &[bump]];
-
     let clock = Clock::get()?;
     let now = clock.unix_timestamp;
 
-    if config_account.data_is_empty() {
-        let rent = Rent::get()?;
-        let lamports = rent.minimum_balance(ACCOUNT_SIZE);
-
-        invoke_signed(
-            &system_instruction::create_account(
-                owner.key,
-                config_account.key,
-                lamports,
-                ACCOUNT_SIZE as u64,
-                program_id,
-            ),
-            &[owner.clone(), config_account.clone(), system_program.clone()],
-            &[signer_seeds],
+    let mut config = if config_account.data_is_empty() {
+        create_pda_account(
+            program_id,
+            owner,
+            config_account,
+            RebalanceConfig::LEN,
+            system_program,
+            &[REBALANCE_SEED, owner.key.as_ref(), position_mint.key.as_ref(), &[bump]],
         )?;
-
-        let mut account_data = config_account.try_borrow_mut_data()?;
-        write_config(
-            &mut account_data,
-            owner.key,
-            position_mint.key,
-            true,
-            max_slippage_bps,
-            min_yield_bps,
-            now,
-            now,
-        );
+        RebalanceConfig {
+            owner: *owner.key,
+            position_mint: *position_mint.key,
+            created_at: now,
+            version: RebalanceConfig::VERSION,
+            bump,
+            ..RebalanceConfig::default()
+        }
     } else {
         // Account exists — verify owner
-        let account_data = config_account.try_borrow_data()?;
-        let stored_owner = Pubkey::try_from(&account_data[8..40])
-            .map_err(|_| ProgramError::InvalidAccountData)?;
-        if stored_owner != *owner.key {
-            return Err(ProgramError::IllegalOwner);
+        let mut config = load_config(program_id, config_account)?;
+        if config.owner != *owner.key {
+            return Err(PoseidonError::ConfigOwnerMismatch.into());
         }
-        let created_at = i64::from_le_bytes(account_data[77..85].try_into().unwrap());
-        drop(account_data);
 
-        let mut account_data = config_account.try_borrow_mut_data()?;
-        write_config(
-            &mut account_data,
-            owner.key,
-            position_mint.key,
-            true,
-            max_slippage_bps,
-            min_yield_bps,
-            created_at,
-            now,
-        );
-    }
+        // Upgrade older layouts in place before rewriting
+        if config.needs_migration() {
+            let from_version = config.version;
+            resize_account(config_account, owner, system_program, RebalanceConfig::LEN)?;
+            config.migrate(bump);
+            ConfigMigrated::new(*config_account.key, &config, from_version, now).emit();
+        }
+        config
+    };
 
+    config.enabled = true;
+    config.max_slippage_bps = params.max_slippage_bps;
+    config.min_yield_improvement_bps = params.min_yield_bps;
+    config.protocol = params.protocol;
+    config.stale = false;
+    config.updated_at = now;
+    config.pack(&mut config_account.try_borrow_mut_data()?)?;
+
+    RebalanceEnabled::new(*config_account.key, &config, false, now).emit();
     msg!("Rebalance enabled for position {} by {}", position_mint.key, owner.key);
     Ok(())
 }
@@ -151,60 +300,935 @@ fn process_disable(
         return Err(ProgramError::MissingRequiredSignature);
     }
 
+    disable_position(program_id, owner, config_account, position_mint)
+}
+
+/// Close one position's config and refund its rent. The signer check is the
+/// caller's.
+fn disable_position(
+    program_id: &Pubkey,
+    owner: &AccountInfo,
+    config_account: &AccountInfo,
+    position_mint: &AccountInfo,
+) -> ProgramResult {
     // Verify per-position PDA
-    let (expected_pda, _bump) = Pubkey::find_program_address(
-        &[SEED, owner.key.as_ref(), position_mint.key.as_ref()],
+    let (expected_pda, _bump) = RebalanceConfig::find_address(owner.key, position_mint.key, program_id);
+    if *config_account.key != expected_pda {
+        return Err(PoseidonError::InvalidConfigAddress.into());
+    }
+
+    // Verify owner
+    let config = load_config(program_id, config_account)?;
+    if config.owner != *owner.key {
+        return Err(PoseidonError::ConfigOwnerMismatch.into());
+    }
+
+    // Close account — refund rent to owner
+    close_account(config_account, owner)?;
+
+    RebalanceDisabled::new(*config_account.key, &config, true, Clock::get()?.unix_timestamp).emit();
+    msg!("Rebalance disabled for position {} by {}", position_mint.key, owner.key);
+    Ok(())
+}
+
+fn process_set_enabled(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    data: &[u8],
+) -> ProgramResult {
+    // Accounts: [owner, config_pda, position_mint, program_config_pda]
+    let iter = &mut accounts.iter();
+    let owner = next_account_info(iter)?;
+    let config_account = next_account_info(iter)?;
+    let position_mint = next_account_info(iter)?;
+    let program_config_account = next_account_info(iter)?;
+
+    if !owner.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    // Data: enabled(1), 0 or 1
+    let enabled = match data.first() {
+        Some(0) => false,
+        Some(1) => true,
+        _ => return Err(ProgramError::InvalidInstructionData),
+    };
+
+    // Resuming grants the agent authority again, so it respects the pause
+    if enabled {
+        require_not_paused(program_id, program_config_account)?;
+    }
+
+    let (expected_pda, _bump) = RebalanceConfig::find_address(owner.key, position_mint.key, program_id);
+    if *config_account.key != expected_pda {
+        return Err(PoseidonError::InvalidConfigAddress.into());
+    }
+
+    let mut config = load_config(program_id, config_account)?;
+    if config.owner != *owner.key {
+        return Err(PoseidonError::ConfigOwnerMismatch.into());
+    }
+    if config.needs_migration() {
+        return Err(PoseidonError::ConfigNeedsMigration.into());
+    }
+
+    let now = Clock::get()?.unix_timestamp;
+    config.enabled = enabled;
+    config.updated_at = now;
+    config.pack(&mut config_account.try_borrow_mut_data()?)?;
+
+    if enabled {
+        RebalanceEnabled::new(*config_account.key, &config, true, now).emit();
+    } else {
+        RebalanceDisabled::new(*config_account.key, &config, false, now).emit();
+    }
+
+    msg!(
+        "Rebalance {} for position {} by {}",
+        if enabled { "resumed" } else { "paused" },
+        position_mint.key,
+        owner.key
+    );
+    Ok(())
+}
+
+fn process_update_config(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    data: &[u8],
+) -> ProgramResult {
+    // Accounts: [owner, config_pda, position_mint, program_config_pda]
+    let iter = &mut accounts.iter();
+    let owner = next_account_info(iter)?;
+    let config_account = next_account_info(iter)?;
+    let position_mint = next_account_info(iter)?;
+    let program_config_account = next_account_info(iter)?;
+
+    if !owner.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    // Data: Option<max_slippage_bps>(1 [+ 2]) + Option<min_yield_bps>(1 [+ 2])
+    //       + Option<inherit>(1 [+ 1]) + Option<fee_destination>(1 [+ 32])
+    //       + Option<strategy_kind>(1 [+ 1]) + Option<strategy_width>(1 [+ 4])
+    //       + Option<recenter_threshold_bps>(1 [+ 2])
+    //       + Option<min_seconds_between_rebalances>(1 [+ 4])
+    //       + Option<max_rebalances_per_day>(1 [+ 2])
+    let offset = &mut 0;
+    let update = ConfigUpdate {
+        max_slippage_bps: read_option(data, offset, 2)?.map(|b| u16::from_le_bytes([b[0], b[1]])),
+        min_yield_improvement_bps: read_option(data, offset, 2)?
+            .map(|b| u16::from_le_bytes([b[0], b[1]])),
+        inherit: read_option(data, offset, 1)?.map(|b| b[0]),
+        fee_destination: read_option(data, offset, 32)?.map(|b| Pubkey::try_from(b).unwrap()),
+        strategy_kind: read_option(data, offset, 1)?
+            .map(|b| StrategyKind::try_from(b[0]))
+            .transpose()?,
+        strategy_width: read_option(data, offset, 4)?
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]])),
+        recenter_threshold_bps: read_option(data, offset, 2)?
+            .map(|b| u16::from_le_bytes([b[0], b[1]])),
+        min_seconds_between_rebalances: read_option(data, offset, 4)?
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]])),
+        max_rebalances_per_day: read_option(data, offset, 2)?
+            .map(|b| u16::from_le_bytes([b[0], b[1]])),
+    };
+    if update.is_empty() {
+        return Err(PoseidonError::NothingToUpdate.into());
+    }
+
+    let (expected_pda, _bump) = RebalanceConfig::find_address(owner.key, position_mint.key, program_id);
+    if *config_account.key != expected_pda {
+        return Err(PoseidonError::InvalidConfigAddress.into());
+    }
+
+    let mut config = load_config(program_id, config_account)?;
+    if config.owner != *owner.key {
+        return Err(PoseidonError::ConfigOwnerMismatch.into());
+    }
+    if config.needs_migration() {
+        return Err(PoseidonError::ConfigNeedsMigration.into());
+    }
+
+    let program_config = admin::load_program_config(program_id, program_config_account)?;
+    program_config.validate_update(&update)?;
+
+    let now = Clock::get()?.unix_timestamp;
+    let event = config.apply_update(*config_account.key, &update, now)?;
+
+    if event.changed != 0 {
+        config.pack(&mut config_account.try_borrow_mut_data()?)?;
+        event.emit();
+    }
+
+    msg!("Config updated for position {} (changed=0b{:02b})", position_mint.key, event.changed);
+    Ok(())
+}
+
+fn process_execute(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    data: &[u8],
+) -> ProgramResult {
+    // Accounts: [agent, config_pda (writable), record_pda, system_program, program_config_pda,
+    //            history_pda, instructions_sysvar, oracle, oracle_feed_pda, pool, dex_position,
+    //            mint_a, mint_b, (user_profile_pda)]
+    // user_profile_pda is required when the config inherits any setting. The
+    // transaction must also hold the DEX instructions that move the position,
+    // after this one and between begin_rebalance and end_rebalance for this
+    // config: the old range and current tick are read from dex_position and
+    // pool, the new range from the DEX instruction opening the owner's new
+    // position in pool.
+    // dex_position is the position PDA (Orca/Raydium)
+    // or position account (Meteora); oracle is a Pyth price update for the
+    // feed oracle_feed_pda sets for mint_a/mint_b.
+    let iter = &mut accounts.iter();
+    let agent = next_account_info(iter)?;
+    let config_account = next_account_info(iter)?;
+    let record_account = next_account_info(iter)?;
+    let system_program = next_account_info(iter)?;
+    let program_config_account = next_account_info(iter)?;
+    let history_account = next_account_info(iter)?;
+    let instructions_sysvar = next_account_info(iter)?;
+    let oracle = next_account_info(iter)?;
+    let oracle_feed = next_account_info(iter)?;
+    let pool = next_account_info(iter)?;
+    let dex_position = next_account_info(iter)?;
+    let mint_a = next_account_info(iter)?;
+    let mint_b = next_account_info(iter)?;
+    let profile_account = next_account_info(iter).ok();
+
+    if !agent.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    let program_config = require_not_paused(program_id, program_config_account)?;
+    if !program_config.is_agent(agent.key) {
+        msg!("Unauthorized agent {}", agent.key);
+        return Err(PoseidonError::UnauthorizedAgent.into());
+    }
+
+    // Data: old_lower(4) + old_upper(4) + new_lower(4) + new_upper(4) + realized_slippage_bps(2)
+    //       + (fees_claimed_a(8) + fees_claimed_b(8))
+    // The old range must match the position and the new one the range the
+    // DEX instructions open; fees default to 0. The program cannot measure
+    // slippage or fees before the DEX instructions run, so both are recorded
+    // as agent-reported; the slippage guard bounds the actual loss.
+    if data.len() < 18 {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    let old_lower = i32::from_le_bytes([data[0], data[1], data[2], data[3]]);
+    let old_upper = i32::from_le_bytes([data[4], data[5], data[6], data[7]]);
+    let new_lower = i32::from_le_bytes([data[8], data[9], data[10], data[11]]);
+    let new_upper = i32::from_le_bytes([data[12], data[13], data[14], data[15]]);
+    let realized_slippage_bps = u16::from_le_bytes([data[16], data[17]]);
+    let (fees_claimed_a, fees_claimed_b) = match data.get(18..34) {
+        Some(b) => (
+            u64::from_le_bytes(b[..8].try_into().unwrap()),
+            u64::from_le_bytes(b[8..].try_into().unwrap()),
+        ),
+        None => (0, 0),
+    };
+
+    if old_lower >= old_upper || new_lower >= new_upper {
+        return Err(PoseidonError::InvalidTickRange.into());
+    }
+
+    // Verify the config belongs to this program and is enabled
+    let mut config = load_active_config(program_id, config_account)?;
+    let dex_span = verify_rebalance_instructions(
+        &program_config,
+        config.protocol,
+        &config.position_mint,
+        &config.owner,
+        pool.key,
+        (new_lower, new_upper),
+        instructions_sysvar,
+    )?;
+    guard::require_guard_around(program_id, config_account.key, instructions_sysvar, dex_span)?;
+    guard::check_pool_price(
         program_id,
+        &program_config,
+        config.protocol,
+        &config.position_mint,
+        guard::PriceAccounts { oracle, oracle_feed, pool, dex_position, mint_a, mint_b },
+    )?;
+    let range = PositionRange::load(config.protocol, dex_position)?;
+    if (old_lower, old_upper) != (range.tick_lower, range.tick_upper) {
+        msg!(
+            "Position {} is at [{}, {}], not [{}, {}]",
+            config.position_mint,
+            range.tick_lower,
+            range.tick_upper,
+            old_lower,
+            old_upper
+        );
+        return Err(PoseidonError::InvalidTickRange.into());
+    }
+    let current_tick = pool_current_tick(config.protocol, pool)?;
+
+    let profile = match profile_account {
+        Some(account) => Some(profile::load_profile(program_id, &config.owner, account)?),
+        None => None,
+    };
+    let settings = config.effective(profile.as_ref())?;
+    if realized_slippage_bps > settings.max_slippage_bps {
+        msg!("Slippage {} bps exceeds max {} bps", realized_slippage_bps, settings.max_slippage_bps);
+        return Err(PoseidonError::SlippageExceeded.into());
+    }
+    if settings.strategy.kind != StrategyKind::Unspecified {
+        settings.strategy.validate(config.protocol)?;
+        if let Err(e) = settings.strategy.check_rebalance(
+            (old_lower, old_upper),
+            (new_lower, new_upper),
+            current_tick,
+        ) {
+            msg!(
+                "Range [{}, {}] at tick {} rejected by strategy {:?}",
+                new_lower,
+                new_upper,
+                current_tick,
+                settings.strategy
+            );
+            return Err(e.into());
+        }
+    }
+
+    let now = Clock::get()?.unix_timestamp;
+    if let Err(e) = config.record_rebalance(now) {
+        msg!(
+            "Rate limit for position {}: last at {}, {} of {} today",
+            config.position_mint,
+            config.last_rebalanced_at,
+            config.rebalances_in_window,
+            config.max_rebalances_per_day
+        );
+        return Err(e.into());
+    }
+    config.pack(&mut config_account.try_borrow_mut_data()?)?;
+
+    log_rebalance(
+        program_id,
+        RebalanceAccounts {
+            agent,
+            system_program,
+            config: config_account,
+            record: record_account,
+            history: history_account,
+        },
+        &config.position_mint,
+        HistoryEntry {
+            executed_at: now,
+            old_tick_lower: old_lower,
+            old_tick_upper: old_upper,
+            new_tick_lower: new_lower,
+            new_tick_upper: new_upper,
+            realized_slippage_bps,
+            fees_claimed_a,
+            fees_claimed_b,
+            agent: *agent.key,
+        },
+        true,
+    )?;
+
+    msg!(
+        "Rebalance executed for position {}: [{}, {}] -> [{}, {}], slippage {} bps",
+        config.position_mint,
+        old_lower,
+        old_upper,
+        new_lower,
+        new_upper,
+        realized_slippage_bps
     );
+    Ok(())
+}
+
+fn process_migrate_config(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+) -> ProgramResult {
+    // Accounts: [payer, config_pda, system_program]
+    // Permissionless: only appends defaults, so a keeper can migrate on users' behalf.
+    let iter = &mut accounts.iter();
+    let payer = next_account_info(iter)?;
+    let config_account = next_account_info(iter)?;
+    let system_program = next_account_info(iter)?;
+
+    if !payer.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    let mut config = load_config(program_id, config_account)?;
+    let version = config.version;
+    if !config.needs_migration() {
+        return Err(PoseidonError::ConfigUpToDate.into());
+    }
+
+    let (expected_pda, bump) =
+        RebalanceConfig::find_address(&config.owner, &config.position_mint, program_id);
     if *config_account.key != expected_pda {
-        return Err(ProgramError::InvalidSeeds);
+        return Err(PoseidonError::InvalidConfigAddress.into());
     }
 
-    // Verify owner
-    let account_data = config_account.try_borrow_data()?;
-    if account_data.len() < ACCOUNT_SIZE {
-        return Err(ProgramError::InvalidAccountData);
+    resize_account(config_account, payer, system_program, RebalanceConfig::LEN)?;
+    config.migrate(bump);
+    config.pack(&mut config_account.try_borrow_mut_data()?)?;
+
+    ConfigMigrated::new(*config_account.key, &config, version, Clock::get()?.unix_timestamp).emit();
+    msg!(
+        "Config for position {} migrated from v{} to v{}",
+        config.position_mint,
+        version,
+        RebalanceConfig::VERSION
+    );
+    Ok(())
+}
+
+/// Permissionless: close the config of a position that no longer exists on
+/// its DEX and return the rent to the config's owner.
+fn process_reap_config(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    // Accounts: [config_pda, position_mint, owner, (vault_pda)]
+    // vault_pda is required once the position was deposited into vault mode:
+    // a vault that rebalanced holds a newer NFT than the closed one, so the
+    // config stays until it is withdrawn.
+    let iter = &mut accounts.iter();
+    let config_account = next_account_info(iter)?;
+    let position_mint = next_account_info(iter)?;
+    let owner = next_account_info(iter)?;
+    let vault_account = next_account_info(iter).ok();
+
+    // Any layout version can be reaped; nothing is written back
+    let config = load_config(program_id, config_account)?;
+    let (expected_pda, _bump) = RebalanceConfig::find_address(&config.owner, position_mint.key, program_id);
+    if *config_account.key != expected_pda {
+        return Err(PoseidonError::InvalidConfigAddress.into());
     }
-    let stored_owner = Pubkey::try_from(&account_data[8..40])
-        .map_err(|_| ProgramError::InvalidAccountData)?;
-    if stored_owner != *owner.key {
-        return Err(ProgramError::IllegalOwner);
+    if config.owner != *owner.key {
+        return Err(PoseidonError::ConfigOwnerMismatch.into());
+    }
+    if !is_position_closed(position_mint) {
+        msg!("Position {} is still open", position_mint.key);
+        return Err(PoseidonError::PositionStillOpen.into());
+    }
+    if config.vaulted {
+        let vault_account = vault_account.ok_or(PoseidonError::InvalidVaultAccount)?;
+        if *vault_account.key != VaultState::find_address(config_account.key, program_id).0 {
+            return Err(PoseidonError::InvalidVaultAccount.into());
+        }
+        if !vault_account.data_is_empty() {
+            msg!("Position {} is held by vault {}", position_mint.key, vault_account.key);
+            return Err(PoseidonError::PositionInVault.into());
+        }
     }
-    drop(account_data);
 
-    // Close account — refund rent to owner
-    let dest_lamports = owner.lamports();
-    let source_lamports = config_account.lamports();
-    **owner.try_borrow_mut_lamports()? = dest_lamports
+    close_account(config_account, owner)?;
+    RebalanceDisabled::new(*config_account.key, &config, true, Clock::get()?.unix_timestamp).emit();
+
+    msg!("Config for closed position {} reaped, rent returned to {}", position_mint.key, owner.key);
+    Ok(())
+}
+
+/// Accounts `log_rebalance` writes.
+struct RebalanceAccounts<'a, 'b> {
+    agent: &'a AccountInfo<'b>,
+    system_program: &'a AccountInfo<'b>,
+    config: &'a AccountInfo<'b>,
+    record: &'a AccountInfo<'b>,
+    history: &'a AccountInfo<'b>,
+}
+
+/// Store an executed rebalance as the config's latest record, append it to
+/// the history and emit `RebalanceExecuted`. The agent pays for either PDA
+/// the first time, and to grow either from an older layout. `agent_reported`
+/// marks the entry's slippage and fees as the agent's own figures.
+fn log_rebalance(
+    program_id: &Pubkey,
+    accounts: RebalanceAccounts,
+    position_mint: &Pubkey,
+    entry: HistoryEntry,
+    agent_reported: bool,
+) -> ProgramResult {
+    let RebalanceAccounts {
+        agent,
+        system_program,
+        config,
+        record: record_account,
+        history: history_account,
+    } = accounts;
+
+    // Derive record PDA: ["rebalance_record", config]
+    let (expected_pda, bump) = RebalanceRecord::find_address(config.key, program_id);
+    if *record_account.key != expected_pda {
+        return Err(PoseidonError::InvalidRecordAccount.into());
+    }
+    let rebalance_count = if record_account.data_is_empty() {
+        create_pda_account(
+            program_id,
+            agent,
+            record_account,
+            RebalanceRecord::LEN,
+            system_program,
+            &[RECORD_SEED, config.key.as_ref(), &[bump]],
+        )?;
+        1
+    } else {
+        let record = RebalanceRecord::unpack(&record_account.try_borrow_data()?)
+            .map_err(|_| PoseidonError::InvalidRecordAccount)?;
+        if record_account.data_len() < RebalanceRecord::LEN {
+            resize_account(record_account, agent, system_program, RebalanceRecord::LEN)?;
+        }
+        record
+            .rebalance_count
+            .checked_add(1)
+            .ok_or(ProgramError::ArithmeticOverflow)?
+    };
+
+    let record = RebalanceRecord {
+        config: *config.key,
+        position_mint: *position_mint,
+        agent: entry.agent,
+        old_tick_lower: entry.old_tick_lower,
+        old_tick_upper: entry.old_tick_upper,
+        new_tick_lower: entry.new_tick_lower,
+        new_tick_upper: entry.new_tick_upper,
+        realized_slippage_bps: entry.realized_slippage_bps,
+        executed_at: entry.executed_at,
+        rebalance_count,
+        agent_reported,
+    };
+    record.pack(&mut record_account.try_borrow_mut_data()?)?;
+
+    // Derive history PDA: ["rebalance_history", config]
+    let (expected_pda, bump) = RebalanceHistory::find_address(config.key, program_id);
+    if *history_account.key != expected_pda {
+        return Err(PoseidonError::InvalidHistoryAccount.into());
+    }
+    let mut history = if history_account.data_is_empty() {
+        create_pda_account(
+            program_id,
+            agent,
+            history_account,
+            RebalanceHistory::LEN,
+            system_program,
+            &[HISTORY_SEED, config.key.as_ref(), &[bump]],
+        )?;
+        RebalanceHistory {
+            version: RebalanceHistory::VERSION,
+            bump,
+            config: *config.key,
+            ..RebalanceHistory::default()
+        }
+    } else {
+        let mut history = RebalanceHistory::unpack(&history_account.try_borrow_data()?)
+            .map_err(|_| PoseidonError::InvalidHistoryAccount)?;
+        if history.version < RebalanceHistory::VERSION {
+            resize_account(history_account, agent, system_program, RebalanceHistory::LEN)?;
+            history.migrate();
+        }
+        history
+    };
+    history.push(entry.clone(), agent_reported);
+    history.pack(&mut history_account.try_borrow_mut_data()?)?;
+
+    RebalanceExecuted {
+        config: *config.key,
+        position_mint: *position_mint,
+        agent: entry.agent,
+        old_tick_lower: entry.old_tick_lower,
+        old_tick_upper: entry.old_tick_upper,
+        new_tick_lower: entry.new_tick_lower,
+        new_tick_upper: entry.new_tick_upper,
+        realized_slippage_bps: entry.realized_slippage_bps,
+        fees_claimed_a: entry.fees_claimed_a,
+        fees_claimed_b: entry.fees_claimed_b,
+        rebalance_count,
+        executed_at: entry.executed_at,
+        agent_reported,
+    }
+    .emit();
+    Ok(())
+}
+
+/// Load a per-position config owned by this program. Older, shorter layouts
+/// load with their newer fields zeroed; check `needs_migration` before relying on them.
+fn load_config(program_id: &Pubkey, account: &AccountInfo) -> Result<RebalanceConfig, ProgramError> {
+    if account.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    let data = account.try_borrow_data()?;
+    if data.len() < RebalanceConfig::V0_LEN {
+        return Err(PoseidonError::InvalidConfigAccount.into());
+    }
+    RebalanceConfig::unpack(&data).map_err(|_| PoseidonError::InvalidConfigAccount.into())
+}
+
+/// Config the agent may act on: current layout, enabled, not stale and with
+/// a verified protocol. Configs split from a legacy one keep
+/// `Protocol::Unknown` until `enable_rebalance` checks the owner holds the
+/// position.
+fn load_active_config(
+    program_id: &Pubkey,
+    account: &AccountInfo,
+) -> Result<RebalanceConfig, ProgramError> {
+    let config = load_config(program_id, account)?;
+    if config.needs_migration() {
+        return Err(PoseidonError::ConfigNeedsMigration.into());
+    }
+    if config.protocol == Protocol::Unknown {
+        msg!("Ownership of position {} has not been verified", config.position_mint);
+        return Err(PoseidonError::PositionUnverified.into());
+    }
+    if !config.enabled {
+        msg!("Rebalance not enabled for position {}", config.position_mint);
+        return Err(PoseidonError::RebalanceDisabled.into());
+    }
+    if config.stale {
+        msg!("Owner of position {} no longer holds it", config.position_mint);
+        return Err(PoseidonError::ConfigStale.into());
+    }
+    Ok(config)
+}
+
+/// Create a PDA owned by this program, funded by `payer`.
+fn create_pda_account<'a>(
+    program_id: &Pubkey,
+    payer: &AccountInfo<'a>,
+    account: &AccountInfo<'a>,
+    space: usize,
+    system_program: &AccountInfo<'a>,
+    signer_seeds: &[&[u8]],
+) -> ProgramResult {
+    let rent = Rent::get()?;
+    let lamports = rent.minimum_balance(space);
+
+    invoke_signed(
+        &system_instruction::create_account(
+            payer.key,
+            account.key,
+            lamports,
+            space as u64,
+            program_id,
+        ),
+        &[payer.clone(), account.clone(), system_program.clone()],
+        &[signer_seeds],
+    )
+}
+
+/// Move all lamports to `destination` and zero the account data.
+fn close_account(account: &AccountInfo, destination: &AccountInfo) -> ProgramResult {
+    let dest_lamports = destination.lamports();
+    let source_lamports = account.lamports();
+    **destination.try_borrow_mut_lamports()? = dest_lamports
         .checked_add(source_lamports)
         .ok_or(ProgramError::ArithmeticOverflow)?;
-    **config_account.try_borrow_mut_lamports()? = 0;
+    **account.try_borrow_mut_lamports()? = 0;
 
-    let mut data = config_account.try_borrow_mut_data()?;
+    let mut data = account.try_borrow_mut_data()?;
     for byte in data.iter_mut() {
         *byte = 0;
     }
-
-    msg!("Rebalance disabled for position {} by {}", position_mint.key, owner.key);
     Ok(())
 }
 
-fn write_config(
-    data: &mut [u8],
-    owner: &Pubkey,
-    position_mint: &Pubkey,
-    enabled: bool,
-    max_slippage_bps: u16,
-    min_yield_bps: u16,
-    created_at: i64,
-    updated_at: i64,
-) {
-    data[..8].copy_from_slice(&ACCOUNT_DISC);
-    data[8..40].copy_from_slice(owner.as_ref());          // owner: 8..40
-    data[40..72].copy_from_slice(position_mint.as_ref());  // position_mint: 40..72
-    data[72] = if enabled { 1 } else { 0 };               // enabled: 72
-    data[73..75].copy_from_slice(&max_slippage_bps.to_le_bytes()); // 73..75
-    data[75..77].copy_from_slice(&min_yield_bps.to_le_bytes());    // 75..77
-    data[77..85].copy_from_slice(&created_at.to_le_bytes());       // 77..85
-    data[85..93].copy_from_slice(&updated_at.to_le_bytes());       // 85..93
+/// Read a Borsh `Option` of a `size`-byte value at `*offset`, advancing past
+/// it. Options missing from the end of the data read as `None`, so clients
+/// built before a field was added keep working.
+fn read_option<'d>(
+    data: &'d [u8],
+    offset: &mut usize,
+    size: usize,
+) -> Result<Option<&'d [u8]>, ProgramError> {
+    match data.get(*offset) {
+        None => Ok(None),
+        Some(0) => {
+            *offset += 1;
+            Ok(None)
+        }
+        Some(1) if data.len() >= *offset + 1 + size => {
+            let value = &data[*offset + 1..*offset + 1 + size];
+            *offset += 1 + size;
+            Ok(Some(value))
+        }
+        _ => Err(ProgramError::InvalidInstructionData),
+    }
+}
+
+/// Grow an account to `new_len`, topping up rent from `payer`.
+fn resize_account<'a>(
+    account: &AccountInfo<'a>,
+    payer: &AccountInfo<'a>,
+    system_program: &AccountInfo<'a>,
+    new_len: usize,
+) -> ProgramResult {
+    let rent = Rent::get()?;
+    let required = rent
+        .minimum_balance(new_len)
+        .saturating_sub(account.lamports());
+    if required > 0 {
+        invoke(
+            &system_instruction::transfer(payer.key, account.key, required),
+            &[payer.clone(), account.clone(), system_program.clone()],
+        )?;
+    }
+    account.realloc(new_len, true)
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_program::system_program;
+
+    use super::*;
+    use crate::test_utils::*;
+
+    // old [-10, 10] -> new [20, 40], 5 bps slippage
+    fn execute_args() -> Vec<u8> {
+        let mut args = Vec::new();
+        for tick in [-10i32, 10, 20, 40] {
+            args.extend_from_slice(&tick.to_le_bytes());
+        }
+        args.extend_from_slice(&5u16.to_le_bytes());
+        ix_data(IX_EXECUTE, &args)
+    }
+
+    /// `execute_rebalance` accounts; those after the program config are
+    /// placeholders, never read when authorization fails.
+    fn execute_accounts(agent: Account, program_config: Account, config: Account) -> Vec<Account> {
+        let record = RebalanceRecord::find_address(&config.key, &PROGRAM_ID).0;
+        let mut accounts = vec![
+            agent,
+            config,
+            Account::empty(record),
+            Account::program(system_program::id()),
+            program_config.readonly(),
+        ];
+        accounts.extend((0..8).map(|_| Account::empty(Pubkey::new_unique())));
+        accounts
+    }
+
+    #[test]
+    fn execute_requires_the_agent_to_sign() {
+        let agent = Pubkey::new_unique();
+        let mut accounts = execute_accounts(
+            Account::signer(agent).unsigned(),
+            program_config(Pubkey::new_unique(), &[agent]),
+            rebalance_config(Pubkey::new_unique(), Pubkey::new_unique(), Protocol::OrcaWhirlpool),
+        );
+        assert_eq!(
+            process(&mut accounts, &execute_args()),
+            Err(ProgramError::MissingRequiredSignature)
+        );
+    }
+
+    #[test]
+    fn execute_rejects_signers_not_authorized_as_agents() {
+        let agent = Pubkey::new_unique();
+        // The position's owner is no agent either
+        let owner = Pubkey::new_unique();
+        for signer in [Pubkey::new_unique(), owner] {
+            let mut accounts = execute_accounts(
+                Account::signer(signer),
+                program_config(Pubkey::new_unique(), &[agent]),
+                rebalance_config(owner, Pubkey::new_unique(), Protocol::OrcaWhirlpool),
+            );
+            assert_eq!(
+                process(&mut accounts, &execute_args()),
+                Err(PoseidonError::UnauthorizedAgent.into())
+            );
+        }
+    }
+
+    #[test]
+    fn execute_rejects_program_configs_not_at_the_pda() {
+        let agent = Pubkey::new_unique();
+        let mut program_config = program_config(Pubkey::new_unique(), &[agent]);
+        program_config.key = Pubkey::new_unique();
+        let mut accounts = execute_accounts(
+            Account::signer(agent),
+            program_config,
+            rebalance_config(Pubkey::new_unique(), Pubkey::new_unique(), Protocol::OrcaWhirlpool),
+        );
+        assert_eq!(
+            process(&mut accounts, &execute_args()),
+            Err(PoseidonError::InvalidProgramConfig.into())
+        );
+    }
+
+    #[test]
+    fn execute_lets_agents_through_to_the_config_checks() {
+        let agent = Pubkey::new_unique();
+        let config =
+            rebalance_config(Pubkey::new_unique(), Pubkey::new_unique(), Protocol::OrcaWhirlpool);
+        let mut state: RebalanceConfig = config.unpack();
+        state.enabled = false;
+        let config = Account::state(config.key, &state);
+        let mut accounts = execute_accounts(
+            Account::signer(agent),
+            program_config(Pubkey::new_unique(), &[Pubkey::new_unique(), agent]),
+            config,
+        );
+        assert_eq!(
+            process(&mut accounts, &execute_args()),
+            Err(PoseidonError::RebalanceDisabled.into())
+        );
+    }
+
+    #[test]
+    fn pause_blocks_agent_actions() {
+        let agent = Pubkey::new_unique();
+        let mut accounts = execute_accounts(
+            Account::signer(agent),
+            paused(program_config(Pubkey::new_unique(), &[agent])),
+            rebalance_config(Pubkey::new_unique(), Pubkey::new_unique(), Protocol::OrcaWhirlpool),
+        );
+        assert_eq!(
+            process(&mut accounts, &execute_args()),
+            Err(PoseidonError::ProgramPaused.into())
+        );
+    }
+
+    #[test]
+    fn pause_blocks_granting_the_agent_authority() {
+        let owner = Pubkey::new_unique();
+        let position_mint = Pubkey::new_unique();
+        let config = rebalance_config(owner, position_mint, Protocol::MeteoraDlmm);
+        let program_config = paused(program_config(Pubkey::new_unique(), &[]));
+
+        let mut accounts = vec![
+            Account::signer(owner),
+            Account::empty(config.key),
+            Account::new(position_mint, Pubkey::new_unique(), Vec::new()).readonly(),
+            Account::program(system_program::id()),
+            program_config.clone().readonly(),
+        ];
+        let mut args = 100u16.to_le_bytes().to_vec();
+        args.extend_from_slice(&0u16.to_le_bytes());
+        args.push(Protocol::MeteoraDlmm as u8);
+        assert_eq!(
+            process(&mut accounts, &ix_data(IX_ENABLE, &args)),
+            Err(PoseidonError::ProgramPaused.into())
+        );
+
+        let mut accounts = vec![
+            Account::signer(owner),
+            config,
+            Account::new(position_mint, Pubkey::new_unique(), Vec::new()).readonly(),
+            program_config.readonly(),
+        ];
+        assert_eq!(
+            process(&mut accounts, &ix_data(IX_SET_ENABLED, &[1])),
+            Err(PoseidonError::ProgramPaused.into())
+        );
+    }
+
+    #[test]
+    fn owners_can_stop_rebalancing_while_paused() {
+        let owner = Pubkey::new_unique();
+        let position_mint = Pubkey::new_unique();
+        let config = rebalance_config(owner, position_mint, Protocol::OrcaWhirlpool);
+        let mint = Account::new(position_mint, Pubkey::new_unique(), Vec::new()).readonly();
+        let mut accounts = vec![
+            Account::signer(owner),
+            config,
+            mint.clone(),
+            paused(program_config(Pubkey::new_unique(), &[])).readonly(),
+        ];
+        process(&mut accounts, &ix_data(IX_SET_ENABLED, &[0])).unwrap();
+        assert!(!accounts[1].unpack::<RebalanceConfig>().enabled);
+
+        let rent = accounts[1].lamports;
+        let mut accounts = vec![accounts.remove(0), accounts.remove(0), mint];
+        let balance = accounts[0].lamports;
+        process(&mut accounts, &ix_data(IX_DISABLE, &[])).unwrap();
+        assert_eq!(accounts[1].lamports, 0);
+        assert_eq!(accounts[0].lamports, balance + rent);
+    }
+
+    fn reap_accounts(config: Account, position: Account) -> Vec<Account> {
+        let owner = config.unpack::<RebalanceConfig>().owner;
+        let vault = VaultState::find_address(&config.key, &PROGRAM_ID).0;
+        vec![
+            config,
+            position.readonly(),
+            Account::new(owner, system_program::id(), Vec::new()),
+            Account::empty(vault).readonly(),
+        ]
+    }
+
+    #[test]
+    fn anyone_reaps_the_config_of_a_closed_position() {
+        let (owner, position_mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let config = rebalance_config(owner, position_mint, Protocol::OrcaWhirlpool);
+        // The NFT was burned when the position closed
+        let mut accounts = reap_accounts(config, mint(position_mint, 0, 0));
+        let (rent, balance) = (accounts[0].lamports, accounts[2].lamports);
+        let state: RebalanceConfig = accounts[0].unpack();
+        process(&mut accounts, &ix_data(IX_REAP_CONFIG, &[])).unwrap();
+
+        assert!(accounts[0].is_closed());
+        assert_eq!(accounts[2].lamports, balance + rent);
+        assert!(emitted(&RebalanceDisabled::new(accounts[0].key, &state, true, NOW)));
+    }
+
+    #[test]
+    fn reap_takes_configs_of_any_layout() {
+        let (owner, position_mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let config = rebalance_config(owner, position_mint, Protocol::Unknown);
+        let mut state: RebalanceConfig = config.unpack();
+        state.version = 0;
+        let mut data = vec![0; RebalanceConfig::LEN];
+        state.pack(&mut data).unwrap();
+        data.truncate(RebalanceConfig::V0_LEN);
+        let config = Account::new(config.key, PROGRAM_ID, data);
+        // A Meteora position account that was closed
+        let mut accounts = reap_accounts(config, Account::empty(position_mint));
+        process(&mut accounts, &ix_data(IX_REAP_CONFIG, &[])).unwrap();
+        assert!(accounts[0].is_closed());
+    }
+
+    #[test]
+    fn reap_leaves_open_positions_and_their_owners_alone() {
+        let (owner, position_mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let config = rebalance_config(owner, position_mint, Protocol::OrcaWhirlpool);
+        let mut accounts = reap_accounts(config.clone(), mint(position_mint, 1, 0));
+        assert_eq!(
+            process(&mut accounts, &ix_data(IX_REAP_CONFIG, &[])),
+            Err(PoseidonError::PositionStillOpen.into())
+        );
+
+        // The rent only goes back to the config's owner
+        let mut accounts = reap_accounts(config, mint(position_mint, 0, 0));
+        accounts[2].key = Pubkey::new_unique();
+        assert_eq!(
+            process(&mut accounts, &ix_data(IX_REAP_CONFIG, &[])),
+            Err(PoseidonError::ConfigOwnerMismatch.into())
+        );
+    }
+
+    #[test]
+    fn reap_waits_for_vaulted_positions_to_be_withdrawn() {
+        let (owner, position_mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let config = rebalance_config(owner, position_mint, Protocol::OrcaWhirlpool);
+        let mut state: RebalanceConfig = config.unpack();
+        state.vaulted = true;
+        let config = Account::state(config.key, &state);
+
+        let mut accounts = reap_accounts(config.clone(), mint(position_mint, 0, 0));
+        accounts.pop();
+        assert_eq!(
+            process(&mut accounts, &ix_data(IX_REAP_CONFIG, &[])),
+            Err(PoseidonError::InvalidVaultAccount.into())
+        );
+
+        // The vault holds the NFT it rebalanced into
+        let mut accounts = reap_accounts(config.clone(), mint(position_mint, 0, 0));
+        let vault = VaultState { config: config.key, owner, ..VaultState::default() };
+        accounts[3] = Account::state(accounts[3].key, &vault);
+        assert_eq!(
+            process(&mut accounts, &ix_data(IX_REAP_CONFIG, &[])),
+            Err(PoseidonError::PositionInVault.into())
+        );
+
+        let mut accounts = reap_accounts(config, mint(position_mint, 0, 0));
+        process(&mut accounts, &ix_data(IX_REAP_CONFIG, &[])).unwrap();
+        assert!(accounts[0].is_closed());
+    }
 }
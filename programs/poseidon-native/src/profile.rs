@@ -0,0 +1,151 @@
+use poseidon_state::{
+    AccountState, Event, PoseidonError, Protocol, Strategy, StrategyKind, UserProfile,
+    UserProfileClosed, UserProfileUpdated, USER_PROFILE_SEED,
+};
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    clock::Clock,
+    entrypoint::ProgramResult,
+    msg,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    sysvar::Sysvar,
+};
+
+use crate::{admin::load_program_config, close_account, create_pda_account, resize_account};
+
+/// Create or overwrite the owner's default settings.
+pub fn process_set_user_profile(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    data: &[u8],
+) -> ProgramResult {
+    // Accounts: [owner, user_profile_pda, system_program, program_config_pda]
+    let iter = &mut accounts.iter();
+    let owner = next_account_info(iter)?;
+    let profile_account = next_account_info(iter)?;
+    let system_program = next_account_info(iter)?;
+    let program_config_account = next_account_info(iter)?;
+
+    if !owner.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    // Data: slippage(2) + min_yield(2) + fee_destination(32)
+    //       + (strategy_kind(1) + strategy_width(4) + recenter_threshold_bps(2))
+    // Without the strategy fields the profile keeps its current strategy.
+    if data.len() < 36 {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    let max_slippage_bps = u16::from_le_bytes([data[0], data[1]]);
+    let min_yield_bps = u16::from_le_bytes([data[2], data[3]]);
+    let fee_destination =
+        Pubkey::try_from(&data[4..36]).map_err(|_| ProgramError::InvalidInstructionData)?;
+
+    let strategy = match data.get(36..43) {
+        Some(b) => Some(Strategy {
+            kind: StrategyKind::try_from(b[0])?,
+            width: u32::from_le_bytes([b[1], b[2], b[3], b[4]]),
+            recenter_threshold_bps: u16::from_le_bytes([b[5], b[6]]),
+        }),
+        None => None,
+    };
+
+    let program_config = load_program_config(program_id, program_config_account)?;
+    program_config.validate_params(max_slippage_bps, min_yield_bps)?;
+    // Protocol-specific limits are checked against each inheriting config at execute
+    if let Some(strategy) = strategy {
+        strategy.validate(Protocol::Unknown)?;
+    }
+
+    let (expected_pda, bump) = UserProfile::find_address(owner.key, program_id);
+    if *profile_account.key != expected_pda {
+        return Err(PoseidonError::InvalidUserProfile.into());
+    }
+
+    let now = Clock::get()?.unix_timestamp;
+    let created = profile_account.data_is_empty();
+    let mut profile = if created {
+        create_pda_account(
+            program_id,
+            owner,
+            profile_account,
+            UserProfile::LEN,
+            system_program,
+            &[USER_PROFILE_SEED, owner.key.as_ref(), &[bump]],
+        )?;
+        UserProfile {
+            version: UserProfile::VERSION,
+            bump,
+            owner: *owner.key,
+            created_at: now,
+            ..UserProfile::default()
+        }
+    } else {
+        let mut profile = load_profile(program_id, owner.key, profile_account)?;
+        if profile.version < UserProfile::VERSION {
+            // v2: `strategy` loads as Unspecified
+            resize_account(profile_account, owner, system_program, UserProfile::LEN)?;
+            profile.version = UserProfile::VERSION;
+        }
+        profile
+    };
+
+    profile.max_slippage_bps = max_slippage_bps;
+    profile.min_yield_improvement_bps = min_yield_bps;
+    profile.fee_destination = fee_destination;
+    if let Some(strategy) = strategy {
+        profile.strategy = strategy;
+    }
+    profile.updated_at = now;
+    profile.pack(&mut profile_account.try_borrow_mut_data()?)?;
+    UserProfileUpdated::new(*profile_account.key, &profile, created).emit();
+
+    msg!("User profile set for {}", owner.key);
+    Ok(())
+}
+
+/// Close the owner's profile and refund its rent. Configs that still inherit
+/// from it are refused by the agent until they override or a new profile
+/// is set.
+pub fn process_close_user_profile(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    // Accounts: [owner, user_profile_pda]
+    let iter = &mut accounts.iter();
+    let owner = next_account_info(iter)?;
+    let profile_account = next_account_info(iter)?;
+
+    if !owner.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    load_profile(program_id, owner.key, profile_account)?;
+    close_account(profile_account, owner)?;
+    UserProfileClosed {
+        profile: *profile_account.key,
+        owner: *owner.key,
+        closed_at: Clock::get()?.unix_timestamp,
+    }
+    .emit();
+
+    msg!("User profile closed for {}", owner.key);
+    Ok(())
+}
+
+/// Load `owner`'s profile, verifying address and program ownership.
+pub fn load_profile(
+    program_id: &Pubkey,
+    owner: &Pubkey,
+    account: &AccountInfo,
+) -> Result<UserProfile, ProgramError> {
+    if account.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    let profile = UserProfile::unpack(&account.try_borrow_data()?)
+        .map_err(|_| PoseidonError::InvalidUserProfile)?;
+    let expected_pda =
+        Pubkey::create_program_address(&[USER_PROFILE_SEED, owner.as_ref(), &[profile.bump]], program_id)
+            .map_err(|_| PoseidonError::InvalidUserProfile)?;
+    if profile.owner != *owner || *account.key != expected_pda {
+        return Err(PoseidonError::InvalidUserProfile.into());
+    }
+    Ok(profile)
+}
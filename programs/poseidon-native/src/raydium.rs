@@ -0,0 +1,385 @@
+use poseidon_state::token_balance;
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    program_error::ProgramError,
+    rent::Rent,
+    sysvar::Sysvar,
+};
+
+use crate::vault::{invoke_dex, RebalanceOutcome, VaultAccounts, VaultRebalance};
+
+// Raydium CLMM instruction discriminators: sha256("global:<name>")[0..8]
+const DECREASE_LIQUIDITY_V2: [u8; 8] = [58, 127, 188, 62, 79, 82, 196, 96];
+const CLOSE_POSITION: [u8; 8] = [123, 134, 81, 0, 49, 68, 98, 98];
+const OPEN_POSITION_WITH_TOKEN22_NFT: [u8; 8] = [77, 255, 174, 82, 125, 29, 201, 46];
+const INCREASE_LIQUIDITY_V2: [u8; 8] = [133, 29, 89, 223, 69, 238, 176, 10];
+
+/// Raydium CLMM accounts a vault rebalance passes after the shared ones.
+pub struct RaydiumAccounts<'a, 'b> {
+    protocol_position: &'a AccountInfo<'b>,
+    new_protocol_position: &'a AccountInfo<'b>,
+    token_vault_0: &'a AccountInfo<'b>,
+    token_vault_1: &'a AccountInfo<'b>,
+    tick_array_lower: &'a AccountInfo<'b>,
+    tick_array_upper: &'a AccountInfo<'b>,
+    new_tick_array_lower: &'a AccountInfo<'b>,
+    new_tick_array_upper: &'a AccountInfo<'b>,
+    spl_token_program: &'a AccountInfo<'b>,
+    token_program_2022: &'a AccountInfo<'b>,
+    memo_program: &'a AccountInfo<'b>,
+    rent: &'a AccountInfo<'b>,
+    associated_token_program: &'a AccountInfo<'b>,
+}
+
+impl<'a, 'b> RaydiumAccounts<'a, 'b> {
+    // Accounts: [protocol_position, new_protocol_position, token_vault_0, token_vault_1,
+    //            tick_array_lower, tick_array_upper, new_tick_array_lower,
+    //            new_tick_array_upper, spl_token_program, token_program_2022, memo_program,
+    //            rent, associated_token_program]
+    pub fn parse<I>(iter: &mut I) -> Result<Self, ProgramError>
+    where
+        I: Iterator<Item = &'a AccountInfo<'b>>,
+    {
+        Ok(Self {
+            protocol_position: next_account_info(iter)?,
+            new_protocol_position: next_account_info(iter)?,
+            token_vault_0: next_account_info(iter)?,
+            token_vault_1: next_account_info(iter)?,
+            tick_array_lower: next_account_info(iter)?,
+            tick_array_upper: next_account_info(iter)?,
+            new_tick_array_lower: next_account_info(iter)?,
+            new_tick_array_upper: next_account_info(iter)?,
+            spl_token_program: next_account_info(iter)?,
+            token_program_2022: next_account_info(iter)?,
+            memo_program: next_account_info(iter)?,
+            rent: next_account_info(iter)?,
+            associated_token_program: next_account_info(iter)?,
+        })
+    }
+}
+
+/// Move the vault's liquidity to a new Raydium position. Fees are harvested
+/// with a zero-liquidity `decrease_liquidity_v2`, then the liquidity is
+/// withdrawn and the position closed. The new position is opened empty with
+/// a Token-2022 NFT, since the vault cannot pay rent, and filled with
+/// `increase_liquidity_v2`. Returns what the withdrawal and fee harvest
+/// brought in. Positions with rewards owed cannot be closed this way.
+pub fn rebalance<'a>(
+    shared: &VaultAccounts<'_, 'a>,
+    accounts: &RaydiumAccounts<'_, 'a>,
+    params: &VaultRebalance,
+    args: &[u8],
+    signer_seeds: &[&[u8]],
+) -> Result<RebalanceOutcome, ProgramError> {
+    // Args: tick_array_lower_start_index(4) + tick_array_upper_start_index(4)
+    if args.len() < 8 {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    let tick_array_lower_start = i32::from_le_bytes(args[0..4].try_into().unwrap());
+    let tick_array_upper_start = i32::from_le_bytes(args[4..8].try_into().unwrap());
+    let VaultAccounts {
+        agent,
+        vault,
+        token_program,
+        system_program,
+        dex_program,
+        pool,
+        position,
+        position_mint,
+        position_token_account,
+        new_position,
+        new_position_mint,
+        new_position_token_account,
+        vault_token_a,
+        vault_token_b,
+        mint_a,
+        mint_b,
+    } = *shared;
+
+    let decrease = |liquidity: u128, min_0: u64, min_1: u64| {
+        let mut data = liquidity.to_le_bytes().to_vec();
+        data.extend_from_slice(&min_0.to_le_bytes());
+        data.extend_from_slice(&min_1.to_le_bytes());
+        invoke_dex(
+            dex_program,
+            vault,
+            DECREASE_LIQUIDITY_V2,
+            &data,
+            &[
+                (vault, false),
+                (position_token_account, false),
+                (position, true),
+                (pool, true),
+                (accounts.protocol_position, true),
+                (accounts.token_vault_0, true),
+                (accounts.token_vault_1, true),
+                (accounts.tick_array_lower, true),
+                (accounts.tick_array_upper, true),
+                (vault_token_a, true),
+                (vault_token_b, true),
+                (accounts.spl_token_program, false),
+                (accounts.token_program_2022, false),
+                (accounts.memo_program, false),
+                (mint_a, false),
+                (mint_b, false),
+            ],
+            signer_seeds,
+        )
+    };
+
+    let before_a = token_balance(vault.key, vault_token_a, None)?;
+    let before_b = token_balance(vault.key, vault_token_b, None)?;
+    decrease(0, 0, 0)?;
+    let fees_a = token_balance(vault.key, vault_token_a, None)?.saturating_sub(before_a);
+    let fees_b = token_balance(vault.key, vault_token_b, None)?.saturating_sub(before_b);
+    if params.liquidity > 0 {
+        decrease(params.liquidity, params.token_min_a, params.token_min_b)?;
+    }
+    let withdrawn_a =
+        token_balance(vault.key, vault_token_a, None)?.saturating_sub(before_a + fees_a);
+    let withdrawn_b =
+        token_balance(vault.key, vault_token_b, None)?.saturating_sub(before_b + fees_b);
+
+    invoke_dex(
+        dex_program,
+        vault,
+        CLOSE_POSITION,
+        &[],
+        &[
+            (vault, true),
+            (position_mint, true),
+            (position_token_account, true),
+            (position, true),
+            (system_program, false),
+            (token_program, false),
+        ],
+        signer_seeds,
+    )?;
+    // The closed accounts' rent lands in the vault as NFT owner; pass it on
+    // to the agent, who funds the new ones.
+    let excess = vault
+        .lamports()
+        .saturating_sub(Rent::get()?.minimum_balance(vault.data_len()));
+    **vault.try_borrow_mut_lamports()? -= excess;
+    **agent.try_borrow_mut_lamports()? += excess;
+
+    let mut data = params.new_tick_lower.to_le_bytes().to_vec();
+    data.extend_from_slice(&params.new_tick_upper.to_le_bytes());
+    data.extend_from_slice(&tick_array_lower_start.to_le_bytes());
+    data.extend_from_slice(&tick_array_upper_start.to_le_bytes());
+    data.extend_from_slice(&0u128.to_le_bytes()); // liquidity
+    data.extend_from_slice(&0u64.to_le_bytes()); // amount_0_max
+    data.extend_from_slice(&0u64.to_le_bytes()); // amount_1_max
+    data.extend_from_slice(&[0, 0]); // with_metadata: false, base_flag: None
+    invoke_dex(
+        dex_program,
+        vault,
+        OPEN_POSITION_WITH_TOKEN22_NFT,
+        &data,
+        &[
+            (agent, true),
+            (vault, false),
+            (new_position_mint, true),
+            (new_position_token_account, true),
+            (pool, true),
+            (accounts.new_protocol_position, true),
+            (accounts.new_tick_array_lower, true),
+            (accounts.new_tick_array_upper, true),
+            (new_position, true),
+            (vault_token_a, true),
+            (vault_token_b, true),
+            (accounts.token_vault_0, true),
+            (accounts.token_vault_1, true),
+            (accounts.rent, false),
+            (system_program, false),
+            (accounts.spl_token_program, false),
+            (accounts.associated_token_program, false),
+            (accounts.token_program_2022, false),
+            (mint_a, false),
+            (mint_b, false),
+        ],
+        signer_seeds,
+    )?;
+
+    if params.liquidity_to_add > 0 {
+        let mut data = params.liquidity_to_add.to_le_bytes().to_vec();
+        data.extend_from_slice(&params.token_max_a.to_le_bytes());
+        data.extend_from_slice(&params.token_max_b.to_le_bytes());
+        data.push(0); // base_flag: None
+        invoke_dex(
+            dex_program,
+            vault,
+            INCREASE_LIQUIDITY_V2,
+            &data,
+            &[
+                (vault, false),
+                (new_position_token_account, false),
+                (pool, true),
+                (accounts.new_protocol_position, true),
+                (new_position, true),
+                (accounts.new_tick_array_lower, true),
+                (accounts.new_tick_array_upper, true),
+                (vault_token_a, true),
+                (vault_token_b, true),
+                (accounts.token_vault_0, true),
+                (accounts.token_vault_1, true),
+                (accounts.spl_token_program, false),
+                (accounts.token_program_2022, false),
+                (mint_a, false),
+                (mint_b, false),
+            ],
+            signer_seeds,
+        )?;
+    }
+
+    Ok(RebalanceOutcome { withdrawn_a, withdrawn_b, fees_a, fees_b })
+}
+
+#[cfg(test)]
+mod tests {
+    use poseidon_state::{
+        position::{
+            ASSOCIATED_TOKEN_PROGRAM_ID, RAYDIUM_CLMM_PROGRAM_ID, TOKEN_2022_PROGRAM_ID,
+            TOKEN_PROGRAM_ID,
+        },
+        Protocol, RebalanceExecuted, RebalanceHistory, RebalanceRecord, VaultState,
+    };
+    use solana_program::{pubkey::Pubkey, sysvar};
+
+    use super::*;
+    use crate::test_utils::*;
+
+    /// Tokens the Raydium program pays the vault for its liquidity, and in fees.
+    const WITHDRAWN: (u64, u64) = (5_000, 5_000);
+    const FEES: (u64, u64) = (7, 9);
+    const TICK_ARRAY_STARTS: (i32, i32) = (-240, 180);
+
+    /// A vaulted Raydium position, followed by the Raydium accounts.
+    fn fixture() -> VaultFixture {
+        let mut fixture = VaultFixture::new(Protocol::RaydiumClmm);
+        // Protocol positions, token vaults and tick arrays
+        fixture.accounts.extend((0..8).map(|_| Account::empty(Pubkey::new_unique())));
+        fixture.accounts.extend([
+            Account::program(TOKEN_PROGRAM_ID),
+            Account::program(TOKEN_2022_PROGRAM_ID),
+            Account::program(Pubkey::new_unique()), // memo program
+            Account::empty(sysvar::rent::id()).readonly(),
+            Account::program(ASSOCIATED_TOKEN_PROGRAM_ID),
+        ]);
+        fixture
+    }
+
+    fn args() -> Vec<u8> {
+        [TICK_ARRAY_STARTS.0.to_le_bytes(), TICK_ARRAY_STARTS.1.to_le_bytes()].concat()
+    }
+
+    /// Have the Raydium program pay the vault fees on a zero-liquidity
+    /// decrease and the liquidity's tokens on a full one, and close the old
+    /// position's accounts to the vault as the NFT's holder.
+    fn pay_vault(fixture: &VaultFixture) {
+        let vault = fixture.key(VaultFixture::VAULT);
+        let token_a = fixture.key(VaultFixture::VAULT_TOKEN_A);
+        let token_b = fixture.key(VaultFixture::VAULT_TOKEN_B);
+        let closed = [
+            fixture.key(VaultFixture::POSITION),
+            fixture.key(VaultFixture::POSITION_TOKEN_ACCOUNT),
+        ];
+        on_cpi(Box::new(move |instruction, infos| {
+            let account = |key: &Pubkey| infos.iter().find(|info| info.key == key).unwrap();
+            match instruction.data[..8].try_into().unwrap() {
+                DECREASE_LIQUIDITY_V2 => {
+                    let (a, b) = if instruction.data[8..24] == [0; 16] { FEES } else { WITHDRAWN };
+                    credit_tokens(infos, &token_a, a)?;
+                    credit_tokens(infos, &token_b, b)
+                }
+                CLOSE_POSITION => {
+                    for key in &closed {
+                        let lamports = account(key).lamports();
+                        **account(key).try_borrow_mut_lamports()? = 0;
+                        **account(&vault).try_borrow_mut_lamports()? += lamports;
+                    }
+                    Ok(())
+                }
+                _ => Ok(()),
+            }
+        }));
+    }
+
+    #[test]
+    fn vault_rebalance_moves_the_position_through_raydium() {
+        let mut fixture = fixture();
+        pay_vault(&fixture);
+        let closed_rent: u64 = [VaultFixture::POSITION, VaultFixture::POSITION_TOKEN_ACCOUNT]
+            .iter()
+            .map(|&index| fixture.accounts[index].lamports)
+            .sum();
+        let agent_lamports = fixture.accounts[0].lamports;
+        let vault_lamports = fixture.accounts[VaultFixture::VAULT].lamports;
+        let data = fixture.data(&args());
+        assert_eq!(process(&mut fixture.accounts, &data), Ok(()));
+
+        let calls: Vec<_> = cpis()
+            .into_iter()
+            .filter(|instruction| instruction.program_id == RAYDIUM_CLMM_PROGRAM_ID)
+            .collect();
+        let discriminators: Vec<&[u8]> = calls.iter().map(|call| &call.data[..8]).collect();
+        assert_eq!(
+            discriminators,
+            [
+                DECREASE_LIQUIDITY_V2,
+                DECREASE_LIQUIDITY_V2,
+                CLOSE_POSITION,
+                OPEN_POSITION_WITH_TOKEN22_NFT,
+                INCREASE_LIQUIDITY_V2,
+            ]
+        );
+        let vault = fixture.key(VaultFixture::VAULT);
+        for call in &calls {
+            assert!(call.accounts.iter().any(|meta| meta.pubkey == vault && meta.is_signer));
+        }
+        // Fees are harvested before any liquidity is withdrawn
+        assert_eq!(calls[0].data[8..], [0; 32]);
+        assert_eq!(calls[1].data[8..24], VaultFixture::LIQUIDITY.to_le_bytes());
+        assert_eq!(calls[3].data[16..24], args()[..]);
+
+        // The closed accounts' rent passes through the vault to the agent,
+        // who paid for the record and history
+        let accounts = &fixture.accounts;
+        let logged = [VaultFixture::RECORD, VaultFixture::HISTORY]
+            .iter()
+            .map(|&index| accounts[index].lamports)
+            .sum::<u64>();
+        assert_eq!(accounts[0].lamports, agent_lamports + closed_rent - logged);
+        assert_eq!(accounts[VaultFixture::VAULT].lamports, vault_lamports);
+
+        let vault: VaultState = accounts[VaultFixture::VAULT].unpack();
+        assert_eq!(vault.position_mint, fixture.key(VaultFixture::NEW_POSITION_MINT));
+        let record: RebalanceRecord = accounts[VaultFixture::RECORD].unpack();
+        let history: RebalanceHistory = accounts[VaultFixture::HISTORY].unpack();
+        assert!(!history.is_agent_reported(0));
+        assert!(emitted(&RebalanceExecuted {
+            config: fixture.key(VaultFixture::CONFIG),
+            position_mint: record.position_mint,
+            agent: fixture.agent,
+            old_tick_lower: VaultFixture::TICKS.0,
+            old_tick_upper: VaultFixture::TICKS.1,
+            new_tick_lower: VaultFixture::NEW_TICKS.0,
+            new_tick_upper: VaultFixture::NEW_TICKS.1,
+            realized_slippage_bps: 0,
+            fees_claimed_a: FEES.0,
+            fees_claimed_b: FEES.1,
+            rebalance_count: 1,
+            executed_at: NOW,
+            agent_reported: false,
+        }));
+    }
+
+    #[test]
+    fn vault_rebalance_needs_the_tick_array_starts() {
+        let mut fixture = fixture();
+        let data = fixture.data(&args()[..4]);
+        let result = process(&mut fixture.accounts, &data);
+        assert_eq!(result, Err(ProgramError::InvalidInstructionData));
+        assert!(cpis().is_empty());
+    }
+}
@@ -0,0 +1,611 @@
+//! Processor-level test harness. Accounts are laid out the way the runtime
+//! passes them to the entrypoint, so reallocs and owner changes behave as
+//! on-chain; sysvars and logs go through syscall stubs, and system and SPL
+//! Token CPIs are emulated. Other CPIs are recorded and handed to a hook.
+
+use std::{cell::RefCell, sync::Once};
+
+use poseidon_state::{
+    oracle::PYTH_RECEIVER_PROGRAM_ID,
+    position::{
+        ORCA_WHIRLPOOL_PROGRAM_ID, RAYDIUM_CLMM_PROGRAM_ID, TOKEN_2022_PROGRAM_ID, TOKEN_PROGRAM_ID,
+    },
+    AccountState, DexProgram, Event, OracleFeed, ProgramConfig, Protocol, RebalanceConfig,
+    RebalanceHistory, RebalanceRecord, VaultState,
+};
+use solana_program::{
+    account_info::AccountInfo,
+    bpf_loader_upgradeable,
+    clock::Clock,
+    entrypoint::{
+        deserialize, ProgramResult, BPF_ALIGN_OF_U128, MAX_PERMITTED_DATA_INCREASE, NON_DUP_MARKER,
+    },
+    instruction::Instruction,
+    native_token::LAMPORTS_PER_SOL,
+    program_error::ProgramError,
+    program_stubs::{set_syscall_stubs, SyscallStubs},
+    program_utils::limited_deserialize,
+    pubkey::Pubkey,
+    rent::Rent,
+    system_instruction::{SystemError, SystemInstruction},
+    system_program,
+};
+
+pub const PROGRAM_ID: Pubkey = Pubkey::new_from_array([0x50; 32]);
+/// Clock time every test starts at.
+pub const NOW: i64 = 1_700_000_000;
+
+// SPL Token instruction tags and errors the emulation uses
+const TOKEN_TRANSFER: u8 = 3;
+const TOKEN_CLOSE_ACCOUNT: u8 = 9;
+const TOKEN_INSUFFICIENT_FUNDS: u32 = 1;
+const TOKEN_OWNER_MISMATCH: u32 = 4;
+const TOKEN_NON_NATIVE_HAS_BALANCE: u32 = 11;
+// sha256("account:PriceUpdateV2")[0..8]
+const PYTH_PRICE_UPDATE_DISCRIMINATOR: [u8; 8] = [34, 241, 35, 99, 157, 126, 244, 205];
+
+/// Handles a CPI the harness does not emulate, e.g. a DEX call, with the
+/// accounts passed to it.
+pub type CpiHook = Box<dyn FnMut(&Instruction, &[AccountInfo]) -> ProgramResult>;
+
+/// An account as a test sets it up and reads it back.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Account {
+    pub key: Pubkey,
+    pub owner: Pubkey,
+    pub lamports: u64,
+    pub data: Vec<u8>,
+    pub is_signer: bool,
+    pub is_writable: bool,
+    pub executable: bool,
+}
+
+impl Account {
+    /// A writable, rent-exempt account holding `data`.
+    pub fn new(key: Pubkey, owner: Pubkey, data: Vec<u8>) -> Self {
+        Self {
+            key,
+            owner,
+            lamports: Rent::default().minimum_balance(data.len()),
+            data,
+            is_signer: false,
+            is_writable: true,
+            executable: false,
+        }
+    }
+
+    /// A wallet that signs and can pay rent.
+    pub fn signer(key: Pubkey) -> Self {
+        Self { lamports: 10 * LAMPORTS_PER_SOL, is_signer: true, ..Self::empty(key) }
+    }
+
+    /// A writable address nothing has been created at.
+    pub fn empty(key: Pubkey) -> Self {
+        Self { lamports: 0, ..Self::new(key, system_program::id(), Vec::new()) }
+    }
+
+    /// An executable program account.
+    pub fn program(id: Pubkey) -> Self {
+        Self {
+            executable: true,
+            is_writable: false,
+            ..Self::new(id, bpf_loader_upgradeable::id(), Vec::new())
+        }
+    }
+
+    /// A program-owned account holding `state` in its current layout.
+    pub fn state<T: AccountState>(key: Pubkey, state: &T) -> Self {
+        let mut data = vec![0; T::LEN];
+        state.pack(&mut data).unwrap();
+        Self::new(key, PROGRAM_ID, data)
+    }
+
+    pub fn readonly(self) -> Self {
+        Self { is_writable: false, ..self }
+    }
+
+    pub fn unsigned(self) -> Self {
+        Self { is_signer: false, ..self }
+    }
+
+    pub fn unpack<T: AccountState>(&self) -> T {
+        T::unpack(&self.data).unwrap()
+    }
+
+    /// Whether the account was closed: no lamports left.
+    pub fn is_closed(&self) -> bool {
+        self.lamports == 0
+    }
+}
+
+/// A current program config at its PDA, run by `admin` with `agents`.
+pub fn program_config(admin: Pubkey, agents: &[Pubkey]) -> Account {
+    let (address, bump) = ProgramConfig::find_address(&PROGRAM_ID);
+    let program_config = ProgramConfig {
+        version: ProgramConfig::VERSION,
+        bump,
+        admin,
+        agents: agents.to_vec(),
+        slippage_cap_bps: ProgramConfig::DEFAULT_SLIPPAGE_CAP_BPS,
+        min_yield_cap_bps: ProgramConfig::DEFAULT_MIN_YIELD_CAP_BPS,
+        dex_programs: DexProgram::defaults(),
+        oracle_max_age_seconds: ProgramConfig::DEFAULT_ORACLE_MAX_AGE_SECONDS,
+        oracle_max_deviation_bps: ProgramConfig::DEFAULT_ORACLE_MAX_DEVIATION_BPS,
+        ..ProgramConfig::default()
+    };
+    Account::state(address, &program_config)
+}
+
+/// `program_config` with agent actions paused.
+pub fn paused(mut program_config: Account) -> Account {
+    program_config.data[ProgramConfig::PAUSED_OFFSET] = 1;
+    program_config
+}
+
+/// An enabled, current config for `owner`'s position at its PDA.
+pub fn rebalance_config(owner: Pubkey, position_mint: Pubkey, protocol: Protocol) -> Account {
+    let (address, bump) = RebalanceConfig::find_address(&owner, &position_mint, &PROGRAM_ID);
+    let config = RebalanceConfig {
+        owner,
+        position_mint,
+        enabled: true,
+        max_slippage_bps: 100,
+        created_at: NOW,
+        updated_at: NOW,
+        version: RebalanceConfig::VERSION,
+        bump,
+        protocol,
+        ..RebalanceConfig::default()
+    };
+    Account::state(address, &config)
+}
+
+/// An initialized SPL Token mint.
+pub fn mint(key: Pubkey, supply: u64, decimals: u8) -> Account {
+    let mut data = vec![0; 82];
+    data[36..44].copy_from_slice(&supply.to_le_bytes());
+    data[44] = decimals;
+    data[45] = 1; // is_initialized
+    Account::new(key, TOKEN_PROGRAM_ID, data)
+}
+
+/// An initialized SPL Token account of `owner` holding `amount` of `mint`.
+pub fn token_account(key: Pubkey, mint: Pubkey, owner: Pubkey, amount: u64) -> Account {
+    let mut data = vec![0; 165];
+    data[..32].copy_from_slice(mint.as_ref());
+    data[32..64].copy_from_slice(owner.as_ref());
+    data[64..72].copy_from_slice(&amount.to_le_bytes());
+    data[108] = 1; // AccountState::Initialized
+    Account::new(key, TOKEN_PROGRAM_ID, data)
+}
+
+/// A fully verified Pyth price update for `feed_id` of `price * 10^expo`,
+/// published now.
+pub fn price_update(key: Pubkey, feed_id: [u8; 32], price: i64, expo: i32) -> Account {
+    let mut data = PYTH_PRICE_UPDATE_DISCRIMINATOR.to_vec();
+    data.extend_from_slice(&[0; 32]); // write_authority
+    data.push(1); // VerificationLevel::Full
+    data.extend_from_slice(&feed_id);
+    data.extend_from_slice(&price.to_le_bytes());
+    data.extend_from_slice(&0u64.to_le_bytes()); // conf
+    data.extend_from_slice(&expo.to_le_bytes());
+    data.extend_from_slice(&NOW.to_le_bytes()); // publish_time
+    Account::new(key, PYTH_RECEIVER_PROGRAM_ID, data)
+}
+
+/// The oracle feed set for a token pair, at its PDA.
+pub fn oracle_feed(mint_a: Pubkey, mint_b: Pubkey, feed_id: [u8; 32]) -> Account {
+    let (address, bump) = OracleFeed::find_address(&mint_a, &mint_b, &PROGRAM_ID);
+    let feed = OracleFeed {
+        version: OracleFeed::VERSION,
+        bump,
+        mint_a,
+        mint_b,
+        feed_id,
+        updated_at: NOW,
+    };
+    Account::state(address, &feed)
+}
+
+/// Where a CLMM keeps the pool and position fields the fixtures fill in.
+struct DexLayout {
+    program_id: Pubkey,
+    pool_len: usize,
+    sqrt_price: usize,
+    mint_a: usize,
+    mint_b: usize,
+    position_len: usize,
+    position_pool: usize,
+    tick_lower: usize,
+    liquidity: usize,
+}
+
+const WHIRLPOOL_LAYOUT: DexLayout = DexLayout {
+    program_id: ORCA_WHIRLPOOL_PROGRAM_ID,
+    pool_len: 653,
+    sqrt_price: 65,
+    mint_a: 101,
+    mint_b: 181,
+    position_len: 216,
+    position_pool: 8,
+    tick_lower: 88,
+    liquidity: 72,
+};
+
+const RAYDIUM_LAYOUT: DexLayout = DexLayout {
+    program_id: RAYDIUM_CLMM_PROGRAM_ID,
+    pool_len: 1544,
+    sqrt_price: 253,
+    mint_a: 73,
+    mint_b: 105,
+    position_len: 281,
+    position_pool: 41,
+    tick_lower: 73,
+    liquidity: 81,
+};
+
+/// `owner`'s position NFT sitting in its vault, in a pool of two 6-decimal
+/// tokens trading 1:1 on-chain and at the oracle. `accounts` are the ones
+/// `vault_rebalance` takes ahead of the DEX accounts, signed by `agent`.
+pub struct VaultFixture {
+    pub agent: Pubkey,
+    pub owner: Pubkey,
+    pub accounts: Vec<Account>,
+}
+
+impl VaultFixture {
+    pub const CONFIG: usize = 1;
+    pub const VAULT: usize = 2;
+    pub const PROGRAM_CONFIG: usize = 3;
+    pub const RECORD: usize = 4;
+    pub const HISTORY: usize = 5;
+    pub const VAULT_TOKEN_A: usize = 12;
+    pub const VAULT_TOKEN_B: usize = 13;
+    pub const POSITION: usize = 16;
+    pub const POSITION_TOKEN_ACCOUNT: usize = 18;
+    pub const NEW_POSITION_MINT: usize = 20;
+    /// Liquidity of the vaulted position.
+    pub const LIQUIDITY: u128 = 1_000_000;
+    pub const TICKS: (i32, i32) = (-100, 100);
+    /// Range and liquidity `data` moves the position to.
+    pub const NEW_TICKS: (i32, i32) = (-200, 200);
+    pub const NEW_LIQUIDITY: u128 = 500_000;
+
+    pub fn new(protocol: Protocol) -> Self {
+        let (agent, owner) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let (mint_a, mint_b) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let (position_mint, new_position_mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let layout = match protocol {
+            Protocol::OrcaWhirlpool => &WHIRLPOOL_LAYOUT,
+            Protocol::RaydiumClmm => &RAYDIUM_LAYOUT,
+            protocol => panic!("no vault fixture for {protocol:?} positions"),
+        };
+        let dex_program = layout.program_id;
+        let pool = Pubkey::new_unique();
+        let feed_id = [7; 32];
+
+        let mut config = rebalance_config(owner, position_mint, protocol);
+        let mut state: RebalanceConfig = config.unpack();
+        state.vaulted = true;
+        state.pack(&mut config.data).unwrap();
+        let (vault, bump) = VaultState::find_address(&config.key, &PROGRAM_ID);
+        let vault_state = VaultState {
+            version: VaultState::VERSION,
+            bump,
+            config: config.key,
+            owner,
+            position_mint,
+            created_at: NOW,
+            updated_at: NOW,
+            ..VaultState::default()
+        };
+        let record = RebalanceRecord::find_address(&config.key, &PROGRAM_ID).0;
+        let history = RebalanceHistory::find_address(&config.key, &PROGRAM_ID).0;
+
+        let mut pool_data = vec![0; layout.pool_len];
+        let write = |data: &mut [u8], offset: usize, bytes: &[u8]| {
+            data[offset..offset + bytes.len()].copy_from_slice(bytes);
+        };
+        write(&mut pool_data, layout.sqrt_price, &(1u128 << 64).to_le_bytes());
+        write(&mut pool_data, layout.mint_a, mint_a.as_ref());
+        write(&mut pool_data, layout.mint_b, mint_b.as_ref());
+        let mut position_data = vec![0; layout.position_len];
+        write(&mut position_data, layout.position_pool, pool.as_ref());
+        write(&mut position_data, layout.tick_lower, &Self::TICKS.0.to_le_bytes());
+        write(&mut position_data, layout.tick_lower + 4, &Self::TICKS.1.to_le_bytes());
+        write(&mut position_data, layout.liquidity, &Self::LIQUIDITY.to_le_bytes());
+
+        let position_address = |mint: &Pubkey| {
+            Pubkey::find_program_address(&[b"position", mint.as_ref()], &dex_program).0
+        };
+        let accounts = vec![
+            Account::signer(agent),
+            config,
+            Account::state(vault, &vault_state),
+            program_config(Pubkey::new_unique(), &[agent]).readonly(),
+            Account::empty(record),
+            Account::empty(history),
+            Account::program(system_program::id()),
+            Account::program(TOKEN_PROGRAM_ID),
+            price_update(Pubkey::new_unique(), feed_id, 100, -2).readonly(),
+            oracle_feed(mint_a, mint_b, feed_id).readonly(),
+            mint(mint_a, 1_000_000_000, 6).readonly(),
+            mint(mint_b, 1_000_000_000, 6).readonly(),
+            token_account(Pubkey::new_unique(), mint_a, vault, 0),
+            token_account(Pubkey::new_unique(), mint_b, vault, 0),
+            Account::program(dex_program),
+            Account::new(pool, dex_program, pool_data),
+            Account::new(position_address(&position_mint), dex_program, position_data),
+            mint(position_mint, 1, 0),
+            token_account(Pubkey::new_unique(), position_mint, vault, 1),
+            Account::empty(position_address(&new_position_mint)),
+            Account { is_signer: true, ..Account::empty(new_position_mint) },
+            Account::empty(Pubkey::new_unique()),
+        ];
+        Self { agent, owner, accounts }
+    }
+
+    /// `vault_rebalance` data moving to `NEW_TICKS` with `NEW_LIQUIDITY`,
+    /// followed by the DEX's `args`.
+    pub fn data(&self, args: &[u8]) -> Vec<u8> {
+        let mut data = Self::NEW_TICKS.0.to_le_bytes().to_vec();
+        data.extend_from_slice(&Self::NEW_TICKS.1.to_le_bytes());
+        data.extend_from_slice(&Self::NEW_LIQUIDITY.to_le_bytes());
+        data.extend_from_slice(&10_000u64.to_le_bytes()); // token_max_a
+        data.extend_from_slice(&10_000u64.to_le_bytes()); // token_max_b
+        data.extend_from_slice(args);
+        ix_data(crate::IX_VAULT_REBALANCE, &data)
+    }
+
+    pub fn key(&self, index: usize) -> Pubkey {
+        self.accounts[index].key
+    }
+}
+
+/// Instruction data: a discriminator followed by its arguments.
+pub fn ix_data(discriminator: [u8; 8], args: &[u8]) -> Vec<u8> {
+    [&discriminator[..], args].concat()
+}
+
+/// Run one instruction over `accounts`. On success the accounts are updated
+/// in place; on failure they are left as they were, as the runtime would.
+pub fn process(accounts: &mut [Account], data: &[u8]) -> ProgramResult {
+    static STUBS: Once = Once::new();
+    STUBS.call_once(|| {
+        set_syscall_stubs(Box::new(Stubs));
+    });
+    RUNTIME.with(|runtime| {
+        let mut runtime = runtime.borrow_mut();
+        runtime.events.clear();
+        runtime.cpis.clear();
+    });
+
+    let mut input = serialize(accounts, data);
+    // SAFETY: `input` is laid out as the runtime lays out program input and
+    // outlives `infos`.
+    let (program_id, infos, data) = unsafe { deserialize(input.as_mut_ptr() as *mut u8) };
+    let result = crate::process_instruction(program_id, &infos, data);
+    if result.is_ok() {
+        for (account, info) in accounts.iter_mut().zip(&infos) {
+            account.owner = *info.owner;
+            account.lamports = info.lamports();
+            account.data = info.data.borrow().to_vec();
+        }
+    }
+    result
+}
+
+/// Whether the last instruction emitted `event`.
+pub fn emitted<E: Event>(event: &E) -> bool {
+    let data = event.data();
+    RUNTIME.with(|runtime| runtime.borrow().events.contains(&data))
+}
+
+/// Route CPIs the harness does not emulate to `hook`.
+pub fn on_cpi(hook: CpiHook) {
+    RUNTIME.with(|runtime| runtime.borrow_mut().hook = Some(hook));
+}
+
+/// CPIs the last instruction made, in order.
+pub fn cpis() -> Vec<Instruction> {
+    RUNTIME.with(|runtime| runtime.borrow().cpis.clone())
+}
+
+/// Add `amount` to the balance of the token account `key` among `infos`, as
+/// a DEX paying out to it would.
+pub fn credit_tokens(infos: &[AccountInfo], key: &Pubkey, amount: u64) -> ProgramResult {
+    let account = account(infos, key)?;
+    let balance = token_amount(&account.try_borrow_data()?);
+    set_token_amount(account, balance + amount)
+}
+
+/// Amount held by an SPL token account.
+pub fn token_amount(data: &[u8]) -> u64 {
+    u64::from_le_bytes(data[64..72].try_into().unwrap())
+}
+
+struct Runtime {
+    now: i64,
+    events: Vec<Vec<u8>>,
+    cpis: Vec<Instruction>,
+    hook: Option<CpiHook>,
+}
+
+thread_local! {
+    // Each test runs on its own thread, so state never leaks between tests
+    static RUNTIME: RefCell<Runtime> = RefCell::new(Runtime {
+        now: NOW,
+        events: Vec::new(),
+        cpis: Vec::new(),
+        hook: None,
+    });
+}
+
+// Program input as `entrypoint::deserialize` reads it, in u128s for alignment
+fn serialize(accounts: &[Account], data: &[u8]) -> Vec<u128> {
+    let mut bytes = (accounts.len() as u64).to_le_bytes().to_vec();
+    for (index, account) in accounts.iter().enumerate() {
+        if let Some(original) = accounts[..index].iter().position(|a| a.key == account.key) {
+            bytes.push(original as u8);
+            bytes.extend_from_slice(&[0; 7]);
+            continue;
+        }
+        bytes.push(NON_DUP_MARKER);
+        bytes.push(account.is_signer as u8);
+        bytes.push(account.is_writable as u8);
+        bytes.push(account.executable as u8);
+        bytes.extend_from_slice(&[0; 4]); // original_data_len
+        bytes.extend_from_slice(account.key.as_ref());
+        bytes.extend_from_slice(account.owner.as_ref());
+        bytes.extend_from_slice(&account.lamports.to_le_bytes());
+        bytes.extend_from_slice(&(account.data.len() as u64).to_le_bytes());
+        bytes.extend_from_slice(&account.data);
+        bytes.resize(bytes.len() + MAX_PERMITTED_DATA_INCREASE, 0);
+        bytes.resize(bytes.len().next_multiple_of(BPF_ALIGN_OF_U128), 0);
+        bytes.extend_from_slice(&0u64.to_le_bytes()); // rent_epoch
+    }
+    bytes.extend_from_slice(&(data.len() as u64).to_le_bytes());
+    bytes.extend_from_slice(data);
+    bytes.extend_from_slice(PROGRAM_ID.as_ref());
+
+    let mut input = vec![0u128; bytes.len().div_ceil(16)];
+    // SAFETY: `input` holds at least `bytes.len()` bytes
+    unsafe {
+        std::ptr::copy_nonoverlapping(bytes.as_ptr(), input.as_mut_ptr() as *mut u8, bytes.len());
+    }
+    input
+}
+
+struct Stubs;
+
+impl SyscallStubs for Stubs {
+    fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+        let now = RUNTIME.with(|runtime| runtime.borrow().now);
+        // SAFETY: the caller passes a pointer to a `Clock`
+        unsafe { *(var_addr as *mut Clock) = Clock { unix_timestamp: now, ..Clock::default() } };
+        0
+    }
+
+    fn sol_get_rent_sysvar(&self, var_addr: *mut u8) -> u64 {
+        // SAFETY: the caller passes a pointer to a `Rent`
+        unsafe { *(var_addr as *mut Rent) = Rent::default() };
+        0
+    }
+
+    fn sol_log_data(&self, fields: &[&[u8]]) {
+        RUNTIME.with(|runtime| runtime.borrow_mut().events.push(fields.concat()));
+    }
+
+    fn sol_invoke_signed(
+        &self,
+        instruction: &Instruction,
+        account_infos: &[AccountInfo],
+        signers_seeds: &[&[&[u8]]],
+    ) -> ProgramResult {
+        let pdas = signers_seeds
+            .iter()
+            .map(|seeds| Pubkey::create_program_address(seeds, &PROGRAM_ID))
+            .collect::<Result<Vec<_>, _>>()?;
+        for meta in instruction.accounts.iter().filter(|meta| meta.is_signer) {
+            let signed = account(account_infos, &meta.pubkey)?.is_signer;
+            if !signed && !pdas.contains(&meta.pubkey) {
+                return Err(ProgramError::MissingRequiredSignature);
+            }
+        }
+        RUNTIME.with(|runtime| runtime.borrow_mut().cpis.push(instruction.clone()));
+
+        let program_id = instruction.program_id;
+        if program_id == system_program::id() {
+            system(instruction, account_infos)
+        } else if program_id == TOKEN_PROGRAM_ID || program_id == TOKEN_2022_PROGRAM_ID {
+            token(instruction, account_infos)
+        } else {
+            // Taken out while it runs so it may call back into the harness
+            let hook = RUNTIME.with(|runtime| runtime.borrow_mut().hook.take());
+            let Some(mut hook) = hook else { return Ok(()) };
+            let result = hook(instruction, account_infos);
+            RUNTIME.with(|runtime| runtime.borrow_mut().hook = Some(hook));
+            result
+        }
+    }
+}
+
+fn account<'a, 'b>(
+    infos: &'a [AccountInfo<'b>],
+    key: &Pubkey,
+) -> Result<&'a AccountInfo<'b>, ProgramError> {
+    infos.iter().find(|info| info.key == key).ok_or(ProgramError::NotEnoughAccountKeys)
+}
+
+fn meta_account<'a, 'b>(
+    instruction: &Instruction,
+    infos: &'a [AccountInfo<'b>],
+    index: usize,
+) -> Result<&'a AccountInfo<'b>, ProgramError> {
+    let meta = instruction.accounts.get(index).ok_or(ProgramError::NotEnoughAccountKeys)?;
+    account(infos, &meta.pubkey)
+}
+
+fn move_lamports(from: &AccountInfo, to: &AccountInfo, lamports: u64) -> ProgramResult {
+    if from.lamports() < lamports {
+        return Err(ProgramError::Custom(SystemError::ResultWithNegativeLamports as u32));
+    }
+    **from.try_borrow_mut_lamports()? -= lamports;
+    **to.try_borrow_mut_lamports()? += lamports;
+    Ok(())
+}
+
+fn system(instruction: &Instruction, infos: &[AccountInfo]) -> ProgramResult {
+    let from = meta_account(instruction, infos, 0)?;
+    let to = meta_account(instruction, infos, 1)?;
+    let instruction_data = limited_deserialize(&instruction.data, 1232)
+        .map_err(|_| ProgramError::InvalidInstructionData)?;
+    match instruction_data {
+        SystemInstruction::CreateAccount { lamports, space, owner } => {
+            if to.lamports() > 0 || !to.data_is_empty() {
+                return Err(ProgramError::Custom(SystemError::AccountAlreadyInUse as u32));
+            }
+            move_lamports(from, to, lamports)?;
+            to.realloc(space as usize, true)?;
+            to.assign(&owner);
+            Ok(())
+        }
+        SystemInstruction::Transfer { lamports } => move_lamports(from, to, lamports),
+        _ => Err(ProgramError::InvalidInstructionData),
+    }
+}
+
+fn set_token_amount(account: &AccountInfo, amount: u64) -> ProgramResult {
+    account.try_borrow_mut_data()?[64..72].copy_from_slice(&amount.to_le_bytes());
+    Ok(())
+}
+
+fn token(instruction: &Instruction, infos: &[AccountInfo]) -> ProgramResult {
+    let source = meta_account(instruction, infos, 0)?;
+    let destination = meta_account(instruction, infos, 1)?;
+    let authority = meta_account(instruction, infos, 2)?;
+    if source.try_borrow_data()?[32..64] != authority.key.as_ref()[..] {
+        return Err(ProgramError::Custom(TOKEN_OWNER_MISMATCH));
+    }
+    match instruction.data.first() {
+        Some(&TOKEN_TRANSFER) => {
+            let amount = u64::from_le_bytes(instruction.data[1..9].try_into().unwrap());
+            let balance = token_amount(&source.try_borrow_data()?);
+            if balance < amount {
+                return Err(ProgramError::Custom(TOKEN_INSUFFICIENT_FUNDS));
+            }
+            set_token_amount(source, balance - amount)?;
+            let balance = token_amount(&destination.try_borrow_data()?);
+            set_token_amount(destination, balance + amount)
+        }
+        Some(&TOKEN_CLOSE_ACCOUNT) => {
+            if token_amount(&source.try_borrow_data()?) != 0 {
+                return Err(ProgramError::Custom(TOKEN_NON_NATIVE_HAS_BALANCE));
+            }
+            move_lamports(source, destination, source.lamports())?;
+            source.try_borrow_mut_data()?.fill(0);
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,265 @@
+use poseidon_state::{
+    position_holder, verify_position_owner, AccountState, ConfigUpdated, Event, PoseidonError,
+    RebalanceConfig, RebalanceDisabled, RebalanceEnabled, VaultState, REBALANCE_SEED,
+};
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    clock::Clock,
+    entrypoint::ProgramResult,
+    msg,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    sysvar::Sysvar,
+};
+
+use crate::{close_account, create_pda_account, load_config};
+
+/// Re-key a config to whoever holds the position now. The new holder pays
+/// for the new PDA; the old one's rent goes back to the previous owner. Fees
+/// go to the new holder's wallet and no field inherits from a profile.
+pub fn process_transfer_config(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    // Accounts: [new_owner, old_config_pda, new_config_pda, position_mint, previous_owner,
+    //            system_program, (position_token_account)]
+    // position_token_account is required for NFT positions (Orca/Raydium).
+    let iter = &mut accounts.iter();
+    let new_owner = next_account_info(iter)?;
+    let old_config_account = next_account_info(iter)?;
+    let new_config_account = next_account_info(iter)?;
+    let position_mint = next_account_info(iter)?;
+    let previous_owner = next_account_info(iter)?;
+    let system_program = next_account_info(iter)?;
+    let position_token_account = next_account_info(iter).ok();
+
+    if !new_owner.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    let old_config = load_current_config(program_id, old_config_account, position_mint)?;
+    if old_config.owner != *previous_owner.key {
+        return Err(PoseidonError::ConfigOwnerMismatch.into());
+    }
+    verify_position_owner(new_owner.key, old_config.protocol, position_mint, position_token_account)?;
+
+    let (expected_pda, bump) = RebalanceConfig::find_address(new_owner.key, position_mint.key, program_id);
+    if *new_config_account.key != expected_pda {
+        return Err(PoseidonError::InvalidConfigAddress.into());
+    }
+    if !new_config_account.data_is_empty() {
+        msg!("{} already has a config for position {}", new_owner.key, position_mint.key);
+        return Err(PoseidonError::ConfigAlreadyExists.into());
+    }
+
+    create_pda_account(
+        program_id,
+        new_owner,
+        new_config_account,
+        RebalanceConfig::LEN,
+        system_program,
+        &[REBALANCE_SEED, new_owner.key.as_ref(), position_mint.key.as_ref(), &[bump]],
+    )?;
+    let now = Clock::get()?.unix_timestamp;
+    let config = RebalanceConfig {
+        owner: *new_owner.key,
+        bump,
+        stale: false,
+        // The previous owner's fee wallet and profile links are theirs
+        inherit: 0,
+        fee_destination: Pubkey::default(),
+        // The new holder has the NFT itself, not through a vault
+        vaulted: false,
+        updated_at: now,
+        ..old_config
+    };
+    config.pack(&mut new_config_account.try_borrow_mut_data()?)?;
+
+    close_account(old_config_account, previous_owner)?;
+    RebalanceDisabled::new(*old_config_account.key, &old_config, true, now).emit();
+    RebalanceEnabled::new(*new_config_account.key, &config, false, now).emit();
+
+    msg!(
+        "Config for position {} transferred from {} to {}",
+        position_mint.key,
+        previous_owner.key,
+        new_owner.key
+    );
+    Ok(())
+}
+
+/// Permissionless: mark a config stale once its owner no longer holds the
+/// position, so the agent stops acting on it. A position in the config's
+/// vault still counts as held.
+pub fn process_flag_stale_config(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    // Accounts: [config_pda, position_mint, (position_token_account)]
+    // position_token_account is the account currently holding the NFT.
+    let iter = &mut accounts.iter();
+    let config_account = next_account_info(iter)?;
+    let position_mint = next_account_info(iter)?;
+    let position_token_account = next_account_info(iter).ok();
+
+    let mut config = load_current_config(program_id, config_account, position_mint)?;
+    let holder = position_holder(config.protocol, position_mint, position_token_account)?;
+    let (vault, _bump) = VaultState::find_address(config_account.key, program_id);
+    if holder == config.owner || holder == vault {
+        return Err(PoseidonError::PositionStillHeld.into());
+    }
+
+    if !config.stale {
+        let now = Clock::get()?.unix_timestamp;
+        config.stale = true;
+        config.updated_at = now;
+        config.pack(&mut config_account.try_borrow_mut_data()?)?;
+        ConfigUpdated::new(*config_account.key, &config, ConfigUpdated::FIELD_STALE, now).emit();
+    }
+
+    msg!(
+        "Config for position {} flagged stale: owner {}, holder {}",
+        position_mint.key,
+        config.owner,
+        holder
+    );
+    Ok(())
+}
+
+/// Load a config at the current layout and check it is the PDA for its
+/// recorded owner and `position_mint`.
+fn load_current_config(
+    program_id: &Pubkey,
+    config_account: &AccountInfo,
+    position_mint: &AccountInfo,
+) -> Result<RebalanceConfig, ProgramError> {
+    let config = load_config(program_id, config_account)?;
+    if config.needs_migration() {
+        return Err(PoseidonError::ConfigNeedsMigration.into());
+    }
+    let (expected_pda, _bump) = RebalanceConfig::find_address(&config.owner, position_mint.key, program_id);
+    if *config_account.key != expected_pda {
+        return Err(PoseidonError::InvalidConfigAddress.into());
+    }
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use poseidon_state::Protocol;
+    use solana_program::system_program;
+
+    use super::*;
+    use crate::{test_utils::*, IX_FLAG_STALE_CONFIG, IX_TRANSFER_CONFIG};
+
+    struct Handover {
+        previous_owner: Pubkey,
+        new_owner: Pubkey,
+        position_mint: Pubkey,
+        config: Account,
+    }
+
+    /// An Orca position whose NFT `previous_owner` configured and then sold.
+    fn handover() -> Handover {
+        let (previous_owner, new_owner) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let position_mint = Pubkey::new_unique();
+        let config = rebalance_config(previous_owner, position_mint, Protocol::OrcaWhirlpool);
+        let mut state: RebalanceConfig = config.unpack();
+        state.max_slippage_bps = 75;
+        state.inherit = 1;
+        state.fee_destination = Pubkey::new_unique();
+        let config = Account::state(config.key, &state);
+        Handover { previous_owner, new_owner, position_mint, config }
+    }
+
+    fn transfer_accounts(handover: &Handover, holder: Pubkey) -> Vec<Account> {
+        let Handover { previous_owner, new_owner, position_mint, .. } = *handover;
+        let new_config = RebalanceConfig::find_address(&new_owner, &position_mint, &PROGRAM_ID).0;
+        vec![
+            Account::signer(new_owner),
+            handover.config.clone(),
+            Account::empty(new_config),
+            mint(position_mint, 1, 0).readonly(),
+            Account::new(previous_owner, system_program::id(), Vec::new()),
+            Account::program(system_program::id()),
+            token_account(Pubkey::new_unique(), position_mint, holder, 1).readonly(),
+        ]
+    }
+
+    #[test]
+    fn new_holder_takes_over_the_config() {
+        let handover = handover();
+        let mut accounts = transfer_accounts(&handover, handover.new_owner);
+        let (old_rent, previous_balance) = (accounts[1].lamports, accounts[4].lamports);
+        process(&mut accounts, &ix_data(IX_TRANSFER_CONFIG, &[])).unwrap();
+
+        let old: RebalanceConfig = handover.config.unpack();
+        let config: RebalanceConfig = accounts[2].unpack();
+        assert_eq!(config.owner, handover.new_owner);
+        assert_eq!(config.position_mint, handover.position_mint);
+        assert_eq!(config.max_slippage_bps, 75);
+        assert_eq!((config.inherit, config.fee_destination), (0, Pubkey::default()));
+        assert!(emitted(&RebalanceEnabled::new(accounts[2].key, &config, false, NOW)));
+
+        assert!(accounts[1].is_closed());
+        assert_eq!(accounts[4].lamports, previous_balance + old_rent);
+        assert!(emitted(&RebalanceDisabled::new(accounts[1].key, &old, true, NOW)));
+    }
+
+    #[test]
+    fn only_the_holder_takes_over_the_config() {
+        let handover = handover();
+        let mut accounts = transfer_accounts(&handover, Pubkey::new_unique());
+        assert_eq!(
+            process(&mut accounts, &ix_data(IX_TRANSFER_CONFIG, &[])),
+            Err(PoseidonError::PositionNotOwned.into())
+        );
+
+        let mut accounts = transfer_accounts(&handover, handover.new_owner);
+        accounts[0].is_signer = false;
+        assert_eq!(
+            process(&mut accounts, &ix_data(IX_TRANSFER_CONFIG, &[])),
+            Err(ProgramError::MissingRequiredSignature)
+        );
+
+        // The rent goes back to the config's owner, no one else
+        let mut accounts = transfer_accounts(&handover, handover.new_owner);
+        accounts[4].key = Pubkey::new_unique();
+        assert_eq!(
+            process(&mut accounts, &ix_data(IX_TRANSFER_CONFIG, &[])),
+            Err(PoseidonError::ConfigOwnerMismatch.into())
+        );
+    }
+
+    fn flag_accounts(handover: &Handover, holder: Pubkey) -> Vec<Account> {
+        vec![
+            handover.config.clone(),
+            mint(handover.position_mint, 1, 0).readonly(),
+            token_account(Pubkey::new_unique(), handover.position_mint, holder, 1).readonly(),
+        ]
+    }
+
+    #[test]
+    fn config_goes_stale_once_the_owner_sells() {
+        let handover = handover();
+        let mut accounts = flag_accounts(&handover, handover.new_owner);
+        process(&mut accounts, &ix_data(IX_FLAG_STALE_CONFIG, &[])).unwrap();
+        let config: RebalanceConfig = accounts[0].unpack();
+        assert!(config.stale);
+        let event = ConfigUpdated::new(accounts[0].key, &config, ConfigUpdated::FIELD_STALE, NOW);
+        assert!(emitted(&event));
+
+        // Flagging again changes nothing
+        let data = accounts[0].data.clone();
+        process(&mut accounts, &ix_data(IX_FLAG_STALE_CONFIG, &[])).unwrap();
+        assert_eq!(accounts[0].data, data);
+        assert!(!emitted(&event));
+    }
+
+    #[test]
+    fn held_positions_cannot_be_flagged() {
+        let handover = handover();
+        let vault = VaultState::find_address(&handover.config.key, &PROGRAM_ID).0;
+        for holder in [handover.previous_owner, vault] {
+            let mut accounts = flag_accounts(&handover, holder);
+            assert_eq!(
+                process(&mut accounts, &ix_data(IX_FLAG_STALE_CONFIG, &[])),
+                Err(PoseidonError::PositionStillHeld.into())
+            );
+        }
+    }
+}
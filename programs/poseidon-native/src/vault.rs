@@ -0,0 +1,811 @@
+use poseidon_state::{
+    math::{mul_div, value_in_b},
+    pool_current_tick, token_balance, verify_position_owner, AccountState, HistoryEntry,
+    PoseidonError, PositionRange, Protocol, RebalanceConfig, StrategyKind, VaultState, VAULT_SEED,
+};
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    clock::Clock,
+    entrypoint::ProgramResult,
+    instruction::{AccountMeta, Instruction},
+    msg,
+    program::{invoke, invoke_signed},
+    program_error::ProgramError,
+    pubkey::Pubkey,
+    sysvar::Sysvar,
+};
+
+use crate::{
+    admin::require_not_paused, close_account, create_pda_account, guard, load_active_config,
+    load_config, log_rebalance, profile, raydium, resize_account, whirlpool, RebalanceAccounts,
+};
+
+// SPL Token instruction tags, shared by Token and Token-2022.
+const TOKEN_TRANSFER: u8 = 3;
+const TOKEN_CLOSE_ACCOUNT: u8 = 9;
+
+/// Hand the position NFT to the vault PDA so the agent can rebalance it
+/// without the owner signing. The owner must have an up-to-date config for
+/// the position and creates the vault's token account for the NFT first.
+/// The config is marked `vaulted`.
+pub fn process_deposit_position(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    // Accounts: [owner, config_pda (writable), vault_pda, position_mint,
+    //            owner_position_token_account, vault_position_token_account, token_program,
+    //            system_program]
+    let iter = &mut accounts.iter();
+    let owner = next_account_info(iter)?;
+    let config_account = next_account_info(iter)?;
+    let vault_account = next_account_info(iter)?;
+    let position_mint = next_account_info(iter)?;
+    let owner_token_account = next_account_info(iter)?;
+    let vault_token_account = next_account_info(iter)?;
+    let token_program = next_account_info(iter)?;
+    let system_program = next_account_info(iter)?;
+
+    if !owner.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+
+    let mut config = load_config(program_id, config_account)?;
+    if config.needs_migration() {
+        return Err(PoseidonError::ConfigNeedsMigration.into());
+    }
+    if config.owner != *owner.key {
+        return Err(PoseidonError::ConfigOwnerMismatch.into());
+    }
+    let (expected_pda, _bump) = RebalanceConfig::find_address(owner.key, position_mint.key, program_id);
+    if *config_account.key != expected_pda {
+        return Err(PoseidonError::InvalidConfigAddress.into());
+    }
+    if !matches!(config.protocol, Protocol::OrcaWhirlpool | Protocol::RaydiumClmm) {
+        msg!("Vault mode does not support {:?} positions", config.protocol);
+        return Err(PoseidonError::VaultProtocolUnsupported.into());
+    }
+    verify_position_owner(owner.key, config.protocol, position_mint, Some(owner_token_account))?;
+
+    // Derive vault PDA: ["vault", config]
+    let (expected_pda, bump) = VaultState::find_address(config_account.key, program_id);
+    if *vault_account.key != expected_pda {
+        return Err(PoseidonError::InvalidVaultAccount.into());
+    }
+    if !vault_account.data_is_empty() {
+        msg!("Position {} is already in a vault", position_mint.key);
+        return Err(PoseidonError::PositionInVault.into());
+    }
+    token_balance(vault_account.key, vault_token_account, Some(position_mint.key))?;
+    if *token_program.key != *owner_token_account.owner {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+
+    create_pda_account(
+        program_id,
+        owner,
+        vault_account,
+        VaultState::LEN,
+        system_program,
+        &[VAULT_SEED, config_account.key.as_ref(), &[bump]],
+    )?;
+    let now = Clock::get()?.unix_timestamp;
+    let vault = VaultState {
+        version: VaultState::VERSION,
+        bump,
+        config: *config_account.key,
+        owner: *owner.key,
+        position_mint: *position_mint.key,
+        created_at: now,
+        updated_at: now,
+        ..VaultState::default()
+    };
+    vault.pack(&mut vault_account.try_borrow_mut_data()?)?;
+    config.vaulted = true;
+    config.pack(&mut config_account.try_borrow_mut_data()?)?;
+
+    invoke(
+        &token_instruction(
+            token_program.key,
+            TOKEN_TRANSFER,
+            &1u64.to_le_bytes(),
+            &[
+                AccountMeta::new(*owner_token_account.key, false),
+                AccountMeta::new(*vault_token_account.key, false),
+                AccountMeta::new_readonly(*owner.key, true),
+            ],
+        ),
+        &[
+            owner_token_account.clone(),
+            vault_token_account.clone(),
+            owner.clone(),
+            token_program.clone(),
+        ],
+    )?;
+
+    msg!("Position {} deposited into vault {}", position_mint.key, vault_account.key);
+    Ok(())
+}
+
+/// Return the NFT the vault currently holds to its owner, drain the vault's
+/// token accounts to the owner and close the vault. After a rebalance this is
+/// a different NFT than the one deposited, and the config for the original
+/// one can be reaped. Works even if the config was closed; otherwise its
+/// `vaulted` flag is cleared.
+pub fn process_withdraw_position(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+    // Accounts: [owner, config_pda, vault_pda, vault_position_token_account,
+    //            owner_position_token_account, token_program,
+    //            (vault_token_account, owner_token_account, token_program)...]
+    // Each trailing triple drains one of the vault's pool token accounts to the
+    // owner and closes it. Once the vault has rebalanced, the triples must
+    // cover both token accounts it recorded.
+    let iter = &mut accounts.iter();
+    let owner = next_account_info(iter)?;
+    let config_account = next_account_info(iter)?;
+    let vault_account = next_account_info(iter)?;
+    let vault_token_account = next_account_info(iter)?;
+    let owner_token_account = next_account_info(iter)?;
+    let token_program = next_account_info(iter)?;
+
+    if !owner.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    let vault = load_vault(program_id, vault_account)?;
+    if vault.owner != *owner.key {
+        return Err(PoseidonError::ConfigOwnerMismatch.into());
+    }
+    if *config_account.key != vault.config {
+        return Err(PoseidonError::InvalidConfigAddress.into());
+    }
+    let signer_seeds: &[&[u8]] = &[VAULT_SEED, vault.config.as_ref(), &[vault.bump]];
+
+    let amount = token_balance(vault_account.key, vault_token_account, Some(&vault.position_mint))?;
+    drain_token_account(
+        token_program,
+        vault_account,
+        vault_token_account,
+        owner_token_account,
+        owner,
+        amount,
+        signer_seeds,
+    )?;
+
+    let mut drained = Vec::new();
+    while let Ok(vault_token) = next_account_info(iter) {
+        let owner_token = next_account_info(iter)?;
+        let token_program = next_account_info(iter)?;
+        let amount = token_balance(vault_account.key, vault_token, None)?;
+        drain_token_account(
+            token_program,
+            vault_account,
+            vault_token,
+            owner_token,
+            owner,
+            amount,
+            signer_seeds,
+        )?;
+        drained.push(*vault_token.key);
+    }
+    if let Some((token_a, token_b)) = vault.token_accounts() {
+        if !drained.contains(&token_a) || !drained.contains(&token_b) {
+            msg!("Vault {} still keeps tokens in {} and {}", vault_account.key, token_a, token_b);
+            return Err(PoseidonError::InvalidVaultAccount.into());
+        }
+    }
+
+    // The config may have been reaped while the position sat in the vault
+    if config_account.owner == program_id && !config_account.data_is_empty() {
+        let mut config = load_config(program_id, config_account)?;
+        config.vaulted = false;
+        config.pack(&mut config_account.try_borrow_mut_data()?)?;
+    }
+    close_account(vault_account, owner)?;
+
+    msg!("Position {} withdrawn from vault to {}", vault.position_mint, owner.key);
+    Ok(())
+}
+
+/// Accounts every vault rebalance passes, whatever the DEX.
+#[derive(Clone, Copy)]
+pub struct VaultAccounts<'a, 'b> {
+    pub agent: &'a AccountInfo<'b>,
+    pub vault: &'a AccountInfo<'b>,
+    pub token_program: &'a AccountInfo<'b>,
+    pub system_program: &'a AccountInfo<'b>,
+    pub dex_program: &'a AccountInfo<'b>,
+    pub pool: &'a AccountInfo<'b>,
+    pub position: &'a AccountInfo<'b>,
+    pub position_mint: &'a AccountInfo<'b>,
+    pub position_token_account: &'a AccountInfo<'b>,
+    pub new_position: &'a AccountInfo<'b>,
+    pub new_position_mint: &'a AccountInfo<'b>,
+    pub new_position_token_account: &'a AccountInfo<'b>,
+    pub vault_token_a: &'a AccountInfo<'b>,
+    pub vault_token_b: &'a AccountInfo<'b>,
+    pub mint_a: &'a AccountInfo<'b>,
+    pub mint_b: &'a AccountInfo<'b>,
+}
+
+/// DEX-specific accounts of a vault rebalance.
+enum DexAccounts<'a, 'b> {
+    Whirlpool(whirlpool::WhirlpoolAccounts<'a, 'b>),
+    Raydium(raydium::RaydiumAccounts<'a, 'b>),
+}
+
+/// Amounts for the DEX calls of a vault rebalance.
+pub struct VaultRebalance {
+    /// Liquidity of the position being closed.
+    pub liquidity: u128,
+    pub new_tick_lower: i32,
+    pub new_tick_upper: i32,
+    pub liquidity_to_add: u128,
+    pub token_max_a: u64,
+    pub token_max_b: u64,
+    /// Least the withdrawal may return: the liquidity's worth at the oracle
+    /// price, less the owner's max slippage.
+    pub token_min_a: u64,
+    pub token_min_b: u64,
+}
+
+/// Tokens a DEX rebalance moved into the vault's token accounts.
+pub struct RebalanceOutcome {
+    /// Returned by withdrawing the old position's liquidity.
+    pub withdrawn_a: u64,
+    pub withdrawn_b: u64,
+    pub fees_a: u64,
+    pub fees_b: u64,
+}
+
+/// Rebalance a vaulted position through the DEX: all of its liquidity and
+/// fees are withdrawn, the old position closed and a new one opened at the
+/// given range, all signed by the vault PDA. Nothing is swapped; tokens that
+/// do not fit the new range stay in the vault's token accounts.
+pub fn process_vault_rebalance(
+    program_id: &Pubkey,
+    accounts: &[AccountInfo],
+    data: &[u8],
+) -> ProgramResult {
+    // Accounts: [agent, config_pda (writable), vault_pda, program_config_pda, record_pda,
+    //            history_pda, system_program, token_program, oracle, oracle_feed_pda, mint_a,
+    //            mint_b, vault_token_a, vault_token_b, dex_program, pool, position,
+    //            position_mint, position_token_account, new_position, new_position_mint,
+    //            new_position_token_account, ...DEX accounts, (user_profile_pda)]
+    // position is the position PDA of the NFT the vault holds and token_program
+    // that NFT's token program; new_position_mint is a fresh keypair that signs.
+    // vault_token_a/b are the vault's accounts for the pool's two mints.
+    // Whirlpool accounts: [token_vault_a, token_vault_b, tick_array_lower, tick_array_upper,
+    //                      new_tick_array_lower, new_tick_array_upper, rent,
+    //                      associated_token_program, pool_token_program]
+    // pool_token_program is SPL Token, used for every Whirlpool call but
+    // close_position.
+    // Raydium accounts: [protocol_position, new_protocol_position, token_vault_0,
+    //                    token_vault_1, tick_array_lower, tick_array_upper,
+    //                    new_tick_array_lower, new_tick_array_upper, spl_token_program,
+    //                    token_program_2022, memo_program, rent, associated_token_program]
+    let iter = &mut accounts.iter();
+    let agent = next_account_info(iter)?;
+    let config_account = next_account_info(iter)?;
+    let vault_account = next_account_info(iter)?;
+    let program_config_account = next_account_info(iter)?;
+    let record_account = next_account_info(iter)?;
+    let history_account = next_account_info(iter)?;
+    let system_program = next_account_info(iter)?;
+    let token_program = next_account_info(iter)?;
+    let oracle = next_account_info(iter)?;
+    let oracle_feed = next_account_info(iter)?;
+    let mint_a = next_account_info(iter)?;
+    let mint_b = next_account_info(iter)?;
+    let shared = VaultAccounts {
+        agent,
+        vault: vault_account,
+        token_program,
+        system_program,
+        vault_token_a: next_account_info(iter)?,
+        vault_token_b: next_account_info(iter)?,
+        dex_program: next_account_info(iter)?,
+        pool: next_account_info(iter)?,
+        position: next_account_info(iter)?,
+        position_mint: next_account_info(iter)?,
+        position_token_account: next_account_info(iter)?,
+        new_position: next_account_info(iter)?,
+        new_position_mint: next_account_info(iter)?,
+        new_position_token_account: next_account_info(iter)?,
+        mint_a,
+        mint_b,
+    };
+
+    if !agent.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    let program_config = require_not_paused(program_id, program_config_account)?;
+    if !program_config.is_agent(agent.key) {
+        msg!("Unauthorized agent {}", agent.key);
+        return Err(PoseidonError::UnauthorizedAgent.into());
+    }
+
+    // Data: new_lower(4) + new_upper(4) + liquidity_to_add(16) + token_max_a(8) + token_max_b(8)
+    //       + DEX args
+    // Whirlpool args: new_position_bump(1)
+    // Raydium args: tick_array_lower_start_index(4) + tick_array_upper_start_index(4)
+    // The withdrawal minimums are set from the oracle price, not by the agent.
+    if data.len() < 40 {
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    let new_lower = i32::from_le_bytes(data[0..4].try_into().unwrap());
+    let new_upper = i32::from_le_bytes(data[4..8].try_into().unwrap());
+    let liquidity_to_add = u128::from_le_bytes(data[8..24].try_into().unwrap());
+    let token_max_a = u64::from_le_bytes(data[24..32].try_into().unwrap());
+    let token_max_b = u64::from_le_bytes(data[32..40].try_into().unwrap());
+    if new_lower >= new_upper {
+        return Err(PoseidonError::InvalidTickRange.into());
+    }
+
+    let mut config = load_active_config(program_id, config_account)?;
+    let mut vault = load_vault(program_id, vault_account)?;
+    if vault.config != *config_account.key || vault.owner != config.owner {
+        return Err(PoseidonError::InvalidVaultAccount.into());
+    }
+    if *shared.position_mint.key != vault.position_mint {
+        msg!("Vault holds position {}, not {}", vault.position_mint, shared.position_mint.key);
+        return Err(PoseidonError::InvalidPositionAccount.into());
+    }
+    let dex_accounts = match config.protocol {
+        Protocol::OrcaWhirlpool => DexAccounts::Whirlpool(whirlpool::WhirlpoolAccounts::parse(iter)?),
+        Protocol::RaydiumClmm => DexAccounts::Raydium(raydium::RaydiumAccounts::parse(iter)?),
+        protocol => {
+            msg!("Vault mode does not support {:?} positions", protocol);
+            return Err(PoseidonError::VaultProtocolUnsupported.into());
+        }
+    };
+    let profile_account = next_account_info(iter).ok();
+
+    let profile = match profile_account {
+        Some(account) => Some(profile::load_profile(program_id, &config.owner, account)?),
+        None => None,
+    };
+    let settings = config.effective(profile.as_ref())?;
+
+    // The pool may trade no further from the oracle than the owner's max
+    // slippage while the vault withdraws and deposits at its price.
+    let (sqrt_price, deviation_bps) = guard::check_pool_price(
+        program_id,
+        &program_config,
+        config.protocol,
+        &vault.position_mint,
+        guard::PriceAccounts {
+            oracle,
+            oracle_feed,
+            pool: shared.pool,
+            dex_position: shared.position,
+            mint_a,
+            mint_b,
+        },
+    )?;
+    if deviation_bps > u64::from(settings.max_slippage_bps) {
+        msg!(
+            "Pool {} is {} bps from the oracle price, max slippage {} bps",
+            shared.pool.key,
+            deviation_bps,
+            settings.max_slippage_bps
+        );
+        return Err(PoseidonError::SlippageExceeded.into());
+    }
+    if *shared.dex_program.key != *shared.position.owner {
+        return Err(PoseidonError::DexProgramNotAccepted.into());
+    }
+    if *token_program.key != *shared.position_token_account.owner {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    token_balance(vault_account.key, shared.position_token_account, Some(&vault.position_mint))?;
+    token_balance(vault_account.key, shared.vault_token_a, Some(mint_a.key))?;
+    token_balance(vault_account.key, shared.vault_token_b, Some(mint_b.key))?;
+    // Leftover tokens stay in these accounts, so withdraw_position must find
+    // them: the first rebalance records them and later ones must reuse them
+    let token_accounts = (*shared.vault_token_a.key, *shared.vault_token_b.key);
+    if vault.token_accounts().is_some_and(|recorded| recorded != token_accounts) {
+        msg!("Vault {} keeps its tokens in {} and {}", vault_account.key, vault.token_a, vault.token_b);
+        return Err(PoseidonError::InvalidVaultAccount.into());
+    }
+    (vault.token_a, vault.token_b) = token_accounts;
+
+    let range = PositionRange::load(config.protocol, shared.position)?;
+    if settings.strategy.kind != StrategyKind::Unspecified {
+        let current_tick = pool_current_tick(config.protocol, shared.pool)?;
+        settings.strategy.validate(config.protocol)?;
+        if let Err(e) = settings.strategy.check_rebalance(
+            (range.tick_lower, range.tick_upper),
+            (new_lower, new_upper),
+            current_tick,
+        ) {
+            msg!(
+                "Range [{}, {}] at tick {} rejected by strategy {:?}",
+                new_lower,
+                new_upper,
+                current_tick,
+                settings.strategy
+            );
+            return Err(e.into());
+        }
+    }
+
+    let now = Clock::get()?.unix_timestamp;
+    if let Err(e) = config.record_rebalance(now) {
+        msg!(
+            "Rate limit for position {}: last at {}, {} of {} today",
+            config.position_mint,
+            config.last_rebalanced_at,
+            config.rebalances_in_window,
+            config.max_rebalances_per_day
+        );
+        return Err(e.into());
+    }
+    config.pack(&mut config_account.try_borrow_mut_data()?)?;
+
+    // Withdraw at no worse than the oracle price less the owner's max slippage
+    let expected = range.token_amounts(sqrt_price);
+    let keep_bps = 10_000u128.saturating_sub(u128::from(settings.max_slippage_bps));
+    let minimum = |amount: u64| (u128::from(amount) * keep_bps / 10_000) as u64;
+    let params = VaultRebalance {
+        liquidity: range.liquidity,
+        new_tick_lower: new_lower,
+        new_tick_upper: new_upper,
+        liquidity_to_add,
+        token_max_a,
+        token_max_b,
+        token_min_a: minimum(expected.0),
+        token_min_b: minimum(expected.1),
+    };
+    let signer_seeds: &[&[u8]] = &[VAULT_SEED, config_account.key.as_ref(), &[vault.bump]];
+    let outcome = match &dex_accounts {
+        DexAccounts::Whirlpool(accounts) => {
+            whirlpool::rebalance(&shared, accounts, &params, &data[40..], signer_seeds)?
+        }
+        DexAccounts::Raydium(accounts) => {
+            raydium::rebalance(&shared, accounts, &params, &data[40..], signer_seeds)?
+        }
+    };
+    let realized_slippage_bps = value_lost_bps(
+        expected,
+        (outcome.withdrawn_a, outcome.withdrawn_b),
+        sqrt_price,
+    );
+
+    vault.position_mint = *shared.new_position_mint.key;
+    vault.updated_at = now;
+    if vault_account.data_len() < VaultState::LEN {
+        resize_account(vault_account, agent, system_program, VaultState::LEN)?;
+    }
+    vault.version = VaultState::VERSION;
+    vault.pack(&mut vault_account.try_borrow_mut_data()?)?;
+
+    log_rebalance(
+        program_id,
+        RebalanceAccounts {
+            agent,
+            system_program,
+            config: config_account,
+            record: record_account,
+            history: history_account,
+        },
+        &config.position_mint,
+        HistoryEntry {
+            executed_at: now,
+            old_tick_lower: range.tick_lower,
+            old_tick_upper: range.tick_upper,
+            new_tick_lower: new_lower,
+            new_tick_upper: new_upper,
+            realized_slippage_bps,
+            fees_claimed_a: outcome.fees_a,
+            fees_claimed_b: outcome.fees_b,
+            agent: *agent.key,
+        },
+        false,
+    )?;
+
+    msg!(
+        "Vault rebalanced position {}: [{}, {}] -> [{}, {}], now {}",
+        config.position_mint,
+        range.tick_lower,
+        range.tick_upper,
+        new_lower,
+        new_upper,
+        vault.position_mint
+    );
+    Ok(())
+}
+
+/// Value of `received` short of `expected`, both priced at the Q64.64
+/// square root price `sqrt_price`, in bps of `expected`.
+fn value_lost_bps(expected: (u64, u64), received: (u64, u64), sqrt_price: u128) -> u16 {
+    let value = |(a, b): (u64, u64)| value_in_b(a, b, sqrt_price).unwrap_or(u128::MAX);
+    let (expected, received) = (value(expected), value(received));
+    if expected == 0 || received >= expected {
+        return 0;
+    }
+    mul_div(expected - received, 10_000, expected).map_or(10_000, |bps| bps as u16)
+}
+
+/// CPI into a DEX program with the vault PDA signing. Each account is passed
+/// with its writable flag; it signs if it signed this instruction or is the
+/// vault.
+pub fn invoke_dex<'a>(
+    dex_program: &AccountInfo<'a>,
+    vault: &AccountInfo<'a>,
+    discriminator: [u8; 8],
+    args: &[u8],
+    accounts: &[(&AccountInfo<'a>, bool)],
+    signer_seeds: &[&[u8]],
+) -> ProgramResult {
+    let mut data = discriminator.to_vec();
+    data.extend_from_slice(args);
+    let metas = accounts
+        .iter()
+        .map(|(account, writable)| AccountMeta {
+            pubkey: *account.key,
+            is_signer: account.is_signer || account.key == vault.key,
+            is_writable: *writable,
+        })
+        .collect();
+    let mut infos: Vec<AccountInfo> =
+        accounts.iter().map(|(account, _)| (*account).clone()).collect();
+    infos.push(dex_program.clone());
+    invoke_signed(
+        &Instruction { program_id: *dex_program.key, accounts: metas, data },
+        &infos,
+        &[signer_seeds],
+    )
+}
+
+/// Load a vault owned by this program.
+fn load_vault(program_id: &Pubkey, account: &AccountInfo) -> Result<VaultState, ProgramError> {
+    if account.owner != program_id {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    VaultState::unpack(&account.try_borrow_data()?)
+        .map_err(|_| PoseidonError::InvalidVaultAccount.into())
+}
+
+/// Move `amount` out of a vault token account to `destination`, then close it
+/// with its rent going to `owner`.
+fn drain_token_account<'a>(
+    token_program: &AccountInfo<'a>,
+    vault: &AccountInfo<'a>,
+    source: &AccountInfo<'a>,
+    destination: &AccountInfo<'a>,
+    owner: &AccountInfo<'a>,
+    amount: u64,
+    signer_seeds: &[&[u8]],
+) -> ProgramResult {
+    if *token_program.key != *source.owner {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    if amount > 0 {
+        invoke_signed(
+            &token_instruction(
+                token_program.key,
+                TOKEN_TRANSFER,
+                &amount.to_le_bytes(),
+                &[
+                    AccountMeta::new(*source.key, false),
+                    AccountMeta::new(*destination.key, false),
+                    AccountMeta::new_readonly(*vault.key, true),
+                ],
+            ),
+            &[source.clone(), destination.clone(), vault.clone(), token_program.clone()],
+            &[signer_seeds],
+        )?;
+    }
+    invoke_signed(
+        &token_instruction(
+            token_program.key,
+            TOKEN_CLOSE_ACCOUNT,
+            &[],
+            &[
+                AccountMeta::new(*source.key, false),
+                AccountMeta::new(*owner.key, false),
+                AccountMeta::new_readonly(*vault.key, true),
+            ],
+        ),
+        &[source.clone(), owner.clone(), vault.clone(), token_program.clone()],
+        &[signer_seeds],
+    )
+}
+
+fn token_instruction(
+    token_program: &Pubkey,
+    tag: u8,
+    args: &[u8],
+    accounts: &[AccountMeta],
+) -> Instruction {
+    Instruction {
+        program_id: *token_program,
+        accounts: accounts.to_vec(),
+        data: [&[tag][..], args].concat(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use poseidon_state::position::{TOKEN_2022_PROGRAM_ID, TOKEN_PROGRAM_ID};
+    use solana_program::system_program;
+
+    use super::*;
+    use crate::test_utils::*;
+
+    /// `deposit_position` accounts for `owner`'s Orca position, whose vault
+    /// does not exist yet.
+    fn deposit_accounts(owner: Pubkey) -> Vec<Account> {
+        let mint_key = Pubkey::new_unique();
+        let config = rebalance_config(owner, mint_key, Protocol::OrcaWhirlpool);
+        let vault = VaultState::find_address(&config.key, &PROGRAM_ID).0;
+        vec![
+            Account::signer(owner),
+            config,
+            Account::empty(vault),
+            mint(mint_key, 1, 0).readonly(),
+            token_account(Pubkey::new_unique(), mint_key, owner, 1),
+            token_account(Pubkey::new_unique(), mint_key, vault, 0),
+            Account::program(TOKEN_PROGRAM_ID),
+            Account::program(system_program::id()),
+        ]
+    }
+
+    /// `withdraw_position` accounts for a vault that has rebalanced: it holds
+    /// its NFT, and 40 and 60 of the pool's tokens in the accounts it recorded.
+    fn withdraw_accounts(owner: Pubkey) -> Vec<Account> {
+        let mint_key = Pubkey::new_unique();
+        let mut config = rebalance_config(owner, mint_key, Protocol::OrcaWhirlpool);
+        let mut state: RebalanceConfig = config.unpack();
+        state.vaulted = true;
+        state.pack(&mut config.data).unwrap();
+        let (vault, bump) = VaultState::find_address(&config.key, &PROGRAM_ID);
+        let token_a = token_account(Pubkey::new_unique(), Pubkey::new_unique(), vault, 40);
+        let token_b = token_account(Pubkey::new_unique(), Pubkey::new_unique(), vault, 60);
+        let vault_state = VaultState {
+            version: VaultState::VERSION,
+            bump,
+            config: config.key,
+            owner,
+            position_mint: mint_key,
+            token_a: token_a.key,
+            token_b: token_b.key,
+            ..VaultState::default()
+        };
+        let owner_token = |mint: Pubkey| token_account(Pubkey::new_unique(), mint, owner, 0);
+        let owner_a = owner_token(Pubkey::new_unique());
+        let owner_b = owner_token(Pubkey::new_unique());
+        vec![
+            Account::signer(owner),
+            config,
+            Account::state(vault, &vault_state),
+            token_account(Pubkey::new_unique(), mint_key, vault, 1),
+            owner_token(mint_key),
+            Account::program(TOKEN_PROGRAM_ID),
+            token_a,
+            owner_a,
+            Account::program(TOKEN_PROGRAM_ID),
+            token_b,
+            owner_b,
+            Account::program(TOKEN_PROGRAM_ID),
+        ]
+    }
+
+    fn error(error: PoseidonError) -> ProgramResult {
+        Err(error.into())
+    }
+
+    #[test]
+    fn deposit_moves_the_nft_into_a_new_vault() {
+        let owner = Pubkey::new_unique();
+        let mut accounts = deposit_accounts(owner);
+        let data = ix_data(crate::IX_DEPOSIT_POSITION, &[]);
+        assert_eq!(process(&mut accounts, &data), Ok(()));
+
+        let vault: VaultState = accounts[2].unpack();
+        assert_eq!(accounts[2].owner, PROGRAM_ID);
+        assert_eq!(vault.config, accounts[1].key);
+        assert_eq!(vault.owner, owner);
+        assert_eq!(vault.position_mint, accounts[3].key);
+        assert_eq!(vault.token_accounts(), None);
+        assert!(accounts[1].unpack::<RebalanceConfig>().vaulted);
+        assert_eq!(token_amount(&accounts[4].data), 0);
+        assert_eq!(token_amount(&accounts[5].data), 1);
+    }
+
+    #[test]
+    fn deposit_requires_the_config_owner() {
+        let mut accounts = deposit_accounts(Pubkey::new_unique());
+        let data = ix_data(crate::IX_DEPOSIT_POSITION, &[]);
+        accounts[0] = accounts[0].clone().unsigned();
+        assert_eq!(process(&mut accounts, &data), Err(ProgramError::MissingRequiredSignature));
+
+        accounts[0] = Account::signer(Pubkey::new_unique());
+        assert_eq!(process(&mut accounts, &data), error(PoseidonError::ConfigOwnerMismatch));
+    }
+
+    #[test]
+    fn deposit_refuses_unsupported_and_vaulted_positions() {
+        let owner = Pubkey::new_unique();
+        let data = ix_data(crate::IX_DEPOSIT_POSITION, &[]);
+
+        let mut accounts = deposit_accounts(owner);
+        accounts[1] = rebalance_config(owner, accounts[3].key, Protocol::MeteoraDlmm);
+        assert_eq!(process(&mut accounts, &data), error(PoseidonError::VaultProtocolUnsupported));
+
+        let mut accounts = deposit_accounts(owner);
+        let vault = VaultState { owner, ..VaultState::default() };
+        accounts[2] = Account::state(accounts[2].key, &vault);
+        assert_eq!(process(&mut accounts, &data), error(PoseidonError::PositionInVault));
+
+        // The NFT's token program must move it
+        let mut accounts = deposit_accounts(owner);
+        accounts[6] = Account::program(TOKEN_2022_PROGRAM_ID);
+        assert_eq!(process(&mut accounts, &data), Err(ProgramError::IncorrectProgramId));
+    }
+
+    #[test]
+    fn withdraw_returns_everything_and_closes_the_vault() {
+        let owner = Pubkey::new_unique();
+        let mut accounts = withdraw_accounts(owner);
+        let rent: u64 = [2, 3, 6, 9].iter().map(|&i| accounts[i].lamports).sum();
+        let lamports = accounts[0].lamports;
+        let data = ix_data(crate::IX_WITHDRAW_POSITION, &[]);
+        assert_eq!(process(&mut accounts, &data), Ok(()));
+
+        assert_eq!(token_amount(&accounts[4].data), 1);
+        assert_eq!(token_amount(&accounts[7].data), 40);
+        assert_eq!(token_amount(&accounts[10].data), 60);
+        for closed in [2, 3, 6, 9] {
+            assert!(accounts[closed].is_closed());
+        }
+        assert_eq!(accounts[0].lamports, lamports + rent);
+        assert!(!accounts[1].unpack::<RebalanceConfig>().vaulted);
+    }
+
+    #[test]
+    fn withdraw_must_drain_the_recorded_token_accounts() {
+        let mut accounts = withdraw_accounts(Pubkey::new_unique());
+        let data = ix_data(crate::IX_WITHDRAW_POSITION, &[]);
+        accounts.truncate(9);
+        assert_eq!(process(&mut accounts, &data), error(PoseidonError::InvalidVaultAccount));
+    }
+
+    #[test]
+    fn withdraw_checks_the_vault_owner_and_config() {
+        let owner = Pubkey::new_unique();
+        let data = ix_data(crate::IX_WITHDRAW_POSITION, &[]);
+
+        let mut accounts = withdraw_accounts(owner);
+        accounts[0] = Account::signer(Pubkey::new_unique());
+        assert_eq!(process(&mut accounts, &data), error(PoseidonError::ConfigOwnerMismatch));
+
+        let mut accounts = withdraw_accounts(owner);
+        accounts[1] = rebalance_config(owner, Pubkey::new_unique(), Protocol::OrcaWhirlpool);
+        assert_eq!(process(&mut accounts, &data), error(PoseidonError::InvalidConfigAddress));
+    }
+
+    #[test]
+    fn withdraw_works_after_the_config_was_reaped() {
+        let mut accounts = withdraw_accounts(Pubkey::new_unique());
+        accounts[1] = Account::empty(accounts[1].key);
+        let data = ix_data(crate::IX_WITHDRAW_POSITION, &[]);
+        assert_eq!(process(&mut accounts, &data), Ok(()));
+        assert!(accounts[2].is_closed());
+    }
+
+    #[test]
+    fn vault_rebalance_requires_an_unpaused_agent() {
+        let fixture = VaultFixture::new(Protocol::OrcaWhirlpool);
+        let data = fixture.data(&[]);
+
+        let mut accounts = fixture.accounts.clone();
+        accounts[0] = accounts[0].clone().unsigned();
+        assert_eq!(process(&mut accounts, &data), Err(ProgramError::MissingRequiredSignature));
+
+        // Not even the owner may move a vaulted position
+        let mut accounts = fixture.accounts.clone();
+        accounts[0] = Account::signer(fixture.owner);
+        assert_eq!(process(&mut accounts, &data), error(PoseidonError::UnauthorizedAgent));
+
+        let mut accounts = fixture.accounts.clone();
+        let program_config = accounts[VaultFixture::PROGRAM_CONFIG].clone();
+        accounts[VaultFixture::PROGRAM_CONFIG] = paused(program_config);
+        assert_eq!(process(&mut accounts, &data), error(PoseidonError::ProgramPaused));
+    }
+}
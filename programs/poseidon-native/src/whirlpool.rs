@@ -0,0 +1,344 @@
+use poseidon_state::{position::TOKEN_PROGRAM_ID, token_balance};
+use solana_program::{
+    account_info::{next_account_info, AccountInfo},
+    program_error::ProgramError,
+};
+
+use crate::vault::{invoke_dex, RebalanceOutcome, VaultAccounts, VaultRebalance};
+
+// Whirlpool instruction discriminators: sha256("global:<name>")[0..8]
+const DECREASE_LIQUIDITY: [u8; 8] = [160, 38, 208, 111, 104, 91, 44, 1];
+const COLLECT_FEES: [u8; 8] = [164, 152, 207, 99, 30, 186, 19, 182];
+const CLOSE_POSITION: [u8; 8] = [123, 134, 81, 0, 49, 68, 98, 98];
+const OPEN_POSITION: [u8; 8] = [135, 128, 47, 77, 15, 152, 240, 49];
+const INCREASE_LIQUIDITY: [u8; 8] = [46, 156, 243, 118, 13, 205, 251, 178];
+
+/// Whirlpool accounts a vault rebalance passes after the shared ones.
+pub struct WhirlpoolAccounts<'a, 'b> {
+    token_vault_a: &'a AccountInfo<'b>,
+    token_vault_b: &'a AccountInfo<'b>,
+    tick_array_lower: &'a AccountInfo<'b>,
+    tick_array_upper: &'a AccountInfo<'b>,
+    new_tick_array_lower: &'a AccountInfo<'b>,
+    new_tick_array_upper: &'a AccountInfo<'b>,
+    rent: &'a AccountInfo<'b>,
+    associated_token_program: &'a AccountInfo<'b>,
+    /// SPL Token, which Whirlpool's v1 liquidity and open instructions take;
+    /// not necessarily the old position NFT's token program.
+    pool_token_program: &'a AccountInfo<'b>,
+}
+
+impl<'a, 'b> WhirlpoolAccounts<'a, 'b> {
+    // Accounts: [token_vault_a, token_vault_b, tick_array_lower, tick_array_upper,
+    //            new_tick_array_lower, new_tick_array_upper, rent, associated_token_program,
+    //            pool_token_program]
+    pub fn parse<I>(iter: &mut I) -> Result<Self, ProgramError>
+    where
+        I: Iterator<Item = &'a AccountInfo<'b>>,
+    {
+        let accounts = Self {
+            token_vault_a: next_account_info(iter)?,
+            token_vault_b: next_account_info(iter)?,
+            tick_array_lower: next_account_info(iter)?,
+            tick_array_upper: next_account_info(iter)?,
+            new_tick_array_lower: next_account_info(iter)?,
+            new_tick_array_upper: next_account_info(iter)?,
+            rent: next_account_info(iter)?,
+            associated_token_program: next_account_info(iter)?,
+            pool_token_program: next_account_info(iter)?,
+        };
+        if *accounts.pool_token_program.key != TOKEN_PROGRAM_ID {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        Ok(accounts)
+    }
+}
+
+/// Move the vault's liquidity to a new Whirlpool position: withdraw it and
+/// the fees owed, close the old position, then open the new range and
+/// deposit. Returns what the withdrawal and fee collection brought in. Pools
+/// with reward emissions owed to the position cannot be closed this way.
+pub fn rebalance<'a>(
+    shared: &VaultAccounts<'_, 'a>,
+    accounts: &WhirlpoolAccounts<'_, 'a>,
+    params: &VaultRebalance,
+    args: &[u8],
+    signer_seeds: &[&[u8]],
+) -> Result<RebalanceOutcome, ProgramError> {
+    // Args: new_position_bump(1)
+    let position_bump = *args.first().ok_or(ProgramError::InvalidInstructionData)?;
+    let VaultAccounts {
+        agent,
+        vault,
+        token_program,
+        system_program,
+        dex_program,
+        pool,
+        position,
+        position_mint,
+        position_token_account,
+        new_position,
+        new_position_mint,
+        new_position_token_account,
+        vault_token_a,
+        vault_token_b,
+        ..
+    } = *shared;
+
+    let start_a = token_balance(vault.key, vault_token_a, None)?;
+    let start_b = token_balance(vault.key, vault_token_b, None)?;
+    if params.liquidity > 0 {
+        let mut data = params.liquidity.to_le_bytes().to_vec();
+        data.extend_from_slice(&params.token_min_a.to_le_bytes());
+        data.extend_from_slice(&params.token_min_b.to_le_bytes());
+        invoke_dex(
+            dex_program,
+            vault,
+            DECREASE_LIQUIDITY,
+            &data,
+            &[
+                (pool, true),
+                (accounts.pool_token_program, false),
+                (vault, false),
+                (position, true),
+                (position_token_account, false),
+                (vault_token_a, true),
+                (vault_token_b, true),
+                (accounts.token_vault_a, true),
+                (accounts.token_vault_b, true),
+                (accounts.tick_array_lower, true),
+                (accounts.tick_array_upper, true),
+            ],
+            signer_seeds,
+        )?;
+    }
+
+    let before_a = token_balance(vault.key, vault_token_a, None)?;
+    let before_b = token_balance(vault.key, vault_token_b, None)?;
+    let withdrawn_a = before_a.saturating_sub(start_a);
+    let withdrawn_b = before_b.saturating_sub(start_b);
+    invoke_dex(
+        dex_program,
+        vault,
+        COLLECT_FEES,
+        &[],
+        &[
+            (pool, false),
+            (vault, false),
+            (position, true),
+            (position_token_account, false),
+            (vault_token_a, true),
+            (accounts.token_vault_a, true),
+            (vault_token_b, true),
+            (accounts.token_vault_b, true),
+            (accounts.pool_token_program, false),
+        ],
+        signer_seeds,
+    )?;
+    let fees_a = token_balance(vault.key, vault_token_a, None)?.saturating_sub(before_a);
+    let fees_b = token_balance(vault.key, vault_token_b, None)?.saturating_sub(before_b);
+
+    // Rent of the old position and its token account goes to the agent, who
+    // funds the new ones.
+    invoke_dex(
+        dex_program,
+        vault,
+        CLOSE_POSITION,
+        &[],
+        &[
+            (vault, false),
+            (agent, true),
+            (position, true),
+            (position_mint, true),
+            (position_token_account, true),
+            (token_program, false),
+        ],
+        signer_seeds,
+    )?;
+
+    let mut data = vec![position_bump];
+    data.extend_from_slice(&params.new_tick_lower.to_le_bytes());
+    data.extend_from_slice(&params.new_tick_upper.to_le_bytes());
+    invoke_dex(
+        dex_program,
+        vault,
+        OPEN_POSITION,
+        &data,
+        &[
+            (agent, true),
+            (vault, false),
+            (new_position, true),
+            (new_position_mint, true),
+            (new_position_token_account, true),
+            (pool, false),
+            (accounts.pool_token_program, false),
+            (system_program, false),
+            (accounts.rent, false),
+            (accounts.associated_token_program, false),
+        ],
+        signer_seeds,
+    )?;
+
+    if params.liquidity_to_add > 0 {
+        let mut data = params.liquidity_to_add.to_le_bytes().to_vec();
+        data.extend_from_slice(&params.token_max_a.to_le_bytes());
+        data.extend_from_slice(&params.token_max_b.to_le_bytes());
+        invoke_dex(
+            dex_program,
+            vault,
+            INCREASE_LIQUIDITY,
+            &data,
+            &[
+                (pool, true),
+                (accounts.pool_token_program, false),
+                (vault, false),
+                (new_position, true),
+                (new_position_token_account, false),
+                (vault_token_a, true),
+                (vault_token_b, true),
+                (accounts.token_vault_a, true),
+                (accounts.token_vault_b, true),
+                (accounts.new_tick_array_lower, true),
+                (accounts.new_tick_array_upper, true),
+            ],
+            signer_seeds,
+        )?;
+    }
+
+    Ok(RebalanceOutcome { withdrawn_a, withdrawn_b, fees_a, fees_b })
+}
+
+#[cfg(test)]
+mod tests {
+    use poseidon_state::{
+        position::{ASSOCIATED_TOKEN_PROGRAM_ID, ORCA_WHIRLPOOL_PROGRAM_ID, TOKEN_2022_PROGRAM_ID},
+        AccountState, OraclePrice, PoseidonError, PositionRange, Protocol, RebalanceExecuted,
+        RebalanceHistory, RebalanceRecord, VaultState,
+    };
+    use solana_program::{pubkey::Pubkey, sysvar};
+
+    use super::*;
+    use crate::test_utils::*;
+
+    /// Tokens the Whirlpool program pays the vault for its liquidity, and in fees.
+    const WITHDRAWN: (u64, u64) = (5_000, 5_000);
+    const FEES: (u64, u64) = (7, 9);
+
+    /// A vaulted Whirlpool position, followed by the Whirlpool accounts.
+    fn fixture() -> VaultFixture {
+        let mut fixture = VaultFixture::new(Protocol::OrcaWhirlpool);
+        // Token vaults and tick arrays
+        fixture.accounts.extend((0..6).map(|_| Account::empty(Pubkey::new_unique())));
+        fixture.accounts.extend([
+            Account::empty(sysvar::rent::id()).readonly(),
+            Account::program(ASSOCIATED_TOKEN_PROGRAM_ID),
+            Account::program(TOKEN_PROGRAM_ID),
+        ]);
+        fixture
+    }
+
+    /// Have the Whirlpool program pay the vault as it decreases liquidity and
+    /// collects fees.
+    fn pay_vault(fixture: &VaultFixture) {
+        let token_a = fixture.key(VaultFixture::VAULT_TOKEN_A);
+        let token_b = fixture.key(VaultFixture::VAULT_TOKEN_B);
+        on_cpi(Box::new(move |instruction, infos| {
+            let (a, b) = match instruction.data[..8].try_into().unwrap() {
+                DECREASE_LIQUIDITY => WITHDRAWN,
+                COLLECT_FEES => FEES,
+                _ => return Ok(()),
+            };
+            credit_tokens(infos, &token_a, a)?;
+            credit_tokens(infos, &token_b, b)
+        }));
+    }
+
+    #[test]
+    fn vault_rebalance_moves_the_position_through_whirlpool() {
+        let mut fixture = fixture();
+        pay_vault(&fixture);
+        let data = fixture.data(&[254]);
+        assert_eq!(process(&mut fixture.accounts, &data), Ok(()));
+
+        let calls: Vec<_> = cpis()
+            .into_iter()
+            .filter(|instruction| instruction.program_id == ORCA_WHIRLPOOL_PROGRAM_ID)
+            .collect();
+        let discriminators: Vec<&[u8]> = calls.iter().map(|call| &call.data[..8]).collect();
+        assert_eq!(
+            discriminators,
+            [DECREASE_LIQUIDITY, COLLECT_FEES, CLOSE_POSITION, OPEN_POSITION, INCREASE_LIQUIDITY]
+        );
+        let vault = fixture.key(VaultFixture::VAULT);
+        for call in &calls {
+            assert!(call.accounts.iter().any(|meta| meta.pubkey == vault && meta.is_signer));
+        }
+        assert_eq!(calls[0].accounts[1].pubkey, TOKEN_PROGRAM_ID);
+        assert_eq!(calls[3].data[8], 254);
+
+        // The withdrawal may return no less than the liquidity is worth at
+        // the oracle price, less the owner's 1% max slippage
+        let oracle = OraclePrice { price: 100, expo: -2, ..OraclePrice::default() };
+        let range = PositionRange {
+            pool: Pubkey::default(),
+            liquidity: VaultFixture::LIQUIDITY,
+            tick_lower: VaultFixture::TICKS.0,
+            tick_upper: VaultFixture::TICKS.1,
+        };
+        let (a, b) = range.token_amounts(oracle.sqrt_price_x64(6, 6).unwrap());
+        let minimum = |amount: u64| (u128::from(amount) * 9_900 / 10_000) as u64;
+        let mut decrease = VaultFixture::LIQUIDITY.to_le_bytes().to_vec();
+        decrease.extend_from_slice(&minimum(a).to_le_bytes());
+        decrease.extend_from_slice(&minimum(b).to_le_bytes());
+        assert_eq!(calls[0].data[8..], decrease);
+
+        let accounts = &fixture.accounts;
+        let vault: VaultState = accounts[VaultFixture::VAULT].unpack();
+        assert_eq!(vault.position_mint, fixture.key(VaultFixture::NEW_POSITION_MINT));
+        let token_a = fixture.key(VaultFixture::VAULT_TOKEN_A);
+        let token_b = fixture.key(VaultFixture::VAULT_TOKEN_B);
+        assert_eq!(vault.token_accounts(), Some((token_a, token_b)));
+        let record: RebalanceRecord = accounts[VaultFixture::RECORD].unpack();
+        assert!(!record.agent_reported);
+        let history: RebalanceHistory = accounts[VaultFixture::HISTORY].unpack();
+        assert!(!history.is_agent_reported(0));
+        assert!(emitted(&RebalanceExecuted {
+            config: fixture.key(VaultFixture::CONFIG),
+            position_mint: record.position_mint,
+            agent: fixture.agent,
+            old_tick_lower: VaultFixture::TICKS.0,
+            old_tick_upper: VaultFixture::TICKS.1,
+            new_tick_lower: VaultFixture::NEW_TICKS.0,
+            new_tick_upper: VaultFixture::NEW_TICKS.1,
+            realized_slippage_bps: 0,
+            fees_claimed_a: FEES.0,
+            fees_claimed_b: FEES.1,
+            rebalance_count: 1,
+            executed_at: NOW,
+            agent_reported: false,
+        }));
+    }
+
+    #[test]
+    fn vault_rebalance_takes_spl_token_as_the_pool_token_program() {
+        let mut fixture = fixture();
+        *fixture.accounts.last_mut().unwrap() = Account::program(TOKEN_2022_PROGRAM_ID);
+        let data = fixture.data(&[254]);
+        assert_eq!(process(&mut fixture.accounts, &data), Err(ProgramError::IncorrectProgramId));
+        assert!(cpis().is_empty());
+    }
+
+    #[test]
+    fn vault_rebalance_keeps_the_recorded_token_accounts() {
+        let mut fixture = fixture();
+        let vault = &mut fixture.accounts[VaultFixture::VAULT];
+        let mut state: VaultState = vault.unpack();
+        (state.token_a, state.token_b) = (Pubkey::new_unique(), Pubkey::new_unique());
+        state.pack(&mut vault.data).unwrap();
+        let data = fixture.data(&[254]);
+        assert_eq!(
+            process(&mut fixture.accounts, &data),
+            Err(PoseidonError::InvalidVaultAccount.into())
+        );
+    }
+}
@@ -1,4 +1,15 @@
 use anchor_lang::prelude::*;
+use anchor_lang::system_program;
+use poseidon_state::{
+    AccountState, BatchProcessed, BatchResult, ConfigMigrated, ConfigUpdated, Event, Protocol,
+    RebalanceDisabled, RebalanceEnabled, Strategy, StrategyKind, UserProfileClosed,
+    UserProfileUpdated, ORACLE_FEED_SEED, PROGRAM_CONFIG_SEED, REBALANCE_SEED, USER_PROFILE_SEED,
+};
+
+mod state;
+
+pub use poseidon_state::PoseidonError;
+pub use state::{OracleFeed, ProgramConfig, RebalanceConfig, UserProfile};
 
 declare_id!("2ro3VBKvqtc86DJVMnZETHMGAtjYFipZwdMFgtZGWscx");
 
@@ -12,7 +23,24 @@ pub mod poseidon_rebalance {
         ctx: Context<EnableRebalance>,
         max_slippage_bps: u16,
         min_yield_improvement_bps: u16,
+        protocol: u8,
     ) -> Result<()> {
+        let program_config = &ctx.accounts.program_config;
+        program_config.validate_params(max_slippage_bps, min_yield_improvement_bps)?;
+        let protocol = Protocol::try_from(protocol)?;
+        poseidon_state::verify_dex_position(
+            program_config,
+            protocol,
+            &ctx.accounts.position_mint,
+            ctx.accounts.dex_position.as_ref().map(|a| a.as_ref()),
+        )?;
+        poseidon_state::verify_position_owner(
+            ctx.accounts.owner.key,
+            protocol,
+            &ctx.accounts.position_mint,
+            ctx.accounts.position_token_account.as_ref().map(|a| a.as_ref()),
+        )?;
+
         let config = &mut ctx.accounts.rebalance_config;
         let clock = Clock::get()?;
 
@@ -20,13 +48,21 @@ pub mod poseidon_rebalance {
             config.owner = ctx.accounts.owner.key();
             config.position_mint = ctx.accounts.position_mint.key();
             config.created_at = clock.unix_timestamp;
+            config.version = poseidon_state::RebalanceConfig::VERSION;
+            config.bump = ctx.bumps.rebalance_config;
+        } else {
+            // Older layouts are too short to write back; run migrate_config first
+            require!(!config.needs_migration(), PoseidonError::ConfigNeedsMigration);
         }
 
         config.enabled = true;
         config.max_slippage_bps = max_slippage_bps;
         config.min_yield_improvement_bps = min_yield_improvement_bps;
+        config.protocol = protocol;
+        config.stale = false;
         config.updated_at = clock.unix_timestamp;
 
+        RebalanceEnabled::new(config.key(), config, false, clock.unix_timestamp).emit();
         msg!(
             "Rebalance enabled for position {} by {}",
             ctx.accounts.position_mint.key(),
@@ -38,6 +74,8 @@ pub mod poseidon_rebalance {
     /// Disable auto-rebalance for a specific position. Closes the config account
     /// and refunds rent to the owner.
     pub fn disable_rebalance(ctx: Context<DisableRebalance>) -> Result<()> {
+        let config = &ctx.accounts.rebalance_config;
+        RebalanceDisabled::new(config.key(), config, true, Clock::get()?.unix_timestamp).emit();
         msg!(
             "Rebalance disabled for position {} by {}",
             ctx.accounts.position_mint.key(),
@@ -46,6 +84,560 @@ pub mod poseidon_rebalance {
         Ok(())
     }
 
+    /// Pause or resume rebalancing without closing the config, so settings,
+    /// `created_at` and rent are kept.
+    pub fn set_enabled(ctx: Context<SetEnabled>, enabled: bool) -> Result<()> {
+        // Resuming grants the agent authority again, so it respects the pause
+        require!(
+            !enabled || !ctx.accounts.program_config.paused,
+            PoseidonError::ProgramPaused
+        );
+
+        let config = &mut ctx.accounts.rebalance_config;
+        require!(!config.needs_migration(), PoseidonError::ConfigNeedsMigration);
+        let now = Clock::get()?.unix_timestamp;
+        config.enabled = enabled;
+        config.updated_at = now;
+
+        if enabled {
+            RebalanceEnabled::new(config.key(), config, true, now).emit();
+        } else {
+            RebalanceDisabled::new(config.key(), config, false, now).emit();
+        }
+
+        msg!(
+            "Rebalance {} for position {} by {}",
+            if enabled { "resumed" } else { "paused" },
+            ctx.accounts.position_mint.key(),
+            ctx.accounts.owner.key()
+        );
+        Ok(())
+    }
+
+    /// Change only the supplied settings; `None` keeps the current value.
+    /// `inherit` takes `INHERIT_*` flags for fields to take from the owner's
+    /// user profile.
+    /// Emits `ConfigUpdated` with a bitmask of the fields that changed.
+    #[allow(clippy::too_many_arguments)]
+    pub fn update_config(
+        ctx: Context<UpdateConfig>,
+        max_slippage_bps: Option<u16>,
+        min_yield_improvement_bps: Option<u16>,
+        inherit: Option<u8>,
+        fee_destination: Option<Pubkey>,
+        strategy_kind: Option<u8>,
+        strategy_width: Option<u32>,
+        recenter_threshold_bps: Option<u16>,
+        min_seconds_between_rebalances: Option<u32>,
+        max_rebalances_per_day: Option<u16>,
+    ) -> Result<()> {
+        let update = poseidon_state::ConfigUpdate {
+            max_slippage_bps,
+            min_yield_improvement_bps,
+            inherit,
+            fee_destination,
+            strategy_kind: strategy_kind.map(StrategyKind::try_from).transpose()?,
+            strategy_width,
+            recenter_threshold_bps,
+            min_seconds_between_rebalances,
+            max_rebalances_per_day,
+        };
+        require!(!update.is_empty(), PoseidonError::NothingToUpdate);
+        ctx.accounts.program_config.validate_update(&update)?;
+
+        let address = ctx.accounts.rebalance_config.key();
+        let config = &mut ctx.accounts.rebalance_config;
+        require!(!config.needs_migration(), PoseidonError::ConfigNeedsMigration);
+
+        let event = config.apply_update(address, &update, Clock::get()?.unix_timestamp)?;
+
+        if event.changed != 0 {
+            event.emit();
+        }
+
+        msg!(
+            "Config updated for position {} (changed=0b{:02b})",
+            ctx.accounts.position_mint.key(),
+            event.changed
+        );
+        Ok(())
+    }
+
+    /// Create or overwrite the owner's default settings, which configs can
+    /// inherit field by field.
+    pub fn set_user_profile(
+        ctx: Context<SetUserProfile>,
+        max_slippage_bps: u16,
+        min_yield_improvement_bps: u16,
+        fee_destination: Pubkey,
+        strategy_kind: u8,
+        strategy_width: u32,
+        recenter_threshold_bps: u16,
+    ) -> Result<()> {
+        ctx.accounts
+            .program_config
+            .validate_params(max_slippage_bps, min_yield_improvement_bps)?;
+        let strategy = Strategy {
+            kind: StrategyKind::try_from(strategy_kind)?,
+            width: strategy_width,
+            recenter_threshold_bps,
+        };
+        // Protocol-specific limits are checked against each inheriting config at execute
+        strategy.validate(Protocol::Unknown)?;
+
+        // v1 profiles are too short for `strategy`
+        let info = ctx.accounts.user_profile.to_account_info();
+        if info.data_len() < UserProfile::LEN {
+            let required = Rent::get()?
+                .minimum_balance(UserProfile::LEN)
+                .saturating_sub(info.lamports());
+            if required > 0 {
+                system_program::transfer(
+                    CpiContext::new(
+                        ctx.accounts.system_program.to_account_info(),
+                        system_program::Transfer {
+                            from: ctx.accounts.owner.to_account_info(),
+                            to: info.clone(),
+                        },
+                    ),
+                    required,
+                )?;
+            }
+            info.realloc(UserProfile::LEN, true)?;
+        }
+
+        let profile = &mut ctx.accounts.user_profile;
+        let now = Clock::get()?.unix_timestamp;
+        profile.version = poseidon_state::UserProfile::VERSION;
+        let created = profile.created_at == 0;
+        if created {
+            profile.bump = ctx.bumps.user_profile;
+            profile.owner = ctx.accounts.owner.key();
+            profile.created_at = now;
+        }
+        profile.max_slippage_bps = max_slippage_bps;
+        profile.min_yield_improvement_bps = min_yield_improvement_bps;
+        profile.fee_destination = fee_destination;
+        profile.strategy = strategy;
+        profile.updated_at = now;
+
+        UserProfileUpdated::new(profile.key(), profile, created).emit();
+        msg!("User profile set for {}", ctx.accounts.owner.key());
+        Ok(())
+    }
+
+    /// Close the owner's profile and refund its rent. Configs that still
+    /// inherit from it are refused by the agent until they override or a
+    /// new profile is set.
+    pub fn close_user_profile(ctx: Context<CloseUserProfile>) -> Result<()> {
+        UserProfileClosed {
+            profile: ctx.accounts.user_profile.key(),
+            owner: ctx.accounts.owner.key(),
+            closed_at: Clock::get()?.unix_timestamp,
+        }
+        .emit();
+        msg!("User profile closed for {}", ctx.accounts.owner.key());
+        Ok(())
+    }
+
+    /// Re-key a config to whoever holds the position now. The new holder pays
+    /// for the new PDA; the old one's rent goes back to the previous owner. Fees
+    /// go to the new holder's wallet and no field inherits from a profile.
+    pub fn transfer_config(ctx: Context<TransferConfig>) -> Result<()> {
+        let old_config = &ctx.accounts.old_config;
+        poseidon_state::verify_position_owner(
+            ctx.accounts.new_owner.key,
+            old_config.protocol,
+            &ctx.accounts.position_mint,
+            ctx.accounts.position_token_account.as_ref().map(|a| a.as_ref()),
+        )?;
+
+        let now = Clock::get()?.unix_timestamp;
+        let config = poseidon_state::RebalanceConfig {
+            owner: ctx.accounts.new_owner.key(),
+            bump: ctx.bumps.new_config,
+            stale: false,
+            // The previous owner's fee wallet and profile links are theirs
+            inherit: 0,
+            fee_destination: Pubkey::default(),
+            // The new holder has the NFT itself, not through a vault
+            vaulted: false,
+            updated_at: now,
+            ..old_config.0.clone()
+        };
+        ctx.accounts.new_config.set_inner(RebalanceConfig(config));
+
+        RebalanceDisabled::new(old_config.key(), old_config, true, now).emit();
+        let new_config = &ctx.accounts.new_config;
+        RebalanceEnabled::new(new_config.key(), new_config, false, now).emit();
+
+        msg!(
+            "Config for position {} transferred from {} to {}",
+            ctx.accounts.position_mint.key(),
+            ctx.accounts.previous_owner.key(),
+            ctx.accounts.new_owner.key()
+        );
+        Ok(())
+    }
+
+    /// Permissionless: mark a config stale once its owner no longer holds
+    /// the position, so the agent stops acting on it.
+    pub fn flag_stale_config(ctx: Context<FlagStaleConfig>) -> Result<()> {
+        let config = &mut ctx.accounts.rebalance_config;
+        let holder = poseidon_state::position_holder(
+            config.protocol,
+            &ctx.accounts.position_mint,
+            ctx.accounts.position_token_account.as_ref().map(|a| a.as_ref()),
+        )?;
+        require_keys_neq!(holder, config.owner, PoseidonError::PositionStillHeld);
+
+        if !config.stale {
+            let now = Clock::get()?.unix_timestamp;
+            config.stale = true;
+            config.updated_at = now;
+            ConfigUpdated::new(config.key(), config, ConfigUpdated::FIELD_STALE, now).emit();
+        }
+
+        msg!(
+            "Config for position {} flagged stale: owner {}, holder {}",
+            config.position_mint,
+            config.owner,
+            holder
+        );
+        Ok(())
+    }
+
+    /// Permissionless: close the config of a position that no longer exists
+    /// on its DEX and return the rent to the config's owner.
+    pub fn reap_config(ctx: Context<ReapConfig>) -> Result<()> {
+        require!(
+            poseidon_state::is_position_closed(&ctx.accounts.position_mint),
+            PoseidonError::PositionStillOpen
+        );
+
+        let config = &ctx.accounts.rebalance_config;
+        RebalanceDisabled::new(config.key(), config, true, Clock::get()?.unix_timestamp).emit();
+        msg!(
+            "Config for closed position {} reaped, rent returned to {}",
+            ctx.accounts.position_mint.key(),
+            ctx.accounts.owner.key()
+        );
+        Ok(())
+    }
+
+    /// Enable many positions with the same settings. Remaining accounts hold
+    /// `(config_pda, position_mint[, position_token_account, dex_position])`
+    /// per mint; the bracketed pair is for NFT protocols. Each position
+    /// succeeds or fails on its own and is reported in `BatchProcessed`.
+    pub fn enable_batch<'info>(
+        ctx: Context<'_, '_, 'info, 'info, EnableBatch<'info>>,
+        position_mints: Vec<Pubkey>,
+        max_slippage_bps: u16,
+        min_yield_improvement_bps: u16,
+        protocol: u8,
+    ) -> Result<()> {
+        ctx.accounts
+            .program_config
+            .validate_params(max_slippage_bps, min_yield_improvement_bps)?;
+        let protocol = Protocol::try_from(protocol)?;
+        let stride = if protocol.is_nft() { 4 } else { 2 };
+        let positions = batch_accounts(ctx.remaining_accounts, &position_mints, stride)?;
+
+        let mut results = Vec::with_capacity(position_mints.len());
+        for (mint, accounts) in position_mints.iter().zip(positions) {
+            let result = enable_position(
+                &ctx.accounts.owner,
+                &ctx.accounts.system_program,
+                &ctx.accounts.program_config,
+                protocol,
+                max_slippage_bps,
+                min_yield_improvement_bps,
+                accounts,
+            );
+            results.push(batch_result(mint, result));
+        }
+
+        report_batch(ctx.accounts.owner.key(), BatchProcessed::ACTION_ENABLE, results);
+        Ok(())
+    }
+
+    /// Close many configs at once, refunding rent to the owner. Remaining
+    /// accounts hold `(config_pda, position_mint)` per mint.
+    pub fn disable_batch<'info>(
+        ctx: Context<'_, '_, 'info, 'info, DisableBatch<'info>>,
+        position_mints: Vec<Pubkey>,
+    ) -> Result<()> {
+        let positions = batch_accounts(ctx.remaining_accounts, &position_mints, 2)?;
+
+        let mut results = Vec::with_capacity(position_mints.len());
+        for (mint, accounts) in position_mints.iter().zip(positions) {
+            let result = disable_position(&ctx.accounts.owner, &accounts[0], &accounts[1]);
+            results.push(batch_result(mint, result));
+        }
+
+        report_batch(ctx.accounts.owner.key(), BatchProcessed::ACTION_DISABLE, results);
+        Ok(())
+    }
+
+    /// Realloc a config written under an older layout to the current one and
+    /// fill the new fields with defaults. Permissionless; the payer covers rent.
+    pub fn migrate_config(ctx: Context<MigrateConfig>) -> Result<()> {
+        let info = ctx.accounts.rebalance_config.to_account_info();
+
+        let mut config = {
+            let data = info.try_borrow_data()?;
+            require!(
+                data.len() >= poseidon_state::RebalanceConfig::V0_LEN,
+                PoseidonError::InvalidConfigAccount
+            );
+            poseidon_state::RebalanceConfig::unpack(&data)
+                .map_err(|_| error!(PoseidonError::InvalidConfigAccount))?
+        };
+        let version = config.version;
+        require!(config.needs_migration(), PoseidonError::ConfigUpToDate);
+
+        let (expected_pda, bump) = poseidon_state::RebalanceConfig::find_address(
+            &config.owner,
+            &config.position_mint,
+            ctx.program_id,
+        );
+        require_keys_eq!(info.key(), expected_pda, PoseidonError::InvalidConfigAccount);
+
+        let required = Rent::get()?
+            .minimum_balance(RebalanceConfig::LEN)
+            .saturating_sub(info.lamports());
+        if required > 0 {
+            system_program::transfer(
+                CpiContext::new(
+                    ctx.accounts.system_program.to_account_info(),
+                    system_program::Transfer {
+                        from: ctx.accounts.payer.to_account_info(),
+                        to: info.clone(),
+                    },
+                ),
+                required,
+            )?;
+        }
+        info.realloc(RebalanceConfig::LEN, true)?;
+
+        config.migrate(bump);
+        config.pack(&mut info.try_borrow_mut_data()?)?;
+
+        ConfigMigrated::new(info.key(), &config, version, Clock::get()?.unix_timestamp).emit();
+
+        msg!(
+            "Config for position {} migrated from v{} to v{}",
+            config.position_mint,
+            version,
+            poseidon_state::RebalanceConfig::VERSION
+        );
+        Ok(())
+    }
+
+    /// One-time setup of the singleton program config. Only the program's
+    /// upgrade authority can call this; it becomes the initial admin.
+    pub fn initialize_program_config(
+        ctx: Context<InitializeProgramConfig>,
+        treasury: Pubkey,
+        deposit_fee_bps: u16,
+        performance_fee_bps: u16,
+    ) -> Result<()> {
+        require!(
+            deposit_fee_bps <= poseidon_state::ProgramConfig::MAX_FEE_BPS
+                && performance_fee_bps <= poseidon_state::ProgramConfig::MAX_FEE_BPS,
+            PoseidonError::InvalidFeeBps
+        );
+        let program_data =
+            ProgramData::try_deserialize(&mut &ctx.accounts.program_data.try_borrow_data()?[..])?;
+        require!(
+            program_data.upgrade_authority_address == Some(ctx.accounts.admin.key()),
+            PoseidonError::Unauthorized
+        );
+
+        let config = &mut ctx.accounts.program_config;
+        config.version = poseidon_state::ProgramConfig::VERSION;
+        config.bump = ctx.bumps.program_config;
+        config.admin = ctx.accounts.admin.key();
+        config.treasury = treasury;
+        config.deposit_fee_bps = deposit_fee_bps;
+        config.performance_fee_bps = performance_fee_bps;
+        config.paused = false;
+        config.agents = Vec::new();
+        config.slippage_cap_bps = poseidon_state::ProgramConfig::DEFAULT_SLIPPAGE_CAP_BPS;
+        config.min_yield_cap_bps = poseidon_state::ProgramConfig::DEFAULT_MIN_YIELD_CAP_BPS;
+        config.dex_programs = poseidon_state::DexProgram::defaults();
+        config.oracle_max_age_seconds = poseidon_state::ProgramConfig::DEFAULT_ORACLE_MAX_AGE_SECONDS;
+        config.oracle_max_deviation_bps =
+            poseidon_state::ProgramConfig::DEFAULT_ORACLE_MAX_DEVIATION_BPS;
+
+        msg!("Program config initialized, admin {}", config.admin);
+        Ok(())
+    }
+
+    /// Hand the admin role to a new key. The new admin must co-sign.
+    pub fn rotate_admin(ctx: Context<RotateAdmin>) -> Result<()> {
+        let config = &mut ctx.accounts.program_config;
+        config.admin = ctx.accounts.new_admin.key();
+
+        msg!(
+            "Admin rotated from {} to {}",
+            ctx.accounts.admin.key(),
+            ctx.accounts.new_admin.key()
+        );
+        Ok(())
+    }
+
+    /// Authorize an agent key to execute rebalances.
+    pub fn add_agent(ctx: Context<UpdateAgents>, agent: Pubkey) -> Result<()> {
+        let config = &mut ctx.accounts.program_config;
+        require!(!config.agents.contains(&agent), PoseidonError::AgentAlreadyAuthorized);
+        require!(
+            config.agents.len() < poseidon_state::ProgramConfig::MAX_AGENTS,
+            PoseidonError::TooManyAgents
+        );
+        config.agents.push(agent);
+
+        msg!("Agent {} authorized", agent);
+        Ok(())
+    }
+
+    /// Revoke an agent key, e.g. after a wallet compromise.
+    pub fn remove_agent(ctx: Context<UpdateAgents>, agent: Pubkey) -> Result<()> {
+        let config = &mut ctx.accounts.program_config;
+        let index = config
+            .agents
+            .iter()
+            .position(|a| *a == agent)
+            .ok_or(PoseidonError::AgentNotFound)?;
+        config.agents.remove(index);
+
+        msg!("Agent {} removed", agent);
+        Ok(())
+    }
+
+    /// Emergency switch. While paused, no new agent authority can be granted;
+    /// owners can still disable and close their configs. Works on a program
+    /// config of any version.
+    pub fn set_paused(ctx: Context<SetPaused>, paused: bool) -> Result<()> {
+        let info = ctx.accounts.program_config.to_account_info();
+        info.try_borrow_mut_data()?[poseidon_state::ProgramConfig::PAUSED_OFFSET] = paused as u8;
+
+        msg!("Program {}", if paused { "paused" } else { "resumed" });
+        Ok(())
+    }
+
+    /// Set the caps owners' `max_slippage_bps` and `min_yield_improvement_bps`
+    /// are checked against on enable. Also upgrades a v1 program config.
+    pub fn set_rebalance_bounds(
+        ctx: Context<SetRebalanceBounds>,
+        slippage_cap_bps: u16,
+        min_yield_cap_bps: u16,
+    ) -> Result<()> {
+        let max = poseidon_state::ProgramConfig::MAX_BPS;
+        require!(
+            (1..=max).contains(&slippage_cap_bps) && (1..=max).contains(&min_yield_cap_bps),
+            PoseidonError::InvalidBounds
+        );
+
+        let config = &mut ctx.accounts.program_config;
+        config.migrate();
+        config.slippage_cap_bps = slippage_cap_bps;
+        config.min_yield_cap_bps = min_yield_cap_bps;
+
+        msg!(
+            "Rebalance bounds set: slippage <= {} bps, min yield <= {} bps",
+            slippage_cap_bps,
+            min_yield_cap_bps
+        );
+        Ok(())
+    }
+
+    /// Set how old an oracle price agent actions may use and how far the pool
+    /// price may sit from it.
+    pub fn set_oracle_bounds(
+        ctx: Context<SetRebalanceBounds>,
+        max_age_seconds: u32,
+        max_deviation_bps: u16,
+    ) -> Result<()> {
+        require!(
+            (1..=poseidon_state::ProgramConfig::MAX_ORACLE_AGE_SECONDS).contains(&max_age_seconds)
+                && (1..=poseidon_state::ProgramConfig::MAX_BPS).contains(&max_deviation_bps),
+            PoseidonError::InvalidBounds
+        );
+
+        let config = &mut ctx.accounts.program_config;
+        config.migrate();
+        config.oracle_max_age_seconds = max_age_seconds;
+        config.oracle_max_deviation_bps = max_deviation_bps;
+
+        msg!(
+            "Oracle bounds set: age <= {}s, deviation <= {} bps",
+            max_age_seconds,
+            max_deviation_bps
+        );
+        Ok(())
+    }
+
+    /// Set the Pyth feed oracle accounts must quote for pools of a token pair.
+    pub fn set_oracle_feed(
+        ctx: Context<SetOracleFeed>,
+        mint_a: Pubkey,
+        mint_b: Pubkey,
+        feed_id: [u8; 32],
+    ) -> Result<()> {
+        let feed = &mut ctx.accounts.oracle_feed;
+        feed.version = poseidon_state::OracleFeed::VERSION;
+        feed.bump = ctx.bumps.oracle_feed;
+        feed.mint_a = mint_a;
+        feed.mint_b = mint_b;
+        feed.feed_id = feed_id;
+        feed.updated_at = Clock::get()?.unix_timestamp;
+
+        msg!("Oracle feed for {} / {} set", mint_a, mint_b);
+        Ok(())
+    }
+
+    /// Accept a DEX deployment for a protocol, e.g. a new venue or a fork
+    /// sharing an existing layout.
+    pub fn add_dex_program(
+        ctx: Context<UpdateDexPrograms>,
+        protocol: u8,
+        program_id: Pubkey,
+    ) -> Result<()> {
+        let dex = poseidon_state::DexProgram { protocol: Protocol::try_from(protocol)?, program_id };
+        let config = &mut ctx.accounts.program_config;
+        config.migrate();
+        require!(!config.dex_programs.contains(&dex), PoseidonError::DexProgramAlreadyAccepted);
+        require!(
+            config.dex_programs.len() < poseidon_state::ProgramConfig::MAX_DEX_PROGRAMS,
+            PoseidonError::TooManyDexPrograms
+        );
+        config.dex_programs.push(dex);
+
+        msg!("DEX program {} accepted for {:?}", program_id, dex.protocol);
+        Ok(())
+    }
+
+    /// Stop accepting a DEX deployment for new configs.
+    pub fn remove_dex_program(
+        ctx: Context<UpdateDexPrograms>,
+        protocol: u8,
+        program_id: Pubkey,
+    ) -> Result<()> {
+        let dex = poseidon_state::DexProgram { protocol: Protocol::try_from(protocol)?, program_id };
+        let config = &mut ctx.accounts.program_config;
+        config.migrate();
+        let index = config
+            .dex_programs
+            .iter()
+            .position(|d| *d == dex)
+            .ok_or(PoseidonError::DexProgramNotFound)?;
+        config.dex_programs.remove(index);
+
+        msg!("DEX program {} removed for {:?}", program_id, dex.protocol);
+        Ok(())
+    }
+
     /// Read-only: check if rebalance is enabled for a position.
     pub fn is_enabled(ctx: Context<IsEnabled>) -> Result<()> {
         let config = &ctx.accounts.rebalance_config;
@@ -60,19 +652,130 @@ pub mod poseidon_rebalance {
     }
 }
 
-#[account]
-pub struct RebalanceConfig {
-    pub owner: Pubkey,                  // 32
-    pub position_mint: Pubkey,          // 32
-    pub enabled: bool,                  // 1
-    pub max_slippage_bps: u16,          // 2
-    pub min_yield_improvement_bps: u16, // 2
-    pub created_at: i64,                // 8
-    pub updated_at: i64,                // 8
+/// Split batch remaining accounts into groups of `stride`, checking each
+/// group's position_mint (second account) against the mint list.
+fn batch_accounts<'a, 'info>(
+    accounts: &'a [AccountInfo<'info>],
+    mints: &[Pubkey],
+    stride: usize,
+) -> Result<std::slice::ChunksExact<'a, AccountInfo<'info>>> {
+    require!(
+        !mints.is_empty() && mints.len() <= BatchProcessed::MAX_POSITIONS,
+        PoseidonError::InvalidBatchSize
+    );
+    require!(
+        accounts.len() == mints.len() * stride,
+        PoseidonError::InvalidPositionAccounts
+    );
+    let groups = accounts.chunks_exact(stride);
+    for (mint, group) in mints.iter().zip(groups.clone()) {
+        require_keys_eq!(group[1].key(), *mint, PoseidonError::InvalidPositionAccounts);
+    }
+    Ok(groups)
+}
+
+/// Create or re-enable one config from a batch's remaining accounts.
+fn enable_position<'info>(
+    owner: &Signer<'info>,
+    system_program: &Program<'info, System>,
+    program_config: &ProgramConfig,
+    protocol: Protocol,
+    max_slippage_bps: u16,
+    min_yield_improvement_bps: u16,
+    accounts: &[AccountInfo<'info>],
+) -> Result<()> {
+    let (config_info, position_mint) = (&accounts[0], &accounts[1]);
+    poseidon_state::verify_dex_position(program_config, protocol, position_mint, accounts.get(3))?;
+    poseidon_state::verify_position_owner(owner.key, protocol, position_mint, accounts.get(2))?;
+
+    let (expected_pda, bump) =
+        poseidon_state::RebalanceConfig::find_address(owner.key, position_mint.key, &crate::ID);
+    require_keys_eq!(config_info.key(), expected_pda, PoseidonError::InvalidConfigAddress);
+
+    let now = Clock::get()?.unix_timestamp;
+    let mut config = if config_info.data_is_empty() {
+        let rent = Rent::get()?.minimum_balance(RebalanceConfig::LEN);
+        system_program::create_account(
+            CpiContext::new_with_signer(
+                system_program.to_account_info(),
+                system_program::CreateAccount {
+                    from: owner.to_account_info(),
+                    to: config_info.clone(),
+                },
+                &[&[REBALANCE_SEED, owner.key.as_ref(), position_mint.key.as_ref(), &[bump]]],
+            ),
+            rent,
+            RebalanceConfig::LEN as u64,
+            &crate::ID,
+        )?;
+        RebalanceConfig(poseidon_state::RebalanceConfig {
+            owner: owner.key(),
+            position_mint: position_mint.key(),
+            created_at: now,
+            version: poseidon_state::RebalanceConfig::VERSION,
+            bump,
+            ..Default::default()
+        })
+    } else {
+        require_keys_eq!(*config_info.owner, crate::ID, PoseidonError::InvalidConfigAccount);
+        let config = RebalanceConfig::try_deserialize(&mut &config_info.try_borrow_data()?[..])?;
+        require_keys_eq!(config.owner, owner.key(), PoseidonError::ConfigOwnerMismatch);
+        // Older layouts are too short to write back; run migrate_config first
+        require!(!config.needs_migration(), PoseidonError::ConfigNeedsMigration);
+        config
+    };
+
+    config.enabled = true;
+    config.max_slippage_bps = max_slippage_bps;
+    config.min_yield_improvement_bps = min_yield_improvement_bps;
+    config.protocol = protocol;
+    config.stale = false;
+    config.updated_at = now;
+    config.try_serialize(&mut &mut config_info.try_borrow_mut_data()?[..])?;
+
+    RebalanceEnabled::new(config_info.key(), &config, false, now).emit();
+    Ok(())
+}
+
+/// Close one config from a batch's remaining accounts.
+fn disable_position<'info>(
+    owner: &Signer<'info>,
+    config_info: &AccountInfo<'info>,
+    position_mint: &AccountInfo<'info>,
+) -> Result<()> {
+    let (expected_pda, _bump) =
+        poseidon_state::RebalanceConfig::find_address(owner.key, position_mint.key, &crate::ID);
+    require_keys_eq!(config_info.key(), expected_pda, PoseidonError::InvalidConfigAddress);
+    require_keys_eq!(*config_info.owner, crate::ID, PoseidonError::InvalidConfigAccount);
+    let config = RebalanceConfig::try_deserialize(&mut &config_info.try_borrow_data()?[..])?;
+    require_keys_eq!(config.owner, owner.key(), PoseidonError::ConfigOwnerMismatch);
+
+    // Same as Anchor's `close` constraint
+    let lamports = config_info.lamports();
+    **owner.to_account_info().try_borrow_mut_lamports()? += lamports;
+    **config_info.try_borrow_mut_lamports()? = 0;
+    config_info.assign(&system_program::ID);
+    config_info.realloc(0, false)?;
+
+    RebalanceDisabled::new(config_info.key(), &config, true, Clock::get()?.unix_timestamp).emit();
+    Ok(())
 }
 
-impl RebalanceConfig {
-    pub const LEN: usize = 8 + 32 + 32 + 1 + 2 + 2 + 8 + 8; // 93
+fn batch_result(mint: &Pubkey, result: Result<()>) -> BatchResult {
+    let code = match result {
+        Ok(()) => 0,
+        Err(e) => {
+            msg!("Position {} failed: {}", mint, e);
+            u64::from(ProgramError::from(e))
+        }
+    };
+    BatchResult { position_mint: *mint, code }
+}
+
+fn report_batch(owner: Pubkey, action: u8, results: Vec<BatchResult>) {
+    let succeeded = results.iter().filter(|r| r.code == 0).count();
+    msg!("Batch processed: {}/{} positions succeeded", succeeded, results.len());
+    BatchProcessed { owner, action, results }.emit();
 }
 
 #[derive(Accounts)]
@@ -81,7 +784,7 @@ pub struct EnableRebalance<'info> {
         init_if_needed,
         payer = owner,
         space = RebalanceConfig::LEN,
-        seeds = [b"rebalance", owner.key().as_ref(), position_mint.key().as_ref()],
+        seeds = [REBALANCE_SEED, owner.key().as_ref(), position_mint.key().as_ref()],
         bump,
     )]
     pub rebalance_config: Account<'info, RebalanceConfig>,
@@ -90,19 +793,57 @@ pub struct EnableRebalance<'info> {
     pub owner: Signer<'info>,
 
     /// The position NFT mint (Orca/Raydium position mint, or Meteora position pubkey).
-    /// CHECK: We don't validate the mint program — any pubkey can be used as an identifier.
-    /// The security model is: only the owner can create/modify their own rebalance configs.
+    /// CHECK: Ownership is verified in the handler against the owner's token
+    /// account (NFT positions) or the DLMM position's recorded owner (Meteora).
     pub position_mint: UncheckedAccount<'info>,
 
+    /// The owner's token account holding the position NFT. Required for
+    /// Orca/Raydium; omit for Meteora.
+    /// CHECK: Mint, owner and amount are checked in the handler.
+    pub position_token_account: Option<UncheckedAccount<'info>>,
+
+    /// The DEX's position PDA for the NFT. Required for Orca/Raydium; omit
+    /// for Meteora.
+    /// CHECK: Owner program and address are checked in the handler.
+    pub dex_position: Option<UncheckedAccount<'info>>,
+
+    #[account(
+        seeds = [PROGRAM_CONFIG_SEED],
+        bump = program_config.bump,
+        constraint = !program_config.paused @ PoseidonError::ProgramPaused,
+    )]
+    pub program_config: Account<'info, ProgramConfig>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct EnableBatch<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    #[account(
+        seeds = [PROGRAM_CONFIG_SEED],
+        bump = program_config.bump,
+        constraint = !program_config.paused @ PoseidonError::ProgramPaused,
+    )]
+    pub program_config: Account<'info, ProgramConfig>,
+
     pub system_program: Program<'info, System>,
 }
 
+#[derive(Accounts)]
+pub struct DisableBatch<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+}
+
 #[derive(Accounts)]
 pub struct DisableRebalance<'info> {
     #[account(
         mut,
         close = owner,
-        seeds = [b"rebalance", owner.key().as_ref(), position_mint.key().as_ref()],
+        seeds = [REBALANCE_SEED, owner.key().as_ref(), position_mint.key().as_ref()],
         bump,
         has_one = owner,
     )]
@@ -115,10 +856,160 @@ pub struct DisableRebalance<'info> {
     pub position_mint: UncheckedAccount<'info>,
 }
 
+#[derive(Accounts)]
+pub struct SetEnabled<'info> {
+    #[account(
+        mut,
+        seeds = [REBALANCE_SEED, owner.key().as_ref(), position_mint.key().as_ref()],
+        bump,
+        has_one = owner,
+    )]
+    pub rebalance_config: Account<'info, RebalanceConfig>,
+
+    pub owner: Signer<'info>,
+
+    /// CHECK: Validated via PDA seeds match.
+    pub position_mint: UncheckedAccount<'info>,
+
+    #[account(seeds = [PROGRAM_CONFIG_SEED], bump = program_config.bump)]
+    pub program_config: Account<'info, ProgramConfig>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateConfig<'info> {
+    #[account(
+        mut,
+        seeds = [REBALANCE_SEED, owner.key().as_ref(), position_mint.key().as_ref()],
+        bump,
+        has_one = owner,
+    )]
+    pub rebalance_config: Account<'info, RebalanceConfig>,
+
+    pub owner: Signer<'info>,
+
+    /// CHECK: Validated via PDA seeds match.
+    pub position_mint: UncheckedAccount<'info>,
+
+    #[account(seeds = [PROGRAM_CONFIG_SEED], bump = program_config.bump)]
+    pub program_config: Account<'info, ProgramConfig>,
+}
+
+#[derive(Accounts)]
+pub struct SetUserProfile<'info> {
+    #[account(
+        init_if_needed,
+        payer = owner,
+        space = UserProfile::LEN,
+        seeds = [USER_PROFILE_SEED, owner.key().as_ref()],
+        bump,
+    )]
+    pub user_profile: Account<'info, UserProfile>,
+
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    #[account(seeds = [PROGRAM_CONFIG_SEED], bump = program_config.bump)]
+    pub program_config: Account<'info, ProgramConfig>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CloseUserProfile<'info> {
+    #[account(
+        mut,
+        close = owner,
+        seeds = [USER_PROFILE_SEED, owner.key().as_ref()],
+        bump = user_profile.bump,
+        has_one = owner,
+    )]
+    pub user_profile: Account<'info, UserProfile>,
+
+    #[account(mut)]
+    pub owner: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct TransferConfig<'info> {
+    #[account(
+        mut,
+        close = previous_owner,
+        seeds = [REBALANCE_SEED, previous_owner.key().as_ref(), position_mint.key().as_ref()],
+        bump,
+        constraint = old_config.owner == previous_owner.key() @ PoseidonError::ConfigOwnerMismatch,
+        constraint = !old_config.needs_migration() @ PoseidonError::ConfigNeedsMigration,
+    )]
+    pub old_config: Account<'info, RebalanceConfig>,
+
+    #[account(
+        init,
+        payer = new_owner,
+        space = RebalanceConfig::LEN,
+        seeds = [REBALANCE_SEED, new_owner.key().as_ref(), position_mint.key().as_ref()],
+        bump,
+    )]
+    pub new_config: Account<'info, RebalanceConfig>,
+
+    #[account(mut)]
+    pub new_owner: Signer<'info>,
+
+    /// CHECK: Must be the old config's owner; receives its rent.
+    #[account(mut)]
+    pub previous_owner: UncheckedAccount<'info>,
+
+    /// CHECK: Validated via PDA seeds match.
+    pub position_mint: UncheckedAccount<'info>,
+
+    /// The new owner's token account holding the position NFT. Required for
+    /// Orca/Raydium; omit for Meteora.
+    /// CHECK: Mint, owner and amount are checked in the handler.
+    pub position_token_account: Option<UncheckedAccount<'info>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct FlagStaleConfig<'info> {
+    #[account(
+        mut,
+        seeds = [REBALANCE_SEED, rebalance_config.owner.as_ref(), position_mint.key().as_ref()],
+        bump,
+        constraint = !rebalance_config.needs_migration() @ PoseidonError::ConfigNeedsMigration,
+    )]
+    pub rebalance_config: Account<'info, RebalanceConfig>,
+
+    /// CHECK: Validated via PDA seeds match.
+    pub position_mint: UncheckedAccount<'info>,
+
+    /// The token account currently holding the position NFT. Required for
+    /// Orca/Raydium; omit for Meteora.
+    /// CHECK: Mint and amount are checked in the handler.
+    pub position_token_account: Option<UncheckedAccount<'info>>,
+}
+
+#[derive(Accounts)]
+pub struct ReapConfig<'info> {
+    #[account(
+        mut,
+        close = owner,
+        seeds = [REBALANCE_SEED, owner.key().as_ref(), position_mint.key().as_ref()],
+        bump,
+        has_one = owner,
+    )]
+    pub rebalance_config: Account<'info, RebalanceConfig>,
+
+    /// CHECK: Receives the rent; must be the config's owner.
+    #[account(mut)]
+    pub owner: UncheckedAccount<'info>,
+
+    /// CHECK: Validated via PDA seeds match; must be closed or have zero supply.
+    pub position_mint: UncheckedAccount<'info>,
+}
+
 #[derive(Accounts)]
 pub struct IsEnabled<'info> {
     #[account(
-        seeds = [b"rebalance", owner.key().as_ref(), position_mint.key().as_ref()],
+        seeds = [REBALANCE_SEED, owner.key().as_ref(), position_mint.key().as_ref()],
         bump,
         has_one = owner,
     )]
@@ -129,3 +1020,149 @@ pub struct IsEnabled<'info> {
     /// CHECK: Validated via PDA seeds match.
     pub position_mint: UncheckedAccount<'info>,
 }
+
+#[derive(Accounts)]
+pub struct MigrateConfig<'info> {
+    /// CHECK: Older layouts can't deserialize as `RebalanceConfig`; the
+    /// discriminator and PDA are checked in the handler.
+    #[account(mut, owner = crate::ID)]
+    pub rebalance_config: UncheckedAccount<'info>,
+
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeProgramConfig<'info> {
+    #[account(
+        init,
+        payer = admin,
+        space = ProgramConfig::LEN,
+        seeds = [PROGRAM_CONFIG_SEED],
+        bump,
+    )]
+    pub program_config: Account<'info, ProgramConfig>,
+
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    #[account(constraint = program.programdata_address()? == Some(program_data.key()))]
+    pub program: Program<'info, crate::program::PoseidonRebalance>,
+
+    /// CHECK: Address checked against the program above; upgrade authority
+    /// checked in the handler.
+    pub program_data: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RotateAdmin<'info> {
+    #[account(
+        mut,
+        seeds = [PROGRAM_CONFIG_SEED],
+        bump = program_config.bump,
+        has_one = admin @ PoseidonError::Unauthorized,
+        constraint = !program_config.needs_migration() @ PoseidonError::ConfigNeedsMigration,
+    )]
+    pub program_config: Account<'info, ProgramConfig>,
+
+    pub admin: Signer<'info>,
+
+    pub new_admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateAgents<'info> {
+    #[account(
+        mut,
+        seeds = [PROGRAM_CONFIG_SEED],
+        bump = program_config.bump,
+        has_one = admin @ PoseidonError::Unauthorized,
+        constraint = !program_config.needs_migration() @ PoseidonError::ConfigNeedsMigration,
+    )]
+    pub program_config: Account<'info, ProgramConfig>,
+
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetPaused<'info> {
+    // Not `mut`: Anchor would write back the current layout on exit, which a
+    // config that still needs migration has no room for. set_paused writes
+    // the paused byte in place instead, so the admin can pause before migrating.
+    #[account(
+        seeds = [PROGRAM_CONFIG_SEED],
+        bump = program_config.bump,
+        has_one = admin @ PoseidonError::Unauthorized,
+        constraint = program_config.to_account_info().is_writable @ ErrorCode::ConstraintMut,
+    )]
+    pub program_config: Account<'info, ProgramConfig>,
+
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetRebalanceBounds<'info> {
+    #[account(
+        mut,
+        seeds = [PROGRAM_CONFIG_SEED],
+        bump = program_config.bump,
+        has_one = admin @ PoseidonError::Unauthorized,
+        realloc = ProgramConfig::LEN,
+        realloc::payer = admin,
+        realloc::zero = false,
+    )]
+    pub program_config: Account<'info, ProgramConfig>,
+
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateDexPrograms<'info> {
+    #[account(
+        mut,
+        seeds = [PROGRAM_CONFIG_SEED],
+        bump = program_config.bump,
+        has_one = admin @ PoseidonError::Unauthorized,
+        realloc = ProgramConfig::LEN,
+        realloc::payer = admin,
+        realloc::zero = false,
+    )]
+    pub program_config: Account<'info, ProgramConfig>,
+
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(mint_a: Pubkey, mint_b: Pubkey)]
+pub struct SetOracleFeed<'info> {
+    #[account(
+        init_if_needed,
+        payer = admin,
+        space = OracleFeed::LEN,
+        seeds = [ORACLE_FEED_SEED, mint_a.as_ref(), mint_b.as_ref()],
+        bump,
+    )]
+    pub oracle_feed: Account<'info, OracleFeed>,
+
+    #[account(
+        seeds = [PROGRAM_CONFIG_SEED],
+        bump = program_config.bump,
+        has_one = admin @ PoseidonError::Unauthorized,
+    )]
+    pub program_config: Account<'info, ProgramConfig>,
+
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    pub system_program: Program<'info, System>,
+}
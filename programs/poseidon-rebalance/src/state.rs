@@ -0,0 +1,83 @@
+//! Anchor account wrappers around the layouts in `poseidon-state`, so this
+//! program and poseidon-native can never disagree on a layout.
+
+use anchor_lang::prelude::*;
+use poseidon_state::AccountState;
+use std::io::Write;
+use std::ops::{Deref, DerefMut};
+
+/// Implements Anchor's account traits for a newtype over a shared state type,
+/// owned by this program.
+macro_rules! shared_account {
+    ($name:ident) => {
+        #[derive(Clone, Default)]
+        pub struct $name(pub poseidon_state::$name);
+
+        impl $name {
+            pub const LEN: usize = <poseidon_state::$name as AccountState>::LEN;
+        }
+
+        impl Deref for $name {
+            type Target = poseidon_state::$name;
+
+            fn deref(&self) -> &Self::Target {
+                &self.0
+            }
+        }
+
+        impl DerefMut for $name {
+            fn deref_mut(&mut self) -> &mut Self::Target {
+                &mut self.0
+            }
+        }
+
+        impl anchor_lang::Discriminator for $name {
+            const DISCRIMINATOR: [u8; 8] = <poseidon_state::$name as AccountState>::DISCRIMINATOR;
+        }
+
+        impl anchor_lang::Owner for $name {
+            fn owner() -> Pubkey {
+                crate::ID
+            }
+        }
+
+        impl anchor_lang::AccountSerialize for $name {
+            fn try_serialize<W: Write>(&self, writer: &mut W) -> Result<()> {
+                writer
+                    .write_all(&<poseidon_state::$name as AccountState>::DISCRIMINATOR)
+                    .map_err(|_| ErrorCode::AccountDidNotSerialize)?;
+                AnchorSerialize::serialize(&self.0, writer)
+                    .map_err(|_| ErrorCode::AccountDidNotSerialize)?;
+                Ok(())
+            }
+        }
+
+        impl anchor_lang::AccountDeserialize for $name {
+            fn try_deserialize(buf: &mut &[u8]) -> Result<Self> {
+                if buf.len() < 8 {
+                    return Err(ErrorCode::AccountDiscriminatorNotFound.into());
+                }
+                if buf[..8] != <poseidon_state::$name as AccountState>::DISCRIMINATOR {
+                    return Err(ErrorCode::AccountDiscriminatorMismatch.into());
+                }
+                Self::try_deserialize_unchecked(buf)
+            }
+
+            fn try_deserialize_unchecked(buf: &mut &[u8]) -> Result<Self> {
+                let inner = poseidon_state::$name::unpack_unchecked(buf)
+                    .map_err(|_| ErrorCode::AccountDidNotDeserialize)?;
+                Ok(Self(inner))
+            }
+        }
+
+        // Shared types aren't visible to Anchor's IDL generator; they're
+        // documented in poseidon-state instead.
+        #[cfg(feature = "idl-build")]
+        impl anchor_lang::anchor_syn::idl::build::IdlBuild for $name {}
+    };
+}
+
+shared_account!(RebalanceConfig);
+shared_account!(ProgramConfig);
+shared_account!(UserProfile);
+shared_account!(OracleFeed);
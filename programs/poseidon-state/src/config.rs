@@ -0,0 +1,302 @@
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::pubkey::Pubkey;
+
+use crate::{
+    AccountState, ConfigUpdated, PoseidonError, Protocol, Strategy, StrategyKind, REBALANCE_SEED,
+};
+
+/// Per-position rebalance preferences, stored at ["rebalance", owner, position_mint].
+///
+/// New fields are always appended; `version` stays at offset 93 so any layout
+/// can be identified from raw account data.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, Default, PartialEq)]
+pub struct RebalanceConfig {
+    pub owner: Pubkey,                  // 32
+    pub position_mint: Pubkey,          // 32
+    pub enabled: bool,                  // 1
+    pub max_slippage_bps: u16,          // 2
+    pub min_yield_improvement_bps: u16, // 2
+    pub created_at: i64,                // 8
+    pub updated_at: i64,                // 8
+    pub version: u8,                    // 1
+    pub bump: u8,                       // 1
+    // v2
+    pub protocol: Protocol,             // 1
+    // v3
+    /// Set when anyone shows the owner no longer holds the position. Agent
+    /// actions are refused until the owner re-enables or the holder takes over.
+    pub stale: bool,                    // 1
+    // v4
+    /// `INHERIT_*` flags; set fields are taken from the owner's `UserProfile`.
+    pub inherit: u8,                    // 1
+    /// Where collected fees go; the default pubkey means the owner's wallet.
+    pub fee_destination: Pubkey,        // 32
+    // v5
+    /// Range shape the agent must keep to; `Unspecified` leaves it free.
+    pub strategy: Strategy,             // 7
+    // v6
+    /// Cooldown after each rebalance; 0 means none.
+    pub min_seconds_between_rebalances: u32, // 4
+    /// Cap per rolling 24h window; 0 means no cap.
+    pub max_rebalances_per_day: u16,    // 2
+    pub last_rebalanced_at: i64,        // 8
+    /// Start of the current 24h window and the rebalances counted in it.
+    pub window_started_at: i64,         // 8
+    pub rebalances_in_window: u16,      // 2
+    // v7
+    /// Set when the position is deposited into vault mode. The vault may then
+    /// hold a newer NFT than `position_mint`, so reaping needs the vault
+    /// account to show it was withdrawn.
+    pub vaulted: bool,                  // 1
+}
+
+/// Fields an `update_config` call may change; `None` keeps the current value.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ConfigUpdate {
+    pub max_slippage_bps: Option<u16>,
+    pub min_yield_improvement_bps: Option<u16>,
+    pub inherit: Option<u8>,
+    pub fee_destination: Option<Pubkey>,
+    pub strategy_kind: Option<StrategyKind>,
+    pub strategy_width: Option<u32>,
+    pub recenter_threshold_bps: Option<u16>,
+    pub min_seconds_between_rebalances: Option<u32>,
+    pub max_rebalances_per_day: Option<u16>,
+}
+
+impl ConfigUpdate {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+impl AccountState for RebalanceConfig {
+    // sha256("account:RebalanceConfig")[0..8]
+    const DISCRIMINATOR: [u8; 8] = [111, 187, 136, 118, 41, 244, 175, 141];
+    const LEN: usize = 8 + 32 + 32 + 1 + 2 + 2 + 8 + 8 + 1 + 1 + 1 + 1 + 1 + 32 + Strategy::LEN
+        + 4 + 2 + 8 + 8 + 2 + 1; // 162
+}
+
+impl RebalanceConfig {
+    pub const VERSION: u8 = 7;
+    pub const RATE_LIMIT_WINDOW_SECONDS: i64 = 86_400;
+    /// Size of the original unversioned layout (version 0).
+    pub const V0_LEN: usize = 93;
+    pub const VERSION_OFFSET: usize = 93;
+
+    pub fn find_address(owner: &Pubkey, position_mint: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(
+            &[REBALANCE_SEED, owner.as_ref(), position_mint.as_ref()],
+            program_id,
+        )
+    }
+
+    /// Layout version of raw account data, without deserializing it.
+    pub fn data_version(data: &[u8]) -> u8 {
+        if data.len() > Self::VERSION_OFFSET {
+            data[Self::VERSION_OFFSET]
+        } else {
+            0
+        }
+    }
+
+    pub fn needs_migration(&self) -> bool {
+        self.version < Self::VERSION
+    }
+
+    /// Fill fields introduced after `self.version` with their defaults and
+    /// bump to the current version.
+    pub fn migrate(&mut self, bump: u8) {
+        if self.version < 1 {
+            self.bump = bump;
+        }
+        // v2: `protocol` stays Unknown until the owner re-enables
+        // v5: `strategy` stays Unspecified, so existing ranges aren't checked
+        // v6: rate limits start at 0 (no limit) with no rebalances counted
+        // v7: `vaulted` starts false; older configs could not be deposited
+        self.version = Self::VERSION;
+    }
+
+    /// Overwrite the fields supplied to `update_config`, leaving `None`s as
+    /// they are, and describe the change for the settings history.
+    pub fn apply_update(
+        &mut self,
+        address: Pubkey,
+        update: &ConfigUpdate,
+        now: i64,
+    ) -> Result<ConfigUpdated, PoseidonError> {
+        if update.inherit.is_some_and(|mask| mask & !Self::INHERIT_ALL != 0) {
+            return Err(PoseidonError::InvalidInheritMask);
+        }
+
+        let mut event = ConfigUpdated {
+            config: address,
+            position_mint: self.position_mint,
+            owner: self.owner,
+            old_max_slippage_bps: self.max_slippage_bps,
+            old_min_yield_improvement_bps: self.min_yield_improvement_bps,
+            old_inherit: self.inherit,
+            old_fee_destination: self.fee_destination,
+            old_strategy: self.strategy,
+            old_min_seconds_between_rebalances: self.min_seconds_between_rebalances,
+            old_max_rebalances_per_day: self.max_rebalances_per_day,
+            updated_at: now,
+            ..ConfigUpdated::default()
+        };
+
+        if let Some(value) = update.max_slippage_bps.filter(|v| *v != self.max_slippage_bps) {
+            self.max_slippage_bps = value;
+            event.changed |= ConfigUpdated::FIELD_MAX_SLIPPAGE;
+        }
+        if let Some(value) = update
+            .min_yield_improvement_bps
+            .filter(|v| *v != self.min_yield_improvement_bps)
+        {
+            self.min_yield_improvement_bps = value;
+            event.changed |= ConfigUpdated::FIELD_MIN_YIELD;
+        }
+        if let Some(value) = update.inherit.filter(|v| *v != self.inherit) {
+            self.inherit = value;
+            event.changed |= ConfigUpdated::FIELD_INHERIT;
+        }
+        if let Some(value) = update.fee_destination.filter(|v| *v != self.fee_destination) {
+            self.fee_destination = value;
+            event.changed |= ConfigUpdated::FIELD_FEE_DESTINATION;
+        }
+        let strategy = Strategy {
+            kind: update.strategy_kind.unwrap_or(self.strategy.kind),
+            width: update.strategy_width.unwrap_or(self.strategy.width),
+            recenter_threshold_bps: update
+                .recenter_threshold_bps
+                .unwrap_or(self.strategy.recenter_threshold_bps),
+        };
+        if strategy != self.strategy {
+            strategy.validate(self.protocol)?;
+            self.strategy = strategy;
+            event.changed |= ConfigUpdated::FIELD_STRATEGY;
+        }
+        if let Some(value) = update
+            .min_seconds_between_rebalances
+            .filter(|v| *v != self.min_seconds_between_rebalances)
+        {
+            self.min_seconds_between_rebalances = value;
+            event.changed |= ConfigUpdated::FIELD_RATE_LIMITS;
+        }
+        if let Some(value) = update
+            .max_rebalances_per_day
+            .filter(|v| *v != self.max_rebalances_per_day)
+        {
+            self.max_rebalances_per_day = value;
+            event.changed |= ConfigUpdated::FIELD_RATE_LIMITS;
+        }
+        if event.changed != 0 {
+            self.updated_at = now;
+        }
+
+        event.new_max_slippage_bps = self.max_slippage_bps;
+        event.new_min_yield_improvement_bps = self.min_yield_improvement_bps;
+        event.new_inherit = self.inherit;
+        event.new_fee_destination = self.fee_destination;
+        event.new_strategy = self.strategy;
+        event.new_min_seconds_between_rebalances = self.min_seconds_between_rebalances;
+        event.new_max_rebalances_per_day = self.max_rebalances_per_day;
+        Ok(event)
+    }
+
+    /// Count a rebalance at `now`, refusing it if it comes inside the cooldown
+    /// or past the cap for the current 24h window.
+    pub fn record_rebalance(&mut self, now: i64) -> Result<(), PoseidonError> {
+        if self.last_rebalanced_at != 0
+            && now < self.last_rebalanced_at + i64::from(self.min_seconds_between_rebalances)
+        {
+            return Err(PoseidonError::RebalanceTooSoon);
+        }
+        if now >= self.window_started_at + Self::RATE_LIMIT_WINDOW_SECONDS {
+            self.window_started_at = now;
+            self.rebalances_in_window = 0;
+        }
+        if self.max_rebalances_per_day != 0
+            && self.rebalances_in_window >= self.max_rebalances_per_day
+        {
+            return Err(PoseidonError::DailyRebalanceLimit);
+        }
+        self.rebalances_in_window = self.rebalances_in_window.saturating_add(1);
+        self.last_rebalanced_at = now;
+        Ok(())
+    }
+}
+
+/// Pre-per-position config, stored at ["rebalance", owner].
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, Default, PartialEq)]
+pub struct LegacyRebalanceConfig {
+    pub owner: Pubkey,                  // 32
+    pub enabled: bool,                  // 1
+    pub max_slippage_bps: u16,          // 2
+    pub min_yield_improvement_bps: u16, // 2
+    pub created_at: i64,                // 8
+    pub updated_at: i64,                // 8
+}
+
+impl AccountState for LegacyRebalanceConfig {
+    // Same Anchor account name as the per-position config
+    const DISCRIMINATOR: [u8; 8] = RebalanceConfig::DISCRIMINATOR;
+    const LEN: usize = 8 + 32 + 1 + 2 + 2 + 8 + 8; // 61
+}
+
+impl LegacyRebalanceConfig {
+    pub fn find_address(owner: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[REBALANCE_SEED, owner.as_ref()], program_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY: i64 = RebalanceConfig::RATE_LIMIT_WINDOW_SECONDS;
+    const NOW: i64 = 1_700_000_000;
+
+    fn config(cooldown: u32, per_day: u16) -> RebalanceConfig {
+        RebalanceConfig {
+            min_seconds_between_rebalances: cooldown,
+            max_rebalances_per_day: per_day,
+            ..RebalanceConfig::default()
+        }
+    }
+
+    #[test]
+    fn record_rebalance_without_limits_counts() {
+        let mut config = config(0, 0);
+        for now in [NOW, NOW, NOW + 1] {
+            config.record_rebalance(now).unwrap();
+        }
+        assert_eq!(config.rebalances_in_window, 3);
+        assert_eq!(config.window_started_at, NOW);
+        assert_eq!(config.last_rebalanced_at, NOW + 1);
+    }
+
+    #[test]
+    fn record_rebalance_enforces_cooldown() {
+        let mut config = config(600, 0);
+        config.record_rebalance(NOW).unwrap();
+        assert_eq!(config.record_rebalance(NOW + 599), Err(PoseidonError::RebalanceTooSoon));
+        assert_eq!(config.rebalances_in_window, 1);
+        assert_eq!(config.last_rebalanced_at, NOW);
+        config.record_rebalance(NOW + 600).unwrap();
+        assert_eq!(config.rebalances_in_window, 2);
+    }
+
+    #[test]
+    fn record_rebalance_enforces_daily_cap_and_resets() {
+        let mut config = config(0, 2);
+        config.record_rebalance(NOW).unwrap();
+        config.record_rebalance(NOW + 1_000).unwrap();
+        assert_eq!(config.record_rebalance(NOW + 2_000), Err(PoseidonError::DailyRebalanceLimit));
+        assert_eq!(config.rebalances_in_window, 2);
+        assert_eq!(config.last_rebalanced_at, NOW + 1_000);
+
+        config.record_rebalance(NOW + DAY).unwrap();
+        assert_eq!(config.window_started_at, NOW + DAY);
+        assert_eq!(config.rebalances_in_window, 1);
+    }
+}
@@ -0,0 +1,182 @@
+use std::fmt;
+
+use solana_program::program_error::ProgramError;
+
+/// Errors returned by both Poseidon programs. Codes start at 6000 to match
+/// Anchor's custom error offset, so one client-side decoder covers mainnet
+/// (native) and devnet (Anchor). Never renumber a variant; append new ones.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u32)]
+pub enum PoseidonError {
+    Unauthorized = 6000,
+    AgentAlreadyAuthorized = 6001,
+    AgentNotFound = 6002,
+    TooManyAgents = 6003,
+    InvalidFeeBps = 6004,
+    ProgramPaused = 6005,
+    InvalidConfigAccount = 6006,
+    ConfigUpToDate = 6007,
+    InvalidConfigAddress = 6008,
+    ConfigOwnerMismatch = 6009,
+    SlippageExceeded = 6010,
+    RebalanceDisabled = 6011,
+    InvalidTickRange = 6012,
+    ConfigNeedsMigration = 6013,
+    UnauthorizedAgent = 6014,
+    NotUpgradeAuthority = 6015,
+    ConfigAlreadyExists = 6016,
+    InvalidRecordAccount = 6017,
+    InvalidLegacyConfig = 6018,
+    InvalidProgramConfig = 6019,
+    InvalidPositionAccounts = 6020,
+    SlippageOutOfBounds = 6021,
+    MinYieldOutOfBounds = 6022,
+    InvalidBounds = 6023,
+    NothingToUpdate = 6024,
+    InvalidPositionAccount = 6025,
+    PositionNotOwned = 6026,
+    InvalidProtocol = 6027,
+    DexProgramNotAccepted = 6028,
+    DexProgramAlreadyAccepted = 6029,
+    DexProgramNotFound = 6030,
+    TooManyDexPrograms = 6031,
+    PositionStillHeld = 6032,
+    ConfigStale = 6033,
+    PositionStillOpen = 6034,
+    InvalidBatchSize = 6035,
+    InvalidUserProfile = 6036,
+    UserProfileRequired = 6037,
+    InvalidInheritMask = 6038,
+    InvalidStrategy = 6039,
+    RangeMismatch = 6040,
+    RebalanceNotDue = 6041,
+    RebalanceTooSoon = 6042,
+    DailyRebalanceLimit = 6043,
+    InvalidHistoryAccount = 6044,
+    InvalidInstructionsSysvar = 6045,
+    RebalanceNotInTransaction = 6046,
+    InvalidOracleAccount = 6047,
+    InvalidTokenAccount = 6048,
+    ValueOverflow = 6049,
+    InvalidGuardAccount = 6050,
+    GuardNotActive = 6051,
+    EndRebalanceMissing = 6052,
+    InvalidPoolAccount = 6053,
+    OraclePriceStale = 6054,
+    PriceDeviationExceeded = 6055,
+    InvalidVaultAccount = 6056,
+    PositionInVault = 6057,
+    VaultProtocolUnsupported = 6058,
+    PositionUnverified = 6059,
+    InvalidOracleFeed = 6060,
+    OracleFeedMismatch = 6061,
+    RebalanceNotGuarded = 6062,
+    GuardProtocolUnsupported = 6063,
+}
+
+impl PoseidonError {
+    /// Variant name, as Anchor reports it in `AnchorError::error_name`.
+    pub fn name(&self) -> String {
+        format!("{:?}", self)
+    }
+
+    pub fn message(&self) -> &'static str {
+        match self {
+            Self::Unauthorized => "Signer is not the program admin",
+            Self::AgentAlreadyAuthorized => "Agent is already authorized",
+            Self::AgentNotFound => "Agent is not authorized",
+            Self::TooManyAgents => "Agent list is full",
+            Self::InvalidFeeBps => "Fee bps out of range",
+            Self::ProgramPaused => "Program is paused",
+            Self::InvalidConfigAccount => "Not a rebalance config account",
+            Self::ConfigUpToDate => "Config is already at the current version",
+            Self::InvalidConfigAddress => "Config address does not match owner and position mint",
+            Self::ConfigOwnerMismatch => "Config is owned by a different wallet",
+            Self::SlippageExceeded => "Slippage exceeds the configured maximum",
+            Self::RebalanceDisabled => "Rebalance is not enabled for this position",
+            Self::InvalidTickRange => "Lower tick must be below upper tick",
+            Self::ConfigNeedsMigration => "Config must be migrated to the current version",
+            Self::UnauthorizedAgent => "Signer is not an authorized agent",
+            Self::NotUpgradeAuthority => "Signer is not the program upgrade authority",
+            Self::ConfigAlreadyExists => "Config already exists for this position",
+            Self::InvalidRecordAccount => "Not the rebalance record for this config",
+            Self::InvalidLegacyConfig => "Not a legacy per-owner config",
+            Self::InvalidProgramConfig => "Not the program config account",
+            Self::InvalidPositionAccounts => "Expected (position_mint, config) account pairs",
+            Self::SlippageOutOfBounds => "Max slippage must be between 1 bps and the protocol cap",
+            Self::MinYieldOutOfBounds => "Min yield improvement exceeds the protocol cap",
+            Self::InvalidBounds => "Bounds must be between 1 and 10000 bps",
+            Self::NothingToUpdate => "No config fields to update",
+            Self::InvalidPositionAccount => "Not a recognized position NFT or DLMM position",
+            Self::PositionNotOwned => "Signer does not hold this position",
+            Self::InvalidProtocol => "Unknown DEX protocol",
+            Self::DexProgramNotAccepted => "Position belongs to a DEX program that is not accepted",
+            Self::DexProgramAlreadyAccepted => "DEX program is already accepted",
+            Self::DexProgramNotFound => "DEX program is not in the accepted list",
+            Self::TooManyDexPrograms => "Accepted DEX program list is full",
+            Self::PositionStillHeld => "Config owner still holds the position",
+            Self::ConfigStale => "Config owner no longer holds the position",
+            Self::PositionStillOpen => "Position is still open on its DEX",
+            Self::InvalidBatchSize => "Batch must hold between 1 and 10 positions",
+            Self::InvalidUserProfile => "Not the user profile for this config's owner",
+            Self::UserProfileRequired => "Config inherits settings; pass the owner's user profile",
+            Self::InvalidInheritMask => "Unknown bits in inherit mask",
+            Self::InvalidStrategy => "Strategy parameters are invalid for this protocol",
+            Self::RangeMismatch => "New range does not match the position's strategy",
+            Self::RebalanceNotDue => "Price is not yet within the re-center threshold of the range edge",
+            Self::RebalanceTooSoon => "Minimum time between rebalances has not passed",
+            Self::DailyRebalanceLimit => "Daily rebalance limit reached for this position",
+            Self::InvalidHistoryAccount => "Not the rebalance history for this config",
+            Self::InvalidInstructionsSysvar => "Not the instructions sysvar",
+            Self::RebalanceNotInTransaction => "Transaction has no matching DEX close/open for this position",
+            Self::InvalidOracleAccount => "Not a verified oracle price account",
+            Self::InvalidTokenAccount => "Not the owner's token account for this mint",
+            Self::ValueOverflow => "Position value overflows at this price",
+            Self::InvalidGuardAccount => "Not the slippage guard for this config",
+            Self::GuardNotActive => "No begin_rebalance snapshot to check against",
+            Self::EndRebalanceMissing => "begin_rebalance must be followed by end_rebalance in the same transaction",
+            Self::InvalidPoolAccount => "Not the pool of this position",
+            Self::OraclePriceStale => "Oracle price is older than the allowed age",
+            Self::PriceDeviationExceeded => "Pool price deviates from the oracle beyond the allowed band",
+            Self::InvalidVaultAccount => "Not the vault for this config",
+            Self::PositionInVault => "Position is held in the config's vault",
+            Self::VaultProtocolUnsupported => "Vault mode does not support this protocol",
+            Self::PositionUnverified => "Position ownership not verified; re-enable the config",
+            Self::InvalidOracleFeed => "Not the oracle feed account for this token pair",
+            Self::OracleFeedMismatch => "Oracle account quotes a different feed than the token pair's",
+            Self::RebalanceNotGuarded => "DEX instructions must sit between begin_rebalance and end_rebalance",
+            Self::GuardProtocolUnsupported => "The slippage guard cannot value this protocol's positions",
+        }
+    }
+}
+
+impl fmt::Display for PoseidonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+impl From<PoseidonError> for u32 {
+    fn from(e: PoseidonError) -> Self {
+        e as u32
+    }
+}
+
+impl From<PoseidonError> for ProgramError {
+    fn from(e: PoseidonError) -> Self {
+        ProgramError::Custom(e as u32)
+    }
+}
+
+#[cfg(feature = "anchor")]
+impl From<PoseidonError> for anchor_lang::error::Error {
+    fn from(e: PoseidonError) -> Self {
+        anchor_lang::error::Error::from(anchor_lang::error::AnchorError {
+            error_name: e.name(),
+            error_code_number: e as u32,
+            error_msg: e.message().to_string(),
+            error_origin: None,
+            compared_values: None,
+        })
+    }
+}
@@ -0,0 +1,283 @@
+use borsh::BorshSerialize;
+use solana_program::{log::sol_log_data, pubkey::Pubkey};
+
+use crate::{Protocol, RebalanceConfig, Strategy, UserProfile};
+
+/// A Borsh-serialized event, logged with `sol_log_data` and framed the way
+/// Anchor's `emit!` frames it, so one parser reads both programs' logs.
+pub trait Event: BorshSerialize {
+    /// sha256("event:<Name>")[0..8]
+    const DISCRIMINATOR: [u8; 8];
+
+    fn data(&self) -> Vec<u8> {
+        let mut data = Self::DISCRIMINATOR.to_vec();
+        // Writing into a Vec can't fail
+        self.serialize(&mut data).unwrap();
+        data
+    }
+
+    fn emit(&self) {
+        sol_log_data(&[&self.data()]);
+    }
+}
+
+/// Settings changed through `update_config`, or a config flagged stale.
+/// `changed` is a bitmask of the `FIELD_*` flags; unchanged fields carry the
+/// same value in `old_*` and `new_*`.
+#[derive(BorshSerialize, Clone, Debug, Default, PartialEq)]
+pub struct ConfigUpdated {
+    pub config: Pubkey,
+    pub position_mint: Pubkey,
+    pub owner: Pubkey,
+    pub changed: u8,
+    pub old_max_slippage_bps: u16,
+    pub new_max_slippage_bps: u16,
+    pub old_min_yield_improvement_bps: u16,
+    pub new_min_yield_improvement_bps: u16,
+    pub old_inherit: u8,
+    pub new_inherit: u8,
+    pub old_fee_destination: Pubkey,
+    pub new_fee_destination: Pubkey,
+    pub old_strategy: Strategy,
+    pub new_strategy: Strategy,
+    pub old_min_seconds_between_rebalances: u32,
+    pub new_min_seconds_between_rebalances: u32,
+    pub old_max_rebalances_per_day: u16,
+    pub new_max_rebalances_per_day: u16,
+    pub updated_at: i64,
+}
+
+impl Event for ConfigUpdated {
+    const DISCRIMINATOR: [u8; 8] = [40, 241, 230, 122, 11, 19, 198, 194];
+}
+
+impl ConfigUpdated {
+    pub const FIELD_MAX_SLIPPAGE: u8 = 1 << 0;
+    pub const FIELD_MIN_YIELD: u8 = 1 << 1;
+    pub const FIELD_INHERIT: u8 = 1 << 2;
+    pub const FIELD_FEE_DESTINATION: u8 = 1 << 3;
+    pub const FIELD_STRATEGY: u8 = 1 << 4;
+    /// Either rebalance cooldown or daily cap.
+    pub const FIELD_RATE_LIMITS: u8 = 1 << 5;
+    /// Set by `flag_stale_config`; no settings change with it.
+    pub const FIELD_STALE: u8 = 1 << 6;
+
+    /// Event for a change to `config` outside its settings, flagged by
+    /// `changed`: every `old_*` and `new_*` is the current value.
+    pub fn new(address: Pubkey, config: &RebalanceConfig, changed: u8, now: i64) -> Self {
+        Self {
+            config: address,
+            position_mint: config.position_mint,
+            owner: config.owner,
+            changed,
+            old_max_slippage_bps: config.max_slippage_bps,
+            new_max_slippage_bps: config.max_slippage_bps,
+            old_min_yield_improvement_bps: config.min_yield_improvement_bps,
+            new_min_yield_improvement_bps: config.min_yield_improvement_bps,
+            old_inherit: config.inherit,
+            new_inherit: config.inherit,
+            old_fee_destination: config.fee_destination,
+            new_fee_destination: config.fee_destination,
+            old_strategy: config.strategy,
+            new_strategy: config.strategy,
+            old_min_seconds_between_rebalances: config.min_seconds_between_rebalances,
+            new_min_seconds_between_rebalances: config.min_seconds_between_rebalances,
+            old_max_rebalances_per_day: config.max_rebalances_per_day,
+            new_max_rebalances_per_day: config.max_rebalances_per_day,
+            updated_at: now,
+        }
+    }
+}
+
+/// A config was created or re-enabled (`enable_rebalance`, `enable_batch`),
+/// created for a position's new holder (`transfer_config`), or resumed with
+/// `set_enabled`.
+#[derive(BorshSerialize, Clone, Debug, Default, PartialEq)]
+pub struct RebalanceEnabled {
+    pub config: Pubkey,
+    pub position_mint: Pubkey,
+    pub owner: Pubkey,
+    pub protocol: Protocol,
+    pub max_slippage_bps: u16,
+    pub min_yield_improvement_bps: u16,
+    /// True for `set_enabled`, which keeps the existing settings.
+    pub resumed: bool,
+    pub enabled_at: i64,
+}
+
+impl Event for RebalanceEnabled {
+    const DISCRIMINATOR: [u8; 8] = [27, 161, 25, 136, 39, 185, 41, 209];
+}
+
+impl RebalanceEnabled {
+    pub fn new(address: Pubkey, config: &RebalanceConfig, resumed: bool, now: i64) -> Self {
+        Self {
+            config: address,
+            position_mint: config.position_mint,
+            owner: config.owner,
+            protocol: config.protocol,
+            max_slippage_bps: config.max_slippage_bps,
+            min_yield_improvement_bps: config.min_yield_improvement_bps,
+            resumed,
+            enabled_at: now,
+        }
+    }
+}
+
+/// A config was closed (`disable_rebalance`, `disable_batch`, `reap_config`,
+/// or `transfer_config` for the previous owner's) or paused with `set_enabled`.
+#[derive(BorshSerialize, Clone, Debug, Default, PartialEq)]
+pub struct RebalanceDisabled {
+    pub config: Pubkey,
+    pub position_mint: Pubkey,
+    pub owner: Pubkey,
+    /// False when only paused; the config and its settings remain.
+    pub closed: bool,
+    pub disabled_at: i64,
+}
+
+impl Event for RebalanceDisabled {
+    const DISCRIMINATOR: [u8; 8] = [102, 126, 245, 57, 197, 247, 166, 56];
+}
+
+impl RebalanceDisabled {
+    pub fn new(address: Pubkey, config: &RebalanceConfig, closed: bool, now: i64) -> Self {
+        Self {
+            config: address,
+            position_mint: config.position_mint,
+            owner: config.owner,
+            closed,
+            disabled_at: now,
+        }
+    }
+}
+
+/// The agent rebalanced a position.
+#[derive(BorshSerialize, Clone, Debug, Default, PartialEq)]
+pub struct RebalanceExecuted {
+    pub config: Pubkey,
+    pub position_mint: Pubkey,
+    pub agent: Pubkey,
+    pub old_tick_lower: i32,
+    pub old_tick_upper: i32,
+    pub new_tick_lower: i32,
+    pub new_tick_upper: i32,
+    pub realized_slippage_bps: u16,
+    pub fees_claimed_a: u64,
+    pub fees_claimed_b: u64,
+    pub rebalance_count: u64,
+    pub executed_at: i64,
+    /// The slippage and fees are the agent's own figures (`execute_rebalance`),
+    /// not measured by the program (`vault_rebalance`).
+    pub agent_reported: bool,
+}
+
+impl Event for RebalanceExecuted {
+    const DISCRIMINATOR: [u8; 8] = [194, 41, 129, 249, 215, 226, 122, 248];
+}
+
+/// A config was upgraded to the current layout, or created from a legacy
+/// per-owner config.
+#[derive(BorshSerialize, Clone, Debug, Default, PartialEq)]
+pub struct ConfigMigrated {
+    pub config: Pubkey,
+    pub position_mint: Pubkey,
+    pub owner: Pubkey,
+    pub from_version: u8,
+    pub to_version: u8,
+    pub migrated_at: i64,
+}
+
+impl Event for ConfigMigrated {
+    const DISCRIMINATOR: [u8; 8] = [115, 69, 99, 100, 192, 77, 40, 50];
+}
+
+impl ConfigMigrated {
+    /// `from_version` for configs split out of a legacy per-owner config.
+    pub const FROM_LEGACY: u8 = u8::MAX;
+
+    pub fn new(address: Pubkey, config: &RebalanceConfig, from_version: u8, now: i64) -> Self {
+        Self {
+            config: address,
+            position_mint: config.position_mint,
+            owner: config.owner,
+            from_version,
+            to_version: config.version,
+            migrated_at: now,
+        }
+    }
+}
+
+/// An owner's profile was created or overwritten with `set_user_profile`.
+#[derive(BorshSerialize, Clone, Debug, Default, PartialEq)]
+pub struct UserProfileUpdated {
+    pub profile: Pubkey,
+    pub owner: Pubkey,
+    pub max_slippage_bps: u16,
+    pub min_yield_improvement_bps: u16,
+    pub fee_destination: Pubkey,
+    pub strategy: Strategy,
+    /// True when the profile did not exist before.
+    pub created: bool,
+    pub updated_at: i64,
+}
+
+impl Event for UserProfileUpdated {
+    const DISCRIMINATOR: [u8; 8] = [137, 227, 236, 168, 126, 29, 3, 132];
+}
+
+impl UserProfileUpdated {
+    pub fn new(address: Pubkey, profile: &UserProfile, created: bool) -> Self {
+        Self {
+            profile: address,
+            owner: profile.owner,
+            max_slippage_bps: profile.max_slippage_bps,
+            min_yield_improvement_bps: profile.min_yield_improvement_bps,
+            fee_destination: profile.fee_destination,
+            strategy: profile.strategy,
+            created,
+            updated_at: profile.updated_at,
+        }
+    }
+}
+
+/// An owner closed their profile with `close_user_profile`.
+#[derive(BorshSerialize, Clone, Debug, Default, PartialEq)]
+pub struct UserProfileClosed {
+    pub profile: Pubkey,
+    pub owner: Pubkey,
+    pub closed_at: i64,
+}
+
+impl Event for UserProfileClosed {
+    const DISCRIMINATOR: [u8; 8] = [146, 238, 57, 117, 209, 189, 72, 166];
+}
+
+/// Outcome of one position in an `enable_batch` / `disable_batch` call.
+#[derive(BorshSerialize, Clone, Debug, Default, PartialEq)]
+pub struct BatchResult {
+    pub position_mint: Pubkey,
+    /// 0 on success, otherwise the `ProgramError` code (custom errors are
+    /// the `PoseidonError` value).
+    pub code: u64,
+}
+
+/// Per-position results of a batch. Failed positions are skipped, not
+/// rolled back, so the rest of the batch still applies.
+#[derive(BorshSerialize, Clone, Debug, Default, PartialEq)]
+pub struct BatchProcessed {
+    pub owner: Pubkey,
+    pub action: u8,
+    pub results: Vec<BatchResult>,
+}
+
+impl Event for BatchProcessed {
+    const DISCRIMINATOR: [u8; 8] = [199, 28, 80, 191, 111, 11, 127, 180];
+}
+
+impl BatchProcessed {
+    pub const ACTION_ENABLE: u8 = 0;
+    pub const ACTION_DISABLE: u8 = 1;
+    /// Keeps a batch of NFT positions within the default compute budget.
+    pub const MAX_POSITIONS: usize = 10;
+}
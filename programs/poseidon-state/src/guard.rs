@@ -0,0 +1,262 @@
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::{account_info::AccountInfo, msg, pubkey::Pubkey};
+
+use crate::{
+    math,
+    position::{associated_token_address, is_token_program, mint_decimals, TOKEN_ACCOUNT_MIN_LEN},
+    AccountState, OraclePrice, PoseidonError, PositionRange, GUARD_SEED,
+};
+
+/// Owner holdings and price captured by `begin_rebalance`, stored at
+/// ["slippage_guard", config]. Holdings are the owner's wallet balances plus
+/// what the old and new positions hold at the snapshot price, so a loss
+/// moved into a position still counts. `end_rebalance` in the same
+/// transaction values them again at the same price and fails if too much
+/// was lost.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, Default, PartialEq)]
+pub struct SlippageGuard {
+    pub version: u8,       // 1
+    pub bump: u8,          // 1
+    pub config: Pubkey,    // 32
+    /// Set by `begin_rebalance`, cleared by `end_rebalance`.
+    pub active: bool,      // 1
+    pub token_a: Pubkey,   // 32
+    pub token_b: Pubkey,   // 32
+    pub amount_a: u64,     // 8
+    pub amount_b: u64,     // 8
+    pub decimals_a: u8,    // 1
+    pub decimals_b: u8,    // 1
+    /// Price of token A in token B, `price * 10^expo`.
+    pub price: i64,        // 8
+    pub expo: i32,         // 4
+    pub started_at: i64,   // 8
+    // v2
+    pub pool: Pubkey,         // 32
+    /// DEX position account the rebalance withdraws from.
+    pub position: Pubkey,     // 32
+    /// DEX position account the rebalance opens or funds.
+    pub new_position: Pubkey, // 32
+}
+
+impl AccountState for SlippageGuard {
+    // sha256("account:SlippageGuard")[0..8]
+    const DISCRIMINATOR: [u8; 8] = [125, 212, 192, 226, 248, 97, 212, 208];
+    const LEN: usize = Self::V1_LEN + 32 + 32 + 32; // 241
+}
+
+impl SlippageGuard {
+    pub const VERSION: u8 = 2;
+    pub const V1_LEN: usize = 8 + 1 + 1 + 32 + 1 + 32 + 32 + 8 + 8 + 1 + 1 + 8 + 4 + 8; // 145
+
+    pub fn find_address(config: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[GUARD_SEED, config.as_ref()], program_id)
+    }
+
+    /// Value of the two balances in token B base units, scaled by a power of
+    /// ten that only depends on the snapshot, so values from the same guard
+    /// compare directly.
+    pub fn value(&self, amount_a: u64, amount_b: u64) -> Result<u128, PoseidonError> {
+        // price_b_units_per_a_unit = price * 10^(expo + decimals_b - decimals_a)
+        let exp = self.expo + i32::from(self.decimals_b) - i32::from(self.decimals_a);
+        let pow = 10u128
+            .checked_pow(exp.unsigned_abs())
+            .ok_or(PoseidonError::ValueOverflow)?;
+        let price = self.price as u128;
+        let (value_a, value_b) = if exp >= 0 {
+            (
+                u128::from(amount_a).checked_mul(price).and_then(|v| v.checked_mul(pow)),
+                Some(u128::from(amount_b)),
+            )
+        } else {
+            (
+                u128::from(amount_a).checked_mul(price),
+                u128::from(amount_b).checked_mul(pow),
+            )
+        };
+        value_a
+            .zip(value_b)
+            .and_then(|(a, b)| a.checked_add(b))
+            .ok_or(PoseidonError::ValueOverflow)
+    }
+
+    /// `wallet` plus what `positions` hold at the snapshot price.
+    pub fn holdings(
+        &self,
+        wallet: (u64, u64),
+        positions: &[PositionRange],
+    ) -> Result<(u64, u64), PoseidonError> {
+        let exp = self.expo + i32::from(self.decimals_b) - i32::from(self.decimals_a);
+        let sqrt_price = math::sqrt_price_x64(self.price.unsigned_abs(), exp)
+            .ok_or(PoseidonError::ValueOverflow)?;
+        positions.iter().try_fold(wallet, |(total_a, total_b), position| {
+            let (amount_a, amount_b) = position.token_amounts(sqrt_price);
+            total_a
+                .checked_add(amount_a)
+                .zip(total_b.checked_add(amount_b))
+                .ok_or(PoseidonError::ValueOverflow)
+        })
+    }
+
+    /// Value lost since the snapshot, in bps of the value at the snapshot.
+    /// Gains count as zero, as does anything once nothing was held.
+    pub fn loss_bps(&self, amount_a: u64, amount_b: u64) -> Result<u64, PoseidonError> {
+        let before = self.value(self.amount_a, self.amount_b)?;
+        let after = self.value(amount_a, amount_b)?;
+        if before == 0 || after >= before {
+            return Ok(0);
+        }
+        Ok(((before - after) * 10_000 / before) as u64)
+    }
+
+    /// Record `price` and the owner's holdings: the balances of `token_a` /
+    /// `token_b`, which must be the owner's associated token accounts for
+    /// `mint_a` / `mint_b`, plus what `positions` hold. The caller checks the
+    /// positions and sets `pool`, `position` and `new_position`.
+    pub fn snapshot(
+        &mut self,
+        owner: &Pubkey,
+        (token_a, mint_a): (&AccountInfo, &AccountInfo),
+        (token_b, mint_b): (&AccountInfo, &AccountInfo),
+        price: &OraclePrice,
+        positions: &[PositionRange],
+        now: i64,
+    ) -> Result<(), PoseidonError> {
+        self.decimals_a = mint_decimals(mint_a)?;
+        self.decimals_b = mint_decimals(mint_b)?;
+        require_associated(owner, token_a, mint_a)?;
+        require_associated(owner, token_b, mint_b)?;
+        self.token_a = *token_a.key;
+        self.token_b = *token_b.key;
+        self.price = price.price;
+        self.expo = price.expo;
+        let wallet = (
+            token_balance(owner, token_a, Some(mint_a.key))?,
+            token_balance(owner, token_b, Some(mint_b.key))?,
+        );
+        (self.amount_a, self.amount_b) = self.holdings(wallet, positions)?;
+        self.started_at = now;
+        self.active = true;
+        Ok(())
+    }
+}
+
+fn require_associated(
+    owner: &Pubkey,
+    token_account: &AccountInfo,
+    mint: &AccountInfo,
+) -> Result<(), PoseidonError> {
+    if *token_account.key != associated_token_address(owner, mint) {
+        msg!(
+            "Token account {} is not {}'s associated account for {}",
+            token_account.key,
+            owner,
+            mint.key
+        );
+        return Err(PoseidonError::InvalidTokenAccount);
+    }
+    Ok(())
+}
+
+/// Balance of an SPL token account held by `owner`, optionally checking its mint.
+pub fn token_balance(
+    owner: &Pubkey,
+    token_account: &AccountInfo,
+    mint: Option<&Pubkey>,
+) -> Result<u64, PoseidonError> {
+    if !is_token_program(token_account.owner) {
+        return Err(PoseidonError::InvalidTokenAccount);
+    }
+    let data = token_account
+        .try_borrow_data()
+        .map_err(|_| PoseidonError::InvalidTokenAccount)?;
+    if data.len() < TOKEN_ACCOUNT_MIN_LEN
+        || data[32..64] != owner.as_ref()[..]
+        || mint.is_some_and(|mint| data[..32] != mint.as_ref()[..])
+    {
+        msg!("Token account {} is not {}'s account for this mint", token_account.key, owner);
+        return Err(PoseidonError::InvalidTokenAccount);
+    }
+    Ok(u64::from_le_bytes(data[64..72].try_into().unwrap()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A snapshot of 1 token A and 2 token B at `price * 10^expo`.
+    fn guard(decimals_a: u8, decimals_b: u8, price: i64, expo: i32) -> SlippageGuard {
+        SlippageGuard {
+            active: true,
+            amount_a: 10u64.pow(u32::from(decimals_a)),
+            amount_b: 2 * 10u64.pow(u32::from(decimals_b)),
+            decimals_a,
+            decimals_b,
+            price,
+            expo,
+            ..SlippageGuard::default()
+        }
+    }
+
+    #[test]
+    fn loss_bps_ignores_swaps_at_the_snapshot_price_and_gains() {
+        let guard = guard(6, 6, 200, -2);
+        assert_eq!(guard.loss_bps(1_000_000, 2_000_000), Ok(0));
+        assert_eq!(guard.loss_bps(0, 4_000_000), Ok(0));
+        assert_eq!(guard.loss_bps(2_000_000, 0), Ok(0));
+        assert_eq!(guard.loss_bps(1_000_000, 2_100_000), Ok(0));
+    }
+
+    #[test]
+    fn loss_bps_measures_value_lost() {
+        let guard = guard(6, 6, 200, -2);
+        assert_eq!(guard.loss_bps(1_000_000, 1_960_000), Ok(100));
+        assert_eq!(guard.loss_bps(990_000, 1_980_000), Ok(100));
+        assert_eq!(guard.loss_bps(0, 0), Ok(10_000));
+    }
+
+    #[test]
+    fn loss_bps_scales_by_decimals() {
+        // Negative exponent: price per base unit is below one
+        let guard_a = guard(9, 6, 200, -2);
+        assert_eq!(guard_a.loss_bps(1_000_000_000, 1_800_000), Ok(500));
+        // Positive exponent: price per base unit is above one
+        let guard_b = guard(6, 9, 2, 0);
+        assert_eq!(guard_b.loss_bps(1_000_000, 1_800_000_000), Ok(500));
+        assert_eq!(guard_b.loss_bps(0, 4_000_000_000), Ok(0));
+    }
+
+    #[test]
+    fn loss_bps_counts_value_lost_inside_positions() {
+        // Price 1: a position over [-100, 100] holds ~4.99M of each token
+        let mut guard = guard(6, 6, 100, -2);
+        let old = PositionRange {
+            liquidity: 1_000_000_000,
+            tick_lower: -100,
+            tick_upper: 100,
+            ..PositionRange::default()
+        };
+        // Empty wallet: the position alone is the baseline
+        (guard.amount_a, guard.amount_b) = guard.holdings((0, 0), &[old]).unwrap();
+        assert_eq!((guard.amount_a, guard.amount_b), (4_987_272, 4_987_272));
+
+        // Withdrawn and redeposited with 10% lost to a bad swap, the wallet
+        // back where it started
+        let closed = PositionRange { liquidity: 0, ..old };
+        let new = PositionRange { liquidity: 900_000_000, ..old };
+        let (amount_a, amount_b) = guard.holdings((0, 0), &[closed, new]).unwrap();
+        assert_eq!(guard.loss_bps(amount_a, amount_b), Ok(1_000));
+
+        // Moved intact
+        let (amount_a, amount_b) = guard.holdings((0, 0), &[closed, old]).unwrap();
+        assert_eq!(guard.loss_bps(amount_a, amount_b), Ok(0));
+    }
+
+    #[test]
+    fn loss_bps_handles_empty_snapshot_and_overflow() {
+        let empty = SlippageGuard { price: 200, expo: -2, ..SlippageGuard::default() };
+        assert_eq!(empty.loss_bps(0, 0), Ok(0));
+
+        let huge = SlippageGuard { amount_a: u64::MAX, price: i64::MAX, expo: 20, ..empty };
+        assert_eq!(huge.loss_bps(0, 0), Err(PoseidonError::ValueOverflow));
+    }
+}
@@ -0,0 +1,176 @@
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::pubkey::Pubkey;
+
+use crate::{AccountState, HISTORY_SEED};
+
+/// One agent rebalance, as kept in a position's history. Whether the
+/// slippage and fees were measured on-chain or reported by the agent is kept
+/// in the history's `agent_reported` mask.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, Default, PartialEq)]
+pub struct HistoryEntry {
+    pub executed_at: i64,           // 8
+    pub old_tick_lower: i32,        // 4
+    pub old_tick_upper: i32,        // 4
+    pub new_tick_lower: i32,        // 4
+    pub new_tick_upper: i32,        // 4
+    pub realized_slippage_bps: u16, // 2
+    pub fees_claimed_a: u64,        // 8
+    pub fees_claimed_b: u64,        // 8
+    pub agent: Pubkey,              // 32
+}
+
+impl HistoryEntry {
+    pub const LEN: usize = 8 + 4 + 4 + 4 + 4 + 2 + 8 + 8 + 32; // 74
+}
+
+/// Last `CAPACITY` rebalances for a position, stored at
+/// ["rebalance_history", config]. Once full, each new entry overwrites the
+/// oldest; `head` is the slot written next, so entries read oldest-first
+/// starting from `head`. Bit `i` of `agent_reported` is set when the
+/// slippage and fees of `entries[i]` are the agent's own figures rather than
+/// measured by the program.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, Default, PartialEq)]
+pub struct RebalanceHistory {
+    pub version: u8,                // 1
+    pub bump: u8,                   // 1
+    pub config: Pubkey,             // 32
+    pub head: u16,                  // 2
+    pub entries: Vec<HistoryEntry>, // 4 + CAPACITY * 74
+    // v2
+    pub agent_reported: u32,        // 4
+}
+
+impl AccountState for RebalanceHistory {
+    // sha256("account:RebalanceHistory")[0..8]
+    const DISCRIMINATOR: [u8; 8] = [99, 118, 90, 210, 209, 145, 80, 204];
+    const LEN: usize = Self::V1_LEN + 4; // 2420
+}
+
+impl RebalanceHistory {
+    pub const VERSION: u8 = 2;
+    pub const V1_LEN: usize = 8 + 1 + 1 + 32 + 2 + 4 + Self::CAPACITY * HistoryEntry::LEN; // 2416
+    pub const CAPACITY: usize = 32;
+
+    pub fn find_address(config: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[HISTORY_SEED, config.as_ref()], program_id)
+    }
+
+    /// Bring a v1 history to the current layout. v1 kept no source for its
+    /// figures, so its entries count as agent-reported.
+    pub fn migrate(&mut self) {
+        if self.version < 2 {
+            self.agent_reported = ((1u64 << self.entries.len()) - 1) as u32;
+        }
+        self.version = Self::VERSION;
+    }
+
+    /// Append `entry`, overwriting the oldest once the buffer is full.
+    /// `agent_reported` marks its slippage and fees as the agent's figures.
+    pub fn push(&mut self, entry: HistoryEntry, agent_reported: bool) {
+        let head = usize::from(self.head);
+        if self.entries.len() < Self::CAPACITY {
+            self.entries.push(entry);
+        } else {
+            self.entries[head] = entry;
+        }
+        if agent_reported {
+            self.agent_reported |= 1 << head;
+        } else {
+            self.agent_reported &= !(1 << head);
+        }
+        self.head = ((head + 1) % Self::CAPACITY) as u16;
+    }
+
+    /// Whether the slippage and fees of `entries[slot]` were reported by the agent.
+    pub fn is_agent_reported(&self, slot: usize) -> bool {
+        slot < Self::CAPACITY && self.agent_reported & (1 << slot) != 0
+    }
+
+    /// Entries from oldest to newest.
+    pub fn chronological(&self) -> impl Iterator<Item = &HistoryEntry> {
+        let (newer, older) = self.entries.split_at(usize::from(self.head).min(self.entries.len()));
+        older.iter().chain(newer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(executed_at: i64) -> HistoryEntry {
+        HistoryEntry { executed_at, ..HistoryEntry::default() }
+    }
+
+    fn times(history: &RebalanceHistory) -> Vec<i64> {
+        history.chronological().map(|e| e.executed_at).collect()
+    }
+
+    #[test]
+    fn push_before_full_reads_in_order() {
+        let mut history = RebalanceHistory::default();
+        for t in 1..=3 {
+            history.push(entry(t), false);
+        }
+        assert_eq!(history.entries.len(), 3);
+        assert_eq!(history.head, 3);
+        assert_eq!(times(&history), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn push_after_wrap_overwrites_oldest() {
+        let mut history = RebalanceHistory::default();
+        let total = RebalanceHistory::CAPACITY as i64 + 5;
+        for t in 1..=total {
+            history.push(entry(t), false);
+        }
+        assert_eq!(history.entries.len(), RebalanceHistory::CAPACITY);
+        assert_eq!(history.head, 5);
+        assert_eq!(times(&history), (6..=total).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn push_tracks_agent_reported_per_slot() {
+        let mut history = RebalanceHistory::default();
+        for t in 1..=RebalanceHistory::CAPACITY as i64 {
+            history.push(entry(t), t % 2 == 1);
+        }
+        assert!(history.is_agent_reported(0));
+        assert!(!history.is_agent_reported(1));
+        // Overwriting a slot takes the new entry's source
+        history.push(entry(0), false);
+        assert!(!history.is_agent_reported(0));
+        assert!(!history.is_agent_reported(RebalanceHistory::CAPACITY));
+    }
+
+    #[test]
+    fn v1_history_migrates_as_agent_reported() {
+        let mut history = RebalanceHistory { version: 1, ..RebalanceHistory::default() };
+        for t in 1..=3 {
+            history.entries.push(entry(t));
+        }
+        history.head = 3;
+        let mut data = vec![0u8; RebalanceHistory::LEN];
+        history.pack(&mut data).unwrap();
+        let mut unpacked = RebalanceHistory::unpack(&data[..RebalanceHistory::V1_LEN]).unwrap();
+        assert_eq!(unpacked.agent_reported, 0);
+        unpacked.migrate();
+        assert_eq!(unpacked.version, RebalanceHistory::VERSION);
+        assert_eq!(unpacked.agent_reported, 0b111);
+    }
+
+    #[test]
+    fn wrapped_history_round_trips() {
+        let mut history = RebalanceHistory {
+            version: RebalanceHistory::VERSION,
+            ..RebalanceHistory::default()
+        };
+        for t in 1..=(RebalanceHistory::CAPACITY as i64 * 2 + 1) {
+            history.push(entry(t), false);
+        }
+        let mut data = vec![0u8; RebalanceHistory::LEN];
+        history.pack(&mut data).unwrap();
+        let unpacked = RebalanceHistory::unpack(&data).unwrap();
+        assert_eq!(unpacked, history);
+        assert_eq!(times(&unpacked).first(), Some(&(RebalanceHistory::CAPACITY as i64 + 2)));
+    }
+}
@@ -0,0 +1,326 @@
+use solana_program::{
+    account_info::AccountInfo, instruction::Instruction, msg, pubkey::Pubkey,
+    sysvar::instructions::load_instruction_at_checked,
+};
+
+use crate::{position::NFT_POSITION_SEED, PoseidonError, ProgramConfig, Protocol};
+
+// sha256("global:<name>")[0..8] of the DEX instructions that take liquidity
+// out of a position or close it.
+const ORCA_REMOVE: &[[u8; 8]] = &[
+    [160, 38, 208, 111, 104, 91, 44, 1], // decrease_liquidity
+    [58, 127, 188, 62, 79, 82, 196, 96], // decrease_liquidity_v2
+    [123, 134, 81, 0, 49, 68, 98, 98],   // close_position
+];
+const RAYDIUM_REMOVE: &[[u8; 8]] = ORCA_REMOVE;
+const METEORA_REMOVE: &[[u8; 8]] = &[
+    [80, 85, 209, 72, 24, 206, 177, 108],    // remove_liquidity
+    [26, 82, 102, 152, 240, 74, 105, 26],    // remove_liquidity_by_range
+    [10, 51, 61, 35, 112, 105, 24, 85],      // remove_all_liquidity
+    [230, 215, 82, 127, 241, 101, 227, 146], // remove_liquidity2
+    [204, 2, 195, 145, 53, 145, 145, 205],   // remove_liquidity_by_range2
+    [123, 134, 81, 0, 49, 68, 98, 98],       // close_position
+];
+
+/// An instruction that opens a position: where its args keep the range and
+/// which of its accounts are the new position's owner and pool.
+struct OpenPosition {
+    discriminator: [u8; 8],
+    /// Offset of the lower tick (bin id on Meteora) in the args.
+    range: usize,
+    /// The lower tick is followed by a width in bins, not the upper tick.
+    width: bool,
+    owner: usize,
+    pool: usize,
+}
+
+impl OpenPosition {
+    const fn ticks(discriminator: [u8; 8], range: usize, owner: usize, pool: usize) -> Self {
+        Self { discriminator, range, width: false, owner, pool }
+    }
+
+    /// Whether the instruction opens the position for `owner` in `pool`.
+    fn opens_for(&self, ix: &Instruction, owner: &Pubkey, pool: &Pubkey) -> bool {
+        let account = |index: usize| ix.accounts.get(index).map(|meta| meta.pubkey);
+        account(self.owner) == Some(*owner) && account(self.pool) == Some(*pool)
+    }
+
+    /// Range the instruction opens, inclusive of the upper bin on Meteora
+    /// as `PositionRange` reads it.
+    fn range(&self, data: &[u8]) -> Option<(i32, i32)> {
+        let args = data.get(8 + self.range..8 + self.range + 8)?;
+        let lower = i32::from_le_bytes(args[..4].try_into().unwrap());
+        let upper = i32::from_le_bytes(args[4..].try_into().unwrap());
+        if self.width {
+            Some((lower, lower.checked_add(upper)?.checked_sub(1)?))
+        } else {
+            Some((lower, upper))
+        }
+    }
+}
+
+// ... and those that open a position.
+const ORCA_OPEN: &[OpenPosition] = &[
+    // open_position(bumps(1), tick_lower, tick_upper)
+    // [funder, owner, position, position_mint, position_token_account, whirlpool, ..]
+    OpenPosition::ticks([135, 128, 47, 77, 15, 152, 240, 49], 1, 1, 5),
+    // open_position_with_metadata(bumps(2), tick_lower, tick_upper)
+    // [funder, owner, position, position_mint, metadata, position_token_account, whirlpool, ..]
+    OpenPosition::ticks([242, 29, 134, 48, 58, 110, 14, 60], 2, 1, 6),
+    // open_position_with_token_extensions(tick_lower, tick_upper, ..)
+    // [funder, owner, position, position_mint, position_token_account, whirlpool, ..]
+    OpenPosition::ticks([212, 47, 95, 92, 114, 102, 131, 250], 0, 1, 5),
+];
+const RAYDIUM_OPEN: &[OpenPosition] = &[
+    // open_position / open_position_v2(tick_lower, tick_upper, ..)
+    // [payer, position_nft_owner, nft_mint, nft_account, metadata, pool_state, ..]
+    OpenPosition::ticks([135, 128, 47, 77, 15, 152, 240, 49], 0, 1, 5),
+    OpenPosition::ticks([77, 184, 74, 214, 112, 86, 241, 199], 0, 1, 5),
+    // open_position_with_token22_nft(tick_lower, tick_upper, ..)
+    // [payer, position_nft_owner, nft_mint, nft_account, pool_state, ..]
+    OpenPosition::ticks([77, 255, 174, 82, 125, 29, 201, 46], 0, 1, 4),
+];
+const METEORA_OPEN: &[OpenPosition] = &[
+    // initialize_position(lower_bin_id, width)
+    // [payer, position, lb_pair, owner, ..]
+    OpenPosition {
+        discriminator: [219, 192, 234, 71, 190, 191, 102, 80],
+        range: 0,
+        width: true,
+        owner: 3,
+        pool: 2,
+    },
+];
+
+/// Check the transaction holding the current instruction also withdraws from
+/// `position` and opens a position for `owner` in `pool` at `new_range`, on
+/// the same accepted DEX program, so a recorded rebalance matches one that
+/// actually happened. Returns the indices of the first and last instruction
+/// on that DEX program.
+///
+/// `position` is the config's position mint (the position account itself on
+/// Meteora); `pool` is the position's pool; `new_range` is the (lower, upper)
+/// tick range, or inclusive bin range on Meteora; `instructions` is the
+/// instructions sysvar.
+pub fn verify_rebalance_instructions(
+    program_config: &ProgramConfig,
+    protocol: Protocol,
+    position: &Pubkey,
+    owner: &Pubkey,
+    pool: &Pubkey,
+    new_range: (i32, i32),
+    instructions: &AccountInfo,
+) -> Result<(usize, usize), PoseidonError> {
+    let (remove, open) = match protocol {
+        Protocol::OrcaWhirlpool => (ORCA_REMOVE, ORCA_OPEN),
+        Protocol::RaydiumClmm => (RAYDIUM_REMOVE, RAYDIUM_OPEN),
+        Protocol::MeteoraDlmm => (METEORA_REMOVE, METEORA_OPEN),
+        // Configs from before the protocol was recorded must be re-enabled
+        Protocol::Unknown => return Err(PoseidonError::InvalidProtocol),
+    };
+
+    // DEX program whose remove instruction touched the position
+    let mut removed_from: Option<Pubkey> = None;
+    // DEX programs that opened the owner a position in the pool at `new_range`
+    let mut opened_on: Vec<Pubkey> = Vec::new();
+    // Index and program of every instruction on an accepted DEX program
+    let mut dex_instructions: Vec<(usize, Pubkey)> = Vec::new();
+    let mut index = 0;
+    // Errors once `index` is past the last instruction
+    while let Ok(ix) = load_instruction_at_checked(index, instructions) {
+        index += 1;
+        if !program_config.accepts_dex(protocol, &ix.program_id) {
+            continue;
+        }
+        dex_instructions.push((index - 1, ix.program_id));
+        let Some(disc) = ix.data.get(..8) else { continue };
+
+        if remove.iter().any(|d| d[..] == *disc) {
+            let dex_position = if protocol.is_nft() {
+                let seeds: &[&[u8]] = &[NFT_POSITION_SEED, position.as_ref()];
+                Pubkey::find_program_address(seeds, &ix.program_id).0
+            } else {
+                *position
+            };
+            if ix.accounts.iter().any(|meta| meta.pubkey == dex_position) {
+                removed_from = Some(ix.program_id);
+            }
+        } else if let Some(open) = open.iter().find(|o| o.discriminator[..] == *disc) {
+            if open.range(&ix.data) == Some(new_range) && open.opens_for(&ix, owner, pool) {
+                opened_on.push(ix.program_id);
+            }
+        }
+    }
+    if index == 0 {
+        // Not the instructions sysvar, or nothing could be read from it
+        return Err(PoseidonError::InvalidInstructionsSysvar);
+    }
+
+    match removed_from {
+        Some(program_id) if opened_on.contains(&program_id) => {
+            let mut indices = dex_instructions
+                .iter()
+                .filter(|(_, dex)| *dex == program_id)
+                .map(|(index, _)| *index);
+            let first = indices.next().unwrap_or_default();
+            Ok((first, indices.next_back().unwrap_or(first)))
+        }
+        _ => {
+            msg!(
+                "No matching {:?} close/open of [{}, {}] for position {} in this transaction",
+                protocol,
+                new_range.0,
+                new_range.1,
+                position
+            );
+            Err(PoseidonError::RebalanceNotInTransaction)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use solana_program::{
+        instruction::AccountMeta,
+        sysvar::{
+            self,
+            instructions::{construct_instructions_data, BorrowedAccountMeta, BorrowedInstruction},
+        },
+    };
+
+    use super::*;
+    use crate::position::ORCA_WHIRLPOOL_PROGRAM_ID;
+
+    const DECREASE: [u8; 8] = ORCA_REMOVE[0];
+    const OPEN: [u8; 8] = ORCA_OPEN[0].discriminator;
+
+    fn program_config() -> ProgramConfig {
+        let mut config = ProgramConfig::default();
+        config.migrate();
+        config
+    }
+
+    fn instruction(program_id: Pubkey, disc: [u8; 8], args: &[u8], accounts: &[Pubkey]) -> Instruction {
+        Instruction {
+            program_id,
+            accounts: accounts.iter().map(|key| AccountMeta::new(*key, false)).collect(),
+            data: [&disc[..], args].concat(),
+        }
+    }
+
+    /// Orca `open_position` args: bump, then the range.
+    fn open_args(lower: i32, upper: i32) -> Vec<u8> {
+        [&[255][..], &lower.to_le_bytes(), &upper.to_le_bytes()].concat()
+    }
+
+    /// Orca `open_position` accounts, with `owner` and `pool` in place.
+    fn open_accounts(owner: Pubkey, pool: Pubkey) -> Vec<Pubkey> {
+        let mut accounts = vec![Pubkey::new_unique(); 6];
+        (accounts[1], accounts[5]) = (owner, pool);
+        accounts
+    }
+
+    fn verify(
+        position_mint: &Pubkey,
+        (owner, pool): (&Pubkey, &Pubkey),
+        new_range: (i32, i32),
+        instructions: &[Instruction],
+    ) -> Result<(usize, usize), PoseidonError> {
+        let borrowed: Vec<BorrowedInstruction> = instructions
+            .iter()
+            .map(|ix| BorrowedInstruction {
+                program_id: &ix.program_id,
+                accounts: ix
+                    .accounts
+                    .iter()
+                    .map(|meta| BorrowedAccountMeta {
+                        pubkey: &meta.pubkey,
+                        is_signer: meta.is_signer,
+                        is_writable: meta.is_writable,
+                    })
+                    .collect(),
+                data: &ix.data,
+            })
+            .collect();
+        let mut data = construct_instructions_data(&borrowed);
+        let mut lamports = 0;
+        let account = AccountInfo::new(
+            &sysvar::instructions::ID,
+            false,
+            false,
+            &mut lamports,
+            &mut data,
+            &sysvar::ID,
+            false,
+            0,
+        );
+        verify_rebalance_instructions(
+            &program_config(),
+            Protocol::OrcaWhirlpool,
+            position_mint,
+            owner,
+            pool,
+            new_range,
+            &account,
+        )
+    }
+
+    #[test]
+    fn open_position_range_is_read_from_args() {
+        let open = &ORCA_OPEN[0];
+        assert_eq!(open.range(&[&OPEN[..], &open_args(-64, 128)].concat()), Some((-64, 128)));
+        assert_eq!(open.range(&OPEN), None);
+
+        let meteora = &METEORA_OPEN[0];
+        let data = [&meteora.discriminator[..], &(-10i32).to_le_bytes(), &70i32.to_le_bytes()].concat();
+        assert_eq!(meteora.range(&data), Some((-10, 59)));
+    }
+
+    #[test]
+    fn rebalance_must_open_the_claimed_range() {
+        let (position_mint, owner, pool) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let (dex_position, _) = Pubkey::find_program_address(
+            &[NFT_POSITION_SEED, position_mint.as_ref()],
+            &ORCA_WHIRLPOOL_PROGRAM_ID,
+        );
+        let other = Pubkey::new_unique();
+        let transaction = [
+            instruction(other, [0; 8], &[], &[]),
+            instruction(ORCA_WHIRLPOOL_PROGRAM_ID, DECREASE, &[], &[dex_position]),
+            instruction(ORCA_WHIRLPOOL_PROGRAM_ID, OPEN, &open_args(-64, 128), &open_accounts(owner, pool)),
+            instruction(other, [0; 8], &[], &[]),
+        ];
+        assert_eq!(verify(&position_mint, (&owner, &pool), (-64, 128), &transaction), Ok((1, 2)));
+        assert_eq!(
+            verify(&position_mint, (&owner, &pool), (-64, 192), &transaction),
+            Err(PoseidonError::RebalanceNotInTransaction)
+        );
+        // Withdrawing from another position doesn't count
+        assert_eq!(
+            verify(&Pubkey::new_unique(), (&owner, &pool), (-64, 128), &transaction),
+            Err(PoseidonError::RebalanceNotInTransaction)
+        );
+    }
+
+    #[test]
+    fn rebalance_must_open_for_the_owner_in_the_pool() {
+        let (position_mint, owner, pool) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let (dex_position, _) = Pubkey::find_program_address(
+            &[NFT_POSITION_SEED, position_mint.as_ref()],
+            &ORCA_WHIRLPOOL_PROGRAM_ID,
+        );
+        let transaction = |accounts: Vec<Pubkey>| {
+            [
+                instruction(ORCA_WHIRLPOOL_PROGRAM_ID, DECREASE, &[], &[dex_position]),
+                instruction(ORCA_WHIRLPOOL_PROGRAM_ID, OPEN, &open_args(-64, 128), &accounts),
+            ]
+        };
+        let stranger = Pubkey::new_unique();
+        for accounts in [open_accounts(stranger, pool), open_accounts(owner, stranger), vec![owner]] {
+            assert_eq!(
+                verify(&position_mint, (&owner, &pool), (-64, 128), &transaction(accounts)),
+                Err(PoseidonError::RebalanceNotInTransaction)
+            );
+        }
+    }
+}
@@ -0,0 +1,96 @@
+//! Account layouts shared by `poseidon-native` (mainnet) and `poseidon-rebalance`
+//! (devnet). Both programs read and write accounts exclusively through these
+//! types, so a layout change lands in both at once.
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::program_error::ProgramError;
+
+pub mod config;
+pub mod error;
+pub mod event;
+pub mod guard;
+pub mod history;
+pub mod introspection;
+pub mod math;
+pub mod oracle;
+pub mod oracle_feed;
+pub mod pool;
+pub mod position;
+pub mod profile;
+pub mod program_config;
+pub mod record;
+pub mod strategy;
+pub mod vault;
+
+pub use config::{ConfigUpdate, LegacyRebalanceConfig, RebalanceConfig};
+pub use error::PoseidonError;
+pub use event::{
+    BatchProcessed, BatchResult, ConfigMigrated, ConfigUpdated, Event, RebalanceDisabled,
+    RebalanceEnabled, RebalanceExecuted, UserProfileClosed, UserProfileUpdated,
+};
+pub use guard::{token_balance, SlippageGuard};
+pub use history::{HistoryEntry, RebalanceHistory};
+pub use introspection::verify_rebalance_instructions;
+pub use oracle::OraclePrice;
+pub use oracle_feed::OracleFeed;
+pub use pool::{pool_current_tick, pool_price, position_pool, PositionRange};
+pub use position::{
+    is_position_closed, mint_decimals, position_holder, verify_dex_position, verify_position_owner,
+    DexProgram, Protocol,
+};
+pub use profile::{EffectiveSettings, UserProfile};
+pub use program_config::ProgramConfig;
+pub use record::RebalanceRecord;
+pub use strategy::{Strategy, StrategyKind};
+pub use vault::VaultState;
+
+// PDA seeds
+pub const REBALANCE_SEED: &[u8] = b"rebalance";
+pub const PROGRAM_CONFIG_SEED: &[u8] = b"program_config";
+pub const RECORD_SEED: &[u8] = b"rebalance_record";
+pub const USER_PROFILE_SEED: &[u8] = b"user_profile";
+pub const HISTORY_SEED: &[u8] = b"rebalance_history";
+pub const GUARD_SEED: &[u8] = b"slippage_guard";
+pub const VAULT_SEED: &[u8] = b"vault";
+pub const ORACLE_FEED_SEED: &[u8] = b"oracle_feed";
+
+/// A Borsh-serialized account prefixed with an Anchor-style 8-byte discriminator.
+pub trait AccountState: BorshSerialize + BorshDeserialize {
+    /// sha256("account:<Name>")[0..8]
+    const DISCRIMINATOR: [u8; 8];
+    /// Full account size, discriminator included.
+    const LEN: usize;
+
+    /// Deserialize account data, checking the discriminator.
+    fn unpack(data: &[u8]) -> Result<Self, ProgramError> {
+        if data.len() < 8 || data[..8] != Self::DISCRIMINATOR {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Self::unpack_unchecked(data)
+    }
+
+    /// Deserialize account data without checking the discriminator. Data
+    /// shorter than `LEN` is zero-padded, so older, shorter layouts still load.
+    fn unpack_unchecked(data: &[u8]) -> Result<Self, ProgramError> {
+        let body = data.get(8..).unwrap_or_default();
+        let result = if body.len() >= Self::LEN - 8 {
+            Self::deserialize(&mut &body[..])
+        } else {
+            let mut padded = vec![0u8; Self::LEN - 8];
+            padded[..body.len()].copy_from_slice(body);
+            Self::deserialize(&mut &padded[..])
+        };
+        result.map_err(|_| ProgramError::InvalidAccountData)
+    }
+
+    /// Write the discriminator and serialized state into account data.
+    fn pack(&self, data: &mut [u8]) -> Result<(), ProgramError> {
+        if data.len() < Self::LEN {
+            return Err(ProgramError::AccountDataTooSmall);
+        }
+        data[..8].copy_from_slice(&Self::DISCRIMINATOR);
+        data[8..].fill(0);
+        self.serialize(&mut &mut data[8..])
+            .map_err(|_| ProgramError::AccountDataTooSmall)
+    }
+}
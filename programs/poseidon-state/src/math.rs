@@ -0,0 +1,193 @@
+//! Integer tick and price math. Prices are Q64.64 fixed point, the format
+//! Whirlpool and Raydium CLMM store them in.
+
+/// `1.0` in Q64.64.
+pub const Q64: u128 = 1 << 64;
+/// Largest tick magnitude `sqrt_price_at_tick` accepts; the CLMM tick bound.
+pub const MAX_TICK: i32 = 443_636;
+
+// floor(2^64 / sqrt(1.0001)^(2^i)): the square root price of tick -2^i.
+const NEGATIVE_TICK_SQRT_PRICES: [u128; 19] = [
+    18_445_821_805_675_392_311,
+    18_444_899_583_751_176_498,
+    18_443_055_278_223_354_162,
+    18_439_367_220_385_604_838,
+    18_431_993_317_065_449_817,
+    18_417_254_355_718_160_513,
+    18_387_811_781_193_591_352,
+    18_329_067_761_203_520_168,
+    18_212_142_134_806_087_854,
+    17_980_523_815_641_551_639,
+    17_526_086_738_831_147_013,
+    16_651_378_430_235_024_244,
+    15_030_750_278_693_429_944,
+    12_247_334_978_882_834_399,
+    8_131_365_268_884_726_200,
+    3_584_323_654_723_342_297,
+    696_457_651_847_595_233,
+    26_294_789_957_452_057,
+    37_481_735_321_082,
+];
+
+/// `sqrt(1.0001^tick)` in Q64.64, for `|tick| <= MAX_TICK`.
+pub fn sqrt_price_at_tick(tick: i32) -> u128 {
+    debug_assert!(tick.unsigned_abs() <= MAX_TICK as u32);
+    let abs = tick.unsigned_abs();
+    // Every factor is below one, so the product never overflows
+    let mut ratio = Q64;
+    for (bit, factor) in NEGATIVE_TICK_SQRT_PRICES.iter().enumerate() {
+        if abs & (1 << bit) != 0 {
+            ratio = (ratio * factor) >> 64;
+        }
+    }
+    if tick > 0 {
+        u128::MAX / ratio
+    } else {
+        ratio
+    }
+}
+
+/// `a * b / denominator`, rounded down, through a 256-bit product. `None`
+/// if the quotient does not fit or `denominator` is zero.
+pub fn mul_div(a: u128, b: u128, denominator: u128) -> Option<u128> {
+    if denominator == 0 {
+        return None;
+    }
+    if let Some(product) = a.checked_mul(b) {
+        return Some(product / denominator);
+    }
+
+    // 256-bit product from 64-bit halves
+    const LOW: u128 = u64::MAX as u128;
+    let (a_hi, a_lo, b_hi, b_lo) = (a >> 64, a & LOW, b >> 64, b & LOW);
+    let (lo_lo, lo_hi, hi_lo) = (a_lo * b_lo, a_lo * b_hi, a_hi * b_lo);
+    let mid = (lo_lo >> 64) + (lo_hi & LOW) + (hi_lo & LOW);
+    let low = (lo_lo & LOW) | (mid << 64);
+    let high = a_hi * b_hi + (lo_hi >> 64) + (hi_lo >> 64) + (mid >> 64);
+    if high >= denominator {
+        return None;
+    }
+
+    // Long division of high:low, one bit at a time
+    let (mut remainder, mut quotient) = (high, 0u128);
+    for bit in (0..128).rev() {
+        let carry = remainder >> 127;
+        remainder = (remainder << 1) | ((low >> bit) & 1);
+        if carry == 1 || remainder >= denominator {
+            remainder = remainder.wrapping_sub(denominator);
+            quotient |= 1 << bit;
+        }
+    }
+    Some(quotient)
+}
+
+/// Integer square root, rounded down.
+pub fn isqrt(value: u128) -> u128 {
+    if value < 2 {
+        return value;
+    }
+    // Newton's method from a power of two above the root
+    let mut x = 1u128 << ((128 - value.leading_zeros()).div_ceil(2));
+    loop {
+        let next = (x + value / x) / 2;
+        if next >= x {
+            return x;
+        }
+        x = next;
+    }
+}
+
+/// Square root, in Q64.64, of the price `price * 10^exp`. `None` for prices
+/// of 2^64 or more.
+pub fn sqrt_price_x64(price: u64, exp: i32) -> Option<u128> {
+    let pow = 10u128.checked_pow(exp.unsigned_abs())?;
+    let (numerator, denominator) = if exp >= 0 {
+        (u128::from(price).checked_mul(pow)?, 1)
+    } else {
+        (u128::from(price), pow)
+    };
+    if numerator == 0 {
+        return Some(0);
+    }
+    // Scale the ratio by 2^shift, as far as it fits, then take the root:
+    // sqrt(ratio * 2^128) = sqrt(ratio * 2^shift) * 2^((128 - shift) / 2)
+    let magnitude = i64::from(128 - numerator.leading_zeros())
+        - i64::from(128 - denominator.leading_zeros());
+    if magnitude >= 64 {
+        return None;
+    }
+    let shift = (127 - magnitude).clamp(0, 126) as u32 & !1;
+    let scaled = mul_div(numerator, 1 << shift, denominator)?;
+    isqrt(scaled).checked_mul(1 << ((128 - shift) / 2))
+}
+
+/// Value of `amount_a` and `amount_b` in token B base units at a Q64.64
+/// square root price of token A in token B. `None` on overflow.
+pub fn value_in_b(amount_a: u64, amount_b: u64, sqrt_price: u128) -> Option<u128> {
+    let value_a = mul_div(mul_div(u128::from(amount_a), sqrt_price, Q64)?, sqrt_price, Q64)?;
+    value_a.checked_add(u128::from(amount_b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sqrt_price_at_tick_matches_clmm_bounds() {
+        assert_eq!(sqrt_price_at_tick(0), Q64);
+        // Whirlpool's MIN_SQRT_PRICE_X64 / MAX_SQRT_PRICE_X64
+        assert_eq!(sqrt_price_at_tick(-MAX_TICK), 4_295_048_016);
+        let max = sqrt_price_at_tick(MAX_TICK);
+        let expected = 79_226_673_515_401_279_992_447_579_055u128;
+        assert!(max.abs_diff(expected) < expected / 1_000_000_000, "{max}");
+    }
+
+    #[test]
+    fn sqrt_price_at_tick_is_monotonic_around_zero() {
+        assert_eq!(sqrt_price_at_tick(-1), NEGATIVE_TICK_SQRT_PRICES[0]);
+        assert!(sqrt_price_at_tick(1) > Q64);
+        assert!(sqrt_price_at_tick(-1) < Q64);
+        assert!(sqrt_price_at_tick(1_907) > sqrt_price_at_tick(1_906));
+    }
+
+    #[test]
+    fn mul_div_uses_full_width_product() {
+        assert_eq!(mul_div(6, 7, 4), Some(10));
+        assert_eq!(mul_div(u128::MAX, u128::MAX, u128::MAX), Some(u128::MAX));
+        assert_eq!(mul_div(u128::MAX, 1 << 64, 1 << 65), Some(u128::MAX >> 1));
+        assert_eq!(mul_div(1 << 100, 1 << 100, 1 << 90), Some(1 << 110));
+        assert_eq!(mul_div(u128::MAX, 2, 1), None);
+        assert_eq!(mul_div(1, 1, 0), None);
+    }
+
+    #[test]
+    fn isqrt_rounds_down() {
+        assert_eq!(isqrt(0), 0);
+        assert_eq!(isqrt(15), 3);
+        assert_eq!(isqrt(16), 4);
+        assert_eq!(isqrt(u128::MAX), u64::MAX as u128);
+    }
+
+    #[test]
+    fn sqrt_price_x64_matches_ticks() {
+        assert_eq!(sqrt_price_x64(1, 0), Some(Q64));
+        assert_eq!(sqrt_price_x64(400, -2), Some(2 * Q64));
+        assert_eq!(sqrt_price_x64(25, -2), Some(Q64 / 2));
+        // The price of tick 1000, to 8 decimals
+        let price = sqrt_price_x64(110_516_539, -8).unwrap();
+        let tick = sqrt_price_at_tick(1_000);
+        assert!(price.abs_diff(tick) < tick / 10_000_000, "{price} / {tick}");
+        // Tiny prices keep their precision: sqrt(1e-12) = 1e-6
+        let tiny = sqrt_price_x64(1, -12).unwrap();
+        assert!(tiny.abs_diff(Q64 / 1_000_000) < Q64 / 1_000_000 / 1_000_000, "{tiny}");
+        assert_eq!(sqrt_price_x64(1, 20), None);
+    }
+
+    #[test]
+    fn value_in_b_prices_token_a() {
+        // 2.25 token B per token A
+        let sqrt_price = 3 * Q64 / 2;
+        assert_eq!(value_in_b(100, 5, sqrt_price), Some(230));
+        assert_eq!(value_in_b(0, 5, sqrt_price), Some(5));
+    }
+}
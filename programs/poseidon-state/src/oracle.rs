@@ -0,0 +1,171 @@
+use solana_program::{account_info::AccountInfo, msg, pubkey, pubkey::Pubkey};
+
+use crate::{math, PoseidonError};
+
+/// Pyth pull-oracle receiver, owner of `PriceUpdateV2` accounts.
+pub const PYTH_RECEIVER_PROGRAM_ID: Pubkey = pubkey!("rec5EKMGg6MxZYaMdyBfgwp4d5rB9T1VQH5pJv5LtFJ");
+
+// sha256("account:PriceUpdateV2")[0..8]
+const PYTH_PRICE_UPDATE_DISCRIMINATOR: [u8; 8] = [34, 241, 35, 99, 157, 126, 244, 205];
+/// `verification_level` follows the discriminator and write authority.
+const PYTH_VERIFICATION_LEVEL: usize = 8 + 32;
+/// `VerificationLevel::Full`; partially verified updates are refused.
+const PYTH_FULLY_VERIFIED: u8 = 1;
+/// `PriceFeedMessage` after a one-byte `Full` verification level:
+/// feed_id(32) + price(8) + conf(8) + exponent(4) + publish_time(8).
+const PYTH_MESSAGE: usize = PYTH_VERIFICATION_LEVEL + 1;
+const PYTH_MESSAGE_LEN: usize = 32 + 8 + 8 + 4 + 8;
+
+/// A price read from an oracle account: `price * 10^expo`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct OraclePrice {
+    pub price: i64,
+    pub conf: u64,
+    pub expo: i32,
+    pub publish_time: i64,
+}
+
+impl OraclePrice {
+    /// Read a fully verified Pyth `PriceUpdateV2` account for `feed_id`.
+    pub fn load(account: &AccountInfo, feed_id: &[u8; 32]) -> Result<Self, PoseidonError> {
+        if *account.owner != PYTH_RECEIVER_PROGRAM_ID {
+            msg!("Oracle {} is not owned by the Pyth receiver", account.key);
+            return Err(PoseidonError::InvalidOracleAccount);
+        }
+        let data = account
+            .try_borrow_data()
+            .map_err(|_| PoseidonError::InvalidOracleAccount)?;
+        if data.len() < PYTH_MESSAGE + PYTH_MESSAGE_LEN
+            || data[..8] != PYTH_PRICE_UPDATE_DISCRIMINATOR
+            || data[PYTH_VERIFICATION_LEVEL] != PYTH_FULLY_VERIFIED
+        {
+            return Err(PoseidonError::InvalidOracleAccount);
+        }
+
+        if data[PYTH_MESSAGE..PYTH_MESSAGE + 32] != feed_id[..] {
+            msg!("Oracle {} does not quote the expected feed", account.key);
+            return Err(PoseidonError::OracleFeedMismatch);
+        }
+
+        let message = &data[PYTH_MESSAGE + 32..];
+        let price = Self {
+            price: i64::from_le_bytes(message[..8].try_into().unwrap()),
+            conf: u64::from_le_bytes(message[8..16].try_into().unwrap()),
+            expo: i32::from_le_bytes(message[16..20].try_into().unwrap()),
+            publish_time: i64::from_le_bytes(message[20..28].try_into().unwrap()),
+        };
+        if price.price <= 0 {
+            return Err(PoseidonError::InvalidOracleAccount);
+        }
+        Ok(price)
+    }
+
+    /// Refuse prices published more than `max_age` seconds before `now`.
+    pub fn check_fresh(&self, now: i64, max_age: u32) -> Result<(), PoseidonError> {
+        if now.saturating_sub(self.publish_time) > i64::from(max_age) {
+            msg!("Oracle price published at {} is older than {}s", self.publish_time, max_age);
+            return Err(PoseidonError::OraclePriceStale);
+        }
+        Ok(())
+    }
+
+    /// This price in token B base units per token A base unit.
+    pub fn base_price(&self, decimals_a: u8, decimals_b: u8) -> f64 {
+        let exp = self.expo + i32::from(decimals_b) - i32::from(decimals_a);
+        self.price as f64 * 10f64.powi(exp)
+    }
+
+    /// Q64.64 square root of this price in token B base units per token A
+    /// base unit, the form CLMM amounts are computed from.
+    pub fn sqrt_price_x64(&self, decimals_a: u8, decimals_b: u8) -> Result<u128, PoseidonError> {
+        let exp = self.expo + i32::from(decimals_b) - i32::from(decimals_a);
+        math::sqrt_price_x64(self.price.unsigned_abs(), exp).ok_or(PoseidonError::ValueOverflow)
+    }
+
+    /// Distance of `pool_price` (token B base units per token A base unit)
+    /// from this price, in bps of this price.
+    pub fn deviation_bps(&self, pool_price: f64, decimals_a: u8, decimals_b: u8) -> u64 {
+        let oracle_price = self.base_price(decimals_a, decimals_b);
+        ((pool_price - oracle_price).abs() / oracle_price * 10_000.0) as u64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FEED: [u8; 32] = [7; 32];
+    const FULL: u8 = PYTH_FULLY_VERIFIED;
+
+    /// A `PriceUpdateV2` account body published at 1000, without the
+    /// trailing EMA fields.
+    fn price_update(verification: u8, feed_id: [u8; 32], price: i64) -> Vec<u8> {
+        let mut data = PYTH_PRICE_UPDATE_DISCRIMINATOR.to_vec();
+        data.extend_from_slice(&[0; 32]); // write_authority
+        data.push(verification);
+        data.extend_from_slice(&feed_id);
+        data.extend_from_slice(&price.to_le_bytes());
+        data.extend_from_slice(&25u64.to_le_bytes()); // conf
+        data.extend_from_slice(&(-2i32).to_le_bytes()); // expo
+        data.extend_from_slice(&1_000i64.to_le_bytes()); // publish_time
+        data
+    }
+
+    fn load(mut data: Vec<u8>, owner: &Pubkey) -> Result<OraclePrice, PoseidonError> {
+        let key = Pubkey::new_unique();
+        let mut lamports = 0;
+        let account = AccountInfo::new(&key, false, false, &mut lamports, &mut data, owner, false, 0);
+        OraclePrice::load(&account, &FEED)
+    }
+
+    /// 2.00 token B per token A, published at 1000.
+    fn two() -> OraclePrice {
+        OraclePrice { price: 200, conf: 25, expo: -2, publish_time: 1_000 }
+    }
+
+    #[test]
+    fn load_reads_fully_verified_update() {
+        let price = load(price_update(FULL, FEED, 200), &PYTH_RECEIVER_PROGRAM_ID);
+        assert_eq!(price, Ok(two()));
+    }
+
+    #[test]
+    fn load_rejects_other_feeds() {
+        let price = load(price_update(FULL, [8; 32], 200), &PYTH_RECEIVER_PROGRAM_ID);
+        assert_eq!(price, Err(PoseidonError::OracleFeedMismatch));
+    }
+
+    #[test]
+    fn load_rejects_unverified_or_foreign_accounts() {
+        let partial = load(price_update(0, FEED, 200), &PYTH_RECEIVER_PROGRAM_ID);
+        assert_eq!(partial, Err(PoseidonError::InvalidOracleAccount));
+        let foreign = load(price_update(FULL, FEED, 200), &Pubkey::new_unique());
+        assert_eq!(foreign, Err(PoseidonError::InvalidOracleAccount));
+        let negative = load(price_update(FULL, FEED, -200), &PYTH_RECEIVER_PROGRAM_ID);
+        assert_eq!(negative, Err(PoseidonError::InvalidOracleAccount));
+    }
+
+    #[test]
+    fn check_fresh_allows_max_age() {
+        assert_eq!(two().check_fresh(1_060, 60), Ok(()));
+        assert_eq!(two().check_fresh(1_061, 60), Err(PoseidonError::OraclePriceStale));
+    }
+
+    #[test]
+    fn deviation_bps_scales_by_decimals() {
+        assert_eq!(two().deviation_bps(2.0, 6, 6), 0);
+        assert_eq!(two().deviation_bps(2.25, 6, 6), 1_250);
+        assert_eq!(two().deviation_bps(1.5, 6, 6), 2_500);
+        // 6-decimal token A against 9-decimal token B: 2000 base units each
+        assert_eq!(two().deviation_bps(2_000.0, 6, 9), 0);
+        assert_eq!(two().deviation_bps(2_500.0, 6, 9), 2_500);
+    }
+
+    #[test]
+    fn sqrt_price_x64_scales_by_decimals() {
+        let four = OraclePrice { price: 400, ..two() };
+        assert_eq!(four.sqrt_price_x64(6, 6), Ok(2 << 64));
+        // 400 token B base units per token A base unit
+        assert_eq!(four.sqrt_price_x64(6, 8), Ok(20 << 64));
+    }
+}
@@ -0,0 +1,34 @@
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::pubkey::Pubkey;
+
+use crate::{AccountState, ORACLE_FEED_SEED};
+
+/// The Pyth feed quoting a pool's token A in token B, set by the admin and
+/// stored at ["oracle_feed", mint_a, mint_b]. Oracle accounts quoting any
+/// other feed are refused for pools of this pair.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, Default, PartialEq)]
+pub struct OracleFeed {
+    pub version: u8,        // 1
+    pub bump: u8,           // 1
+    pub mint_a: Pubkey,     // 32
+    pub mint_b: Pubkey,     // 32
+    pub feed_id: [u8; 32],  // 32
+    pub updated_at: i64,    // 8
+}
+
+impl AccountState for OracleFeed {
+    // sha256("account:OracleFeed")[0..8]
+    const DISCRIMINATOR: [u8; 8] = [102, 96, 193, 204, 107, 0, 111, 27];
+    const LEN: usize = 8 + 1 + 1 + 32 + 32 + 32 + 8; // 114
+}
+
+impl OracleFeed {
+    pub const VERSION: u8 = 1;
+
+    pub fn find_address(mint_a: &Pubkey, mint_b: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(
+            &[ORACLE_FEED_SEED, mint_a.as_ref(), mint_b.as_ref()],
+            program_id,
+        )
+    }
+}
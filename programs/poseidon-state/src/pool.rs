@@ -0,0 +1,249 @@
+use solana_program::{account_info::AccountInfo, msg, pubkey::Pubkey};
+
+use crate::{
+    math::{mul_div, sqrt_price_at_tick, MAX_TICK, Q64},
+    position::NFT_POSITION_SEED,
+    PoseidonError, ProgramConfig, Protocol,
+};
+
+// Whirlpool: sqrt_price (Q64.64), current tick and the two mints.
+const WHIRLPOOL_SQRT_PRICE: usize = 65;
+const WHIRLPOOL_TICK_CURRENT: usize = 81;
+const WHIRLPOOL_MINT_A: usize = 101;
+const WHIRLPOOL_MINT_B: usize = 181;
+// Raydium CLMM PoolState: token_mint_0/1, sqrt_price_x64 and current tick.
+const RAYDIUM_MINT_0: usize = 73;
+const RAYDIUM_MINT_1: usize = 105;
+const RAYDIUM_SQRT_PRICE: usize = 253;
+const RAYDIUM_TICK_CURRENT: usize = 269;
+// Meteora DLMM LbPair: active_id, bin_step and token_x/y mints.
+const DLMM_ACTIVE_ID: usize = 76;
+const DLMM_BIN_STEP: usize = 80;
+const DLMM_MINT_X: usize = 88;
+const DLMM_MINT_Y: usize = 120;
+// Pool address inside the position account.
+const WHIRLPOOL_POSITION_POOL: usize = 8;
+const RAYDIUM_POSITION_POOL: usize = 41;
+const DLMM_POSITION_POOL: usize = 8;
+// Whirlpool Position: liquidity, then the tick range.
+const WHIRLPOOL_POSITION_LIQUIDITY: usize = 72;
+const WHIRLPOOL_POSITION_TICK_LOWER: usize = 88;
+// Raydium PersonalPositionState: the tick range, then liquidity.
+const RAYDIUM_POSITION_TICK_LOWER: usize = 73;
+const RAYDIUM_POSITION_LIQUIDITY: usize = 81;
+// Meteora DLMM PositionV2: per-bin liquidity shares, then the bin range after
+// the reward and fee infos.
+const DLMM_POSITION_SHARES: usize = 72;
+const DLMM_POSITION_BINS: usize = 70;
+const DLMM_POSITION_LOWER_BIN: usize = 7912;
+
+/// Liquidity and range of a DEX position account.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PositionRange {
+    pub pool: Pubkey,
+    pub liquidity: u128,
+    pub tick_lower: i32,
+    pub tick_upper: i32,
+}
+
+impl PositionRange {
+    /// Read a Whirlpool `Position`, Raydium `PersonalPositionState` or DLMM
+    /// `PositionV2` account. On Meteora the ticks are bin ids and liquidity
+    /// is the sum of the per-bin shares.
+    pub fn load(protocol: Protocol, dex_position: &AccountInfo) -> Result<Self, PoseidonError> {
+        let (pool, ticks, liquidity) = match protocol {
+            Protocol::OrcaWhirlpool => (
+                WHIRLPOOL_POSITION_POOL,
+                WHIRLPOOL_POSITION_TICK_LOWER,
+                WHIRLPOOL_POSITION_LIQUIDITY..WHIRLPOOL_POSITION_LIQUIDITY + 16,
+            ),
+            Protocol::RaydiumClmm => (
+                RAYDIUM_POSITION_POOL,
+                RAYDIUM_POSITION_TICK_LOWER,
+                RAYDIUM_POSITION_LIQUIDITY..RAYDIUM_POSITION_LIQUIDITY + 16,
+            ),
+            Protocol::MeteoraDlmm => (
+                DLMM_POSITION_POOL,
+                DLMM_POSITION_LOWER_BIN,
+                DLMM_POSITION_SHARES..DLMM_POSITION_SHARES + 16 * DLMM_POSITION_BINS,
+            ),
+            Protocol::Unknown => return Err(PoseidonError::InvalidProtocol),
+        };
+        let data = dex_position
+            .try_borrow_data()
+            .map_err(|_| PoseidonError::InvalidPositionAccount)?;
+        if data.len() < (ticks + 8).max(liquidity.end) {
+            return Err(PoseidonError::InvalidPositionAccount);
+        }
+        Ok(Self {
+            pool: Pubkey::try_from(&data[pool..pool + 32]).unwrap(),
+            liquidity: data[liquidity]
+                .chunks_exact(16)
+                .map(|share| u128::from_le_bytes(share.try_into().unwrap()))
+                .fold(0, u128::saturating_add),
+            tick_lower: i32::from_le_bytes(data[ticks..ticks + 4].try_into().unwrap()),
+            tick_upper: i32::from_le_bytes(data[ticks + 4..ticks + 8].try_into().unwrap()),
+        })
+    }
+
+    /// Token A and token B, in base units, the position's liquidity is worth
+    /// at `sqrt_price`, the Q64.64 square root of the price in token B base
+    /// units per token A base unit. Tick ranges only; DLMM bins are priced
+    /// per bin. Amounts past `u64::MAX` saturate.
+    pub fn token_amounts(&self, sqrt_price: u128) -> (u64, u64) {
+        let sqrt_lower = sqrt_price_at_tick(self.tick_lower.clamp(-MAX_TICK, MAX_TICK));
+        let sqrt_upper = sqrt_price_at_tick(self.tick_upper.clamp(-MAX_TICK, MAX_TICK));
+        let sqrt_price = sqrt_price.max(sqrt_lower).min(sqrt_upper);
+        // a = L * (upper - price) / (upper * price), b = L * (price - lower)
+        let amount_a = mul_div(self.liquidity, sqrt_upper.saturating_sub(sqrt_price), sqrt_upper)
+            .and_then(|a| mul_div(a, Q64, sqrt_price));
+        let amount_b = mul_div(self.liquidity, sqrt_price.saturating_sub(sqrt_lower), Q64);
+        let saturate = |amount: Option<u128>| {
+            amount.map_or(u64::MAX, |amount| u64::try_from(amount).unwrap_or(u64::MAX))
+        };
+        (saturate(amount_a), saturate(amount_b))
+    }
+}
+
+/// Current tick of a pool; the active bin id on Meteora.
+pub fn pool_current_tick(protocol: Protocol, pool: &AccountInfo) -> Result<i32, PoseidonError> {
+    let offset = match protocol {
+        Protocol::OrcaWhirlpool => WHIRLPOOL_TICK_CURRENT,
+        Protocol::RaydiumClmm => RAYDIUM_TICK_CURRENT,
+        Protocol::MeteoraDlmm => DLMM_ACTIVE_ID,
+        Protocol::Unknown => return Err(PoseidonError::InvalidProtocol),
+    };
+    let data = pool.try_borrow_data().map_err(|_| PoseidonError::InvalidPoolAccount)?;
+    data.get(offset..offset + 4)
+        .map(|b| i32::from_le_bytes(b.try_into().unwrap()))
+        .ok_or(PoseidonError::InvalidPoolAccount)
+}
+
+/// Pool the position belongs to. `dex_position` is the position PDA for NFT
+/// protocols and the position account itself on Meteora; `position` is the
+/// config's position mint.
+pub fn position_pool(
+    program_config: &ProgramConfig,
+    protocol: Protocol,
+    position: &Pubkey,
+    dex_position: &AccountInfo,
+) -> Result<Pubkey, PoseidonError> {
+    if !program_config.accepts_dex(protocol, dex_position.owner) {
+        msg!(
+            "Position {} is owned by {}, not an accepted {:?} program",
+            dex_position.key,
+            dex_position.owner,
+            protocol
+        );
+        return Err(PoseidonError::DexProgramNotAccepted);
+    }
+    let expected = if protocol.is_nft() {
+        let seeds: &[&[u8]] = &[NFT_POSITION_SEED, position.as_ref()];
+        Pubkey::find_program_address(seeds, dex_position.owner).0
+    } else {
+        *position
+    };
+    if *dex_position.key != expected {
+        return Err(PoseidonError::InvalidPositionAccount);
+    }
+
+    let offset = match protocol {
+        Protocol::OrcaWhirlpool => WHIRLPOOL_POSITION_POOL,
+        Protocol::RaydiumClmm => RAYDIUM_POSITION_POOL,
+        Protocol::MeteoraDlmm => DLMM_POSITION_POOL,
+        Protocol::Unknown => return Err(PoseidonError::InvalidProtocol),
+    };
+    let data = dex_position
+        .try_borrow_data()
+        .map_err(|_| PoseidonError::InvalidPositionAccount)?;
+    data.get(offset..offset + 32)
+        .map(|b| Pubkey::try_from(b).unwrap())
+        .ok_or(PoseidonError::InvalidPositionAccount)
+}
+
+/// Current pool price and its two mints. The price is token B (Y) base units
+/// per token A (X) base unit.
+pub fn pool_price(
+    protocol: Protocol,
+    pool: &AccountInfo,
+) -> Result<(f64, Pubkey, Pubkey), PoseidonError> {
+    let data = pool.try_borrow_data().map_err(|_| PoseidonError::InvalidPoolAccount)?;
+    let pubkey_at = |offset: usize| {
+        data.get(offset..offset + 32)
+            .map(|b| Pubkey::try_from(b).unwrap())
+            .ok_or(PoseidonError::InvalidPoolAccount)
+    };
+    let sqrt_price_at = |offset: usize| {
+        data.get(offset..offset + 16)
+            .map(|b| u128::from_le_bytes(b.try_into().unwrap()) as f64 / 2f64.powi(64))
+            .ok_or(PoseidonError::InvalidPoolAccount)
+    };
+
+    match protocol {
+        Protocol::OrcaWhirlpool => {
+            let sqrt_price = sqrt_price_at(WHIRLPOOL_SQRT_PRICE)?;
+            let (mint_a, mint_b) = (pubkey_at(WHIRLPOOL_MINT_A)?, pubkey_at(WHIRLPOOL_MINT_B)?);
+            Ok((sqrt_price * sqrt_price, mint_a, mint_b))
+        }
+        Protocol::RaydiumClmm => {
+            let sqrt_price = sqrt_price_at(RAYDIUM_SQRT_PRICE)?;
+            let (mint_a, mint_b) = (pubkey_at(RAYDIUM_MINT_0)?, pubkey_at(RAYDIUM_MINT_1)?);
+            Ok((sqrt_price * sqrt_price, mint_a, mint_b))
+        }
+        Protocol::MeteoraDlmm => {
+            let active_id = data
+                .get(DLMM_ACTIVE_ID..DLMM_ACTIVE_ID + 4)
+                .map(|b| i32::from_le_bytes(b.try_into().unwrap()))
+                .ok_or(PoseidonError::InvalidPoolAccount)?;
+            let bin_step = data
+                .get(DLMM_BIN_STEP..DLMM_BIN_STEP + 2)
+                .map(|b| u16::from_le_bytes(b.try_into().unwrap()))
+                .ok_or(PoseidonError::InvalidPoolAccount)?;
+            let price = (1.0 + f64::from(bin_step) / 10_000.0).powi(active_id);
+            Ok((price, pubkey_at(DLMM_MINT_X)?, pubkey_at(DLMM_MINT_Y)?))
+        }
+        Protocol::Unknown => Err(PoseidonError::InvalidProtocol),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(tick_lower: i32, tick_upper: i32) -> PositionRange {
+        PositionRange { liquidity: 1_000_000_000, tick_lower, tick_upper, ..PositionRange::default() }
+    }
+
+    #[test]
+    fn token_amounts_split_in_range() {
+        // Symmetric around price 1: equal amounts of each token
+        let (amount_a, amount_b) = range(-100, 100).token_amounts(Q64);
+        assert!(amount_a > 0 && amount_a.abs_diff(amount_b) <= 1, "{amount_a} / {amount_b}");
+        // Closer to the upper edge: mostly token B
+        let (amount_a, amount_b) = range(-100, 100).token_amounts(sqrt_price_at_tick(90));
+        assert!(amount_b > 10 * amount_a);
+    }
+
+    #[test]
+    fn token_amounts_are_one_sided_out_of_range() {
+        let (amount_a, amount_b) = range(-100, 100).token_amounts(sqrt_price_at_tick(-7_000));
+        assert!(amount_a > 0);
+        assert_eq!(amount_b, 0);
+        let (amount_a, amount_b) = range(-100, 100).token_amounts(sqrt_price_at_tick(7_000));
+        assert_eq!(amount_a, 0);
+        assert!(amount_b > 0);
+        // The price past an edge doesn't change the amount
+        assert_eq!(range(-100, 100).token_amounts(sqrt_price_at_tick(14_000)), (amount_a, amount_b));
+    }
+
+    #[test]
+    fn token_amounts_match_the_range_formulas() {
+        // L = 1e9 over [-100, 100] at price 1: L * (1 - 1.0001^-50) each side
+        let (amount_a, amount_b) = range(-100, 100).token_amounts(Q64);
+        assert!(amount_a.abs_diff(4_987_272) <= 1, "{amount_a}");
+        assert!(amount_b.abs_diff(4_987_272) <= 1, "{amount_b}");
+        // Inverted or out-of-bounds ticks don't panic
+        assert_eq!(range(100, -100).token_amounts(Q64).1, 0);
+        assert_eq!(range(i32::MIN, i32::MAX).token_amounts(Q64).0, 999_999_999);
+    }
+}
@@ -0,0 +1,224 @@
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::{account_info::AccountInfo, msg, pubkey, pubkey::Pubkey};
+
+use crate::{PoseidonError, ProgramConfig};
+
+pub const TOKEN_PROGRAM_ID: Pubkey = pubkey!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
+pub const TOKEN_2022_PROGRAM_ID: Pubkey = pubkey!("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb");
+pub const ORCA_WHIRLPOOL_PROGRAM_ID: Pubkey = pubkey!("whirLbMiicVdio4qvUfM5KAg6Ct8VwpYzGff3uctyCc");
+pub const RAYDIUM_CLMM_PROGRAM_ID: Pubkey = pubkey!("CAMMCzo5YL8w4VFF8KVHrK22GGUsp5VTaW7grrKgrWqK");
+pub const ASSOCIATED_TOKEN_PROGRAM_ID: Pubkey =
+    pubkey!("ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL");
+pub const METEORA_DLMM_PROGRAM_ID: Pubkey = pubkey!("LBUZKhRxPF3XUpBCjp4YzTKgLccjZhTSDM9YuVaPwxo");
+
+/// Seed of the per-NFT position PDA in both Whirlpool and Raydium CLMM.
+pub(crate) const NFT_POSITION_SEED: &[u8] = b"position";
+
+/// DEX a position lives on. `Unknown` is only seen on configs created before
+/// the protocol was recorded.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Protocol {
+    #[default]
+    Unknown = 0,
+    OrcaWhirlpool = 1,
+    RaydiumClmm = 2,
+    MeteoraDlmm = 3,
+}
+
+impl TryFrom<u8> for Protocol {
+    type Error = PoseidonError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            1 => Ok(Self::OrcaWhirlpool),
+            2 => Ok(Self::RaydiumClmm),
+            3 => Ok(Self::MeteoraDlmm),
+            _ => Err(PoseidonError::InvalidProtocol),
+        }
+    }
+}
+
+impl Protocol {
+    /// Orca and Raydium positions are NFTs backed by a position PDA; Meteora
+    /// positions are plain program accounts.
+    pub fn is_nft(&self) -> bool {
+        matches!(self, Self::OrcaWhirlpool | Self::RaydiumClmm)
+    }
+}
+
+/// An accepted DEX deployment. Forks sharing a protocol's layout can be
+/// accepted under the same `protocol`.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DexProgram {
+    pub protocol: Protocol,
+    pub program_id: Pubkey,
+}
+
+impl DexProgram {
+    pub const LEN: usize = 1 + 32;
+
+    /// Mainnet deployments accepted before the admin edits the list.
+    pub fn defaults() -> Vec<Self> {
+        vec![
+            Self { protocol: Protocol::OrcaWhirlpool, program_id: ORCA_WHIRLPOOL_PROGRAM_ID },
+            Self { protocol: Protocol::RaydiumClmm, program_id: RAYDIUM_CLMM_PROGRAM_ID },
+            Self { protocol: Protocol::MeteoraDlmm, program_id: METEORA_DLMM_PROGRAM_ID },
+        ]
+    }
+}
+
+/// Check that the position belongs to an accepted deployment of `protocol`.
+///
+/// For NFT protocols `dex_position` must be the DEX's position PDA for
+/// `position`; for Meteora `position` is itself the DLMM position account.
+pub fn verify_dex_position(
+    program_config: &ProgramConfig,
+    protocol: Protocol,
+    position: &AccountInfo,
+    dex_position: Option<&AccountInfo>,
+) -> Result<(), PoseidonError> {
+    if protocol.is_nft() {
+        let dex_position = dex_position.ok_or_else(|| {
+            msg!("{:?} positions require the DEX position account", protocol);
+            PoseidonError::InvalidPositionAccount
+        })?;
+        let dex_program = dex_position.owner;
+        if !program_config.accepts_dex(protocol, dex_program) {
+            msg!("{} is not an accepted {:?} program", dex_program, protocol);
+            return Err(PoseidonError::DexProgramNotAccepted);
+        }
+        let (expected, _) =
+            Pubkey::find_program_address(&[NFT_POSITION_SEED, position.key.as_ref()], dex_program);
+        if *dex_position.key != expected {
+            msg!("{} is not the position account for {}", dex_position.key, position.key);
+            return Err(PoseidonError::InvalidPositionAccount);
+        }
+        Ok(())
+    } else if protocol == Protocol::MeteoraDlmm {
+        if !program_config.accepts_dex(protocol, position.owner) {
+            msg!("{} is not an accepted {:?} program", position.owner, protocol);
+            return Err(PoseidonError::DexProgramNotAccepted);
+        }
+        Ok(())
+    } else {
+        Err(PoseidonError::InvalidProtocol)
+    }
+}
+
+// SPL token account layout: mint(32) + owner(32) + amount(8) + ...
+pub(crate) const TOKEN_ACCOUNT_MIN_LEN: usize = 165;
+// SPL mint layout: COption<authority>(36) + supply(8) + ...
+const MINT_SUPPLY: core::ops::Range<usize> = 36..44;
+/// `decimals` in an SPL mint.
+const MINT_DECIMALS: usize = 44;
+// DLMM position layout: discriminator(8) + lb_pair(32) + owner(32) + ...
+const DLMM_POSITION_OWNER: core::ops::Range<usize> = 40..72;
+
+/// Check that `owner` holds the position identified by `position`. Run
+/// `verify_dex_position` first so the account is known to belong to an
+/// accepted DEX.
+pub fn verify_position_owner(
+    owner: &Pubkey,
+    protocol: Protocol,
+    position: &AccountInfo,
+    token_account: Option<&AccountInfo>,
+) -> Result<(), PoseidonError> {
+    let holder = position_holder(protocol, position, token_account)?;
+    if holder != *owner {
+        msg!("Position {} is held by {}, not {}", position.key, holder, owner);
+        return Err(PoseidonError::PositionNotOwned);
+    }
+    Ok(())
+}
+
+/// Current holder of the position identified by `position`.
+///
+/// Orca and Raydium positions are NFTs: `position` is the mint and
+/// `token_account` must hold exactly one of it. Meteora positions are
+/// program accounts: `position` is the DLMM position itself and records its
+/// owner.
+pub fn position_holder(
+    protocol: Protocol,
+    position: &AccountInfo,
+    token_account: Option<&AccountInfo>,
+) -> Result<Pubkey, PoseidonError> {
+    if protocol.is_nft() {
+        if !is_token_program(position.owner) {
+            msg!("Position {} is not an NFT mint", position.key);
+            return Err(PoseidonError::InvalidPositionAccount);
+        }
+        let token_account = token_account.ok_or_else(|| {
+            msg!("Position {} is an NFT; its token account is required", position.key);
+            PoseidonError::InvalidPositionAccount
+        })?;
+        nft_holder(position.key, token_account)
+    } else if protocol == Protocol::MeteoraDlmm {
+        let data = position
+            .try_borrow_data()
+            .map_err(|_| PoseidonError::InvalidPositionAccount)?;
+        if data.len() < DLMM_POSITION_OWNER.end {
+            return Err(PoseidonError::InvalidPositionAccount);
+        }
+        Ok(Pubkey::try_from(&data[DLMM_POSITION_OWNER]).unwrap())
+    } else {
+        Err(PoseidonError::InvalidProtocol)
+    }
+}
+
+fn nft_holder(mint: &Pubkey, token_account: &AccountInfo) -> Result<Pubkey, PoseidonError> {
+    if !is_token_program(token_account.owner) {
+        return Err(PoseidonError::InvalidPositionAccount);
+    }
+    let data = token_account
+        .try_borrow_data()
+        .map_err(|_| PoseidonError::InvalidPositionAccount)?;
+    if data.len() < TOKEN_ACCOUNT_MIN_LEN || data[..32] != mint.as_ref()[..] {
+        msg!("Token account {} is not for position {}", token_account.key, mint);
+        return Err(PoseidonError::InvalidPositionAccount);
+    }
+    let amount = u64::from_le_bytes(data[64..72].try_into().unwrap());
+    if amount != 1 {
+        msg!("Token account {} does not hold position {}", token_account.key, mint);
+        return Err(PoseidonError::PositionNotOwned);
+    }
+    Ok(Pubkey::try_from(&data[32..64]).unwrap())
+}
+
+/// Whether the position no longer exists on its DEX: the account has been
+/// closed, or it's an NFT mint whose only token was burned.
+pub fn is_position_closed(position: &AccountInfo) -> bool {
+    if position.lamports() == 0 || position.data_is_empty() {
+        return true;
+    }
+    if is_token_program(position.owner) {
+        if let Ok(data) = position.try_borrow_data() {
+            if data.len() >= MINT_SUPPLY.end {
+                return data[MINT_SUPPLY] == [0u8; 8];
+            }
+        }
+    }
+    false
+}
+
+/// Decimals of an SPL Token or Token-2022 mint.
+pub fn mint_decimals(mint: &AccountInfo) -> Result<u8, PoseidonError> {
+    if !is_token_program(mint.owner) {
+        return Err(PoseidonError::InvalidTokenAccount);
+    }
+    let data = mint.try_borrow_data().map_err(|_| PoseidonError::InvalidTokenAccount)?;
+    data.get(MINT_DECIMALS).copied().ok_or(PoseidonError::InvalidTokenAccount)
+}
+
+/// Associated token account of `wallet` for `mint`, under the mint's token program.
+pub fn associated_token_address(wallet: &Pubkey, mint: &AccountInfo) -> Pubkey {
+    Pubkey::find_program_address(
+        &[wallet.as_ref(), mint.owner.as_ref(), mint.key.as_ref()],
+        &ASSOCIATED_TOKEN_PROGRAM_ID,
+    )
+    .0
+}
+
+pub(crate) fn is_token_program(program_id: &Pubkey) -> bool {
+    *program_id == TOKEN_PROGRAM_ID || *program_id == TOKEN_2022_PROGRAM_ID
+}
@@ -0,0 +1,96 @@
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::pubkey::Pubkey;
+
+use crate::{AccountState, PoseidonError, RebalanceConfig, Strategy, USER_PROFILE_SEED};
+
+/// Per-owner defaults, stored at ["user_profile", owner]. Position configs
+/// inherit the fields flagged in their `inherit` mask.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, Default, PartialEq)]
+pub struct UserProfile {
+    pub version: u8,                    // 1
+    pub bump: u8,                       // 1
+    pub owner: Pubkey,                  // 32
+    pub max_slippage_bps: u16,          // 2
+    pub min_yield_improvement_bps: u16, // 2
+    /// Where collected fees go; the default pubkey means the owner's wallet.
+    pub fee_destination: Pubkey,        // 32
+    pub created_at: i64,                // 8
+    pub updated_at: i64,                // 8
+    // v2
+    pub strategy: Strategy,             // 7
+}
+
+impl AccountState for UserProfile {
+    // sha256("account:UserProfile")[0..8]
+    const DISCRIMINATOR: [u8; 8] = [32, 37, 119, 205, 179, 180, 13, 194];
+    const LEN: usize = 8 + 1 + 1 + 32 + 2 + 2 + 32 + 8 + 8 + Strategy::LEN; // 101
+}
+
+impl UserProfile {
+    pub const VERSION: u8 = 2;
+    /// Size of the version 1 layout, before `strategy`.
+    pub const V1_LEN: usize = 94;
+
+    pub fn find_address(owner: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[USER_PROFILE_SEED, owner.as_ref()], program_id)
+    }
+}
+
+/// Settings the agent acts on once a config's inherited fields are filled in
+/// from the owner's profile.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct EffectiveSettings {
+    pub max_slippage_bps: u16,
+    pub min_yield_improvement_bps: u16,
+    pub fee_destination: Pubkey,
+    pub strategy: Strategy,
+}
+
+impl RebalanceConfig {
+    pub const INHERIT_MAX_SLIPPAGE: u8 = 1 << 0;
+    pub const INHERIT_MIN_YIELD: u8 = 1 << 1;
+    pub const INHERIT_FEE_DESTINATION: u8 = 1 << 2;
+    pub const INHERIT_STRATEGY: u8 = 1 << 3;
+    pub const INHERIT_ALL: u8 = Self::INHERIT_MAX_SLIPPAGE
+        | Self::INHERIT_MIN_YIELD
+        | Self::INHERIT_FEE_DESTINATION
+        | Self::INHERIT_STRATEGY;
+
+    pub fn inherits(&self, field: u8) -> bool {
+        self.inherit & field != 0
+    }
+
+    /// Combine this config with the owner's profile. The profile is only
+    /// required when at least one field is inherited.
+    pub fn effective(
+        &self,
+        profile: Option<&UserProfile>,
+    ) -> Result<EffectiveSettings, PoseidonError> {
+        let profile = match profile {
+            Some(profile) if profile.owner != self.owner => {
+                return Err(PoseidonError::InvalidUserProfile)
+            }
+            Some(profile) => Some(profile),
+            None if self.inherit != 0 => return Err(PoseidonError::UserProfileRequired),
+            None => None,
+        };
+        let pick = |field: u8| profile.filter(|_| self.inherits(field));
+
+        let fee_destination = match pick(Self::INHERIT_FEE_DESTINATION) {
+            Some(p) => p.fee_destination,
+            None => self.fee_destination,
+        };
+        Ok(EffectiveSettings {
+            max_slippage_bps: pick(Self::INHERIT_MAX_SLIPPAGE)
+                .map_or(self.max_slippage_bps, |p| p.max_slippage_bps),
+            min_yield_improvement_bps: pick(Self::INHERIT_MIN_YIELD)
+                .map_or(self.min_yield_improvement_bps, |p| p.min_yield_improvement_bps),
+            fee_destination: if fee_destination == Pubkey::default() {
+                self.owner
+            } else {
+                fee_destination
+            },
+            strategy: pick(Self::INHERIT_STRATEGY).map_or(self.strategy, |p| p.strategy),
+        })
+    }
+}
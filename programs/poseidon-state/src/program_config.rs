@@ -0,0 +1,190 @@
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::pubkey::Pubkey;
+
+use crate::{AccountState, ConfigUpdate, DexProgram, PoseidonError, Protocol, PROGRAM_CONFIG_SEED};
+
+/// Singleton protocol settings, stored at ["program_config"].
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, Default, PartialEq)]
+pub struct ProgramConfig {
+    pub version: u8,              // 1
+    pub bump: u8,                 // 1
+    pub admin: Pubkey,            // 32
+    pub treasury: Pubkey,         // 32
+    pub deposit_fee_bps: u16,     // 2
+    pub performance_fee_bps: u16, // 2
+    pub paused: bool,             // 1
+    pub agents: Vec<Pubkey>,      // 4 + 32 * MAX_AGENTS
+    // v2
+    pub slippage_cap_bps: u16,    // 2
+    pub min_yield_cap_bps: u16,   // 2
+    // v3
+    pub dex_programs: Vec<DexProgram>, // 4 + 33 * MAX_DEX_PROGRAMS
+    // v4
+    /// Oldest oracle price, in seconds, agent actions may rely on.
+    pub oracle_max_age_seconds: u32, // 4
+    /// Widest gap between pool and oracle price an agent may act at.
+    pub oracle_max_deviation_bps: u16, // 2
+}
+
+impl AccountState for ProgramConfig {
+    // sha256("account:ProgramConfig")[0..8]
+    const DISCRIMINATOR: [u8; 8] = [196, 210, 90, 231, 144, 149, 140, 63];
+    const LEN: usize = Self::V3_LEN + 4 + 2; // 617
+}
+
+impl ProgramConfig {
+    pub const VERSION: u8 = 4;
+    pub const V1_LEN: usize = 8 + 1 + 1 + 32 + 32 + 2 + 2 + 1 + 4 + 32 * Self::MAX_AGENTS; // 339
+    pub const V2_LEN: usize = Self::V1_LEN + 2 + 2; // 343
+    pub const V3_LEN: usize = Self::V2_LEN + 4 + DexProgram::LEN * Self::MAX_DEX_PROGRAMS; // 611
+    /// Offset of `paused`; it precedes `agents`, so it is fixed across versions.
+    pub const PAUSED_OFFSET: usize = 8 + 1 + 1 + 32 + 32 + 2 + 2;
+    pub const MAX_AGENTS: usize = 8;
+    pub const MAX_DEX_PROGRAMS: usize = 8;
+    pub const MAX_FEE_BPS: u16 = 10_000;
+    pub const MAX_BPS: u16 = 10_000;
+    pub const DEFAULT_SLIPPAGE_CAP_BPS: u16 = 500;
+    pub const DEFAULT_MIN_YIELD_CAP_BPS: u16 = 1_000;
+    pub const DEFAULT_ORACLE_MAX_AGE_SECONDS: u32 = 60;
+    pub const DEFAULT_ORACLE_MAX_DEVIATION_BPS: u16 = 200;
+    /// Longest `oracle_max_age_seconds` the admin may set.
+    pub const MAX_ORACLE_AGE_SECONDS: u32 = 3_600;
+
+    pub fn find_address(program_id: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[PROGRAM_CONFIG_SEED], program_id)
+    }
+
+    pub fn is_agent(&self, key: &Pubkey) -> bool {
+        self.agents.contains(key)
+    }
+
+    pub fn needs_migration(&self) -> bool {
+        self.version < Self::VERSION
+    }
+
+    /// Fill fields introduced after `self.version` with their defaults and
+    /// bump to the current version. The account must be `LEN` bytes to pack.
+    pub fn migrate(&mut self) {
+        if self.version < 2 {
+            self.slippage_cap_bps = Self::DEFAULT_SLIPPAGE_CAP_BPS;
+            self.min_yield_cap_bps = Self::DEFAULT_MIN_YIELD_CAP_BPS;
+        }
+        if self.version < 3 {
+            self.dex_programs = DexProgram::defaults();
+        }
+        if self.version < 4 {
+            self.oracle_max_age_seconds = Self::DEFAULT_ORACLE_MAX_AGE_SECONDS;
+            self.oracle_max_deviation_bps = Self::DEFAULT_ORACLE_MAX_DEVIATION_BPS;
+        }
+        self.version = Self::VERSION;
+    }
+
+    /// Whether `program_id` is an accepted deployment of `protocol`. Configs
+    /// older than v3 accept the mainnet defaults.
+    pub fn accepts_dex(&self, protocol: Protocol, program_id: &Pubkey) -> bool {
+        let entry = DexProgram { protocol, program_id: *program_id };
+        if self.version < 3 {
+            DexProgram::defaults().contains(&entry)
+        } else {
+            self.dex_programs.contains(&entry)
+        }
+    }
+
+    /// Largest `max_slippage_bps` an owner may authorize. v1 accounts predate
+    /// the setting and get the default until the admin sets bounds.
+    pub fn slippage_cap(&self) -> u16 {
+        if self.version < 2 {
+            Self::DEFAULT_SLIPPAGE_CAP_BPS
+        } else {
+            self.slippage_cap_bps
+        }
+    }
+
+    /// Largest `min_yield_improvement_bps` an owner may require.
+    pub fn min_yield_cap(&self) -> u16 {
+        if self.version < 2 {
+            Self::DEFAULT_MIN_YIELD_CAP_BPS
+        } else {
+            self.min_yield_cap_bps
+        }
+    }
+
+    /// Oldest oracle price agent actions accept; pre-v4 accounts get the default.
+    pub fn oracle_max_age(&self) -> u32 {
+        if self.version < 4 {
+            Self::DEFAULT_ORACLE_MAX_AGE_SECONDS
+        } else {
+            self.oracle_max_age_seconds
+        }
+    }
+
+    /// Widest pool/oracle price gap agent actions accept.
+    pub fn oracle_max_deviation(&self) -> u16 {
+        if self.version < 4 {
+            Self::DEFAULT_ORACLE_MAX_DEVIATION_BPS
+        } else {
+            self.oracle_max_deviation_bps
+        }
+    }
+
+    /// Check owner-supplied rebalance parameters against the configured caps.
+    pub fn validate_params(
+        &self,
+        max_slippage_bps: u16,
+        min_yield_improvement_bps: u16,
+    ) -> Result<(), PoseidonError> {
+        if max_slippage_bps == 0 || max_slippage_bps > self.slippage_cap() {
+            return Err(PoseidonError::SlippageOutOfBounds);
+        }
+        if min_yield_improvement_bps > self.min_yield_cap() {
+            return Err(PoseidonError::MinYieldOutOfBounds);
+        }
+        Ok(())
+    }
+
+    /// Check the rebalance parameters `update` sets against the configured
+    /// caps. Fields it leaves out keep their value even if a cap has since
+    /// been lowered below it.
+    pub fn validate_update(&self, update: &ConfigUpdate) -> Result<(), PoseidonError> {
+        if let Some(max_slippage_bps) = update.max_slippage_bps {
+            if max_slippage_bps == 0 || max_slippage_bps > self.slippage_cap() {
+                return Err(PoseidonError::SlippageOutOfBounds);
+            }
+        }
+        if update.min_yield_improvement_bps.is_some_and(|bps| bps > self.min_yield_cap()) {
+            return Err(PoseidonError::MinYieldOutOfBounds);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validate_update_checks_only_fields_set() {
+        let program_config = ProgramConfig {
+            version: ProgramConfig::VERSION,
+            slippage_cap_bps: 100,
+            min_yield_cap_bps: 50,
+            ..ProgramConfig::default()
+        };
+        // A config above lowered caps can still change its other fields
+        let update = ConfigUpdate { max_rebalances_per_day: Some(4), ..ConfigUpdate::default() };
+        assert_eq!(program_config.validate_update(&update), Ok(()));
+
+        let update = ConfigUpdate { max_slippage_bps: Some(101), ..ConfigUpdate::default() };
+        assert_eq!(program_config.validate_update(&update), Err(PoseidonError::SlippageOutOfBounds));
+        let update = ConfigUpdate { max_slippage_bps: Some(0), ..ConfigUpdate::default() };
+        assert_eq!(program_config.validate_update(&update), Err(PoseidonError::SlippageOutOfBounds));
+        let update = ConfigUpdate { min_yield_improvement_bps: Some(51), ..ConfigUpdate::default() };
+        assert_eq!(program_config.validate_update(&update), Err(PoseidonError::MinYieldOutOfBounds));
+        let update = ConfigUpdate {
+            max_slippage_bps: Some(100),
+            min_yield_improvement_bps: Some(50),
+            ..ConfigUpdate::default()
+        };
+        assert_eq!(program_config.validate_update(&update), Ok(()));
+    }
+}
@@ -0,0 +1,37 @@
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::pubkey::Pubkey;
+
+use crate::{AccountState, RECORD_SEED};
+
+/// Latest agent rebalance for a position, stored at ["rebalance_record", config].
+/// `agent_reported` is set when the slippage is the agent's own figure rather
+/// than measured by the program.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, Default, PartialEq)]
+pub struct RebalanceRecord {
+    pub config: Pubkey,             // 32
+    pub position_mint: Pubkey,      // 32
+    pub agent: Pubkey,              // 32
+    pub old_tick_lower: i32,        // 4
+    pub old_tick_upper: i32,        // 4
+    pub new_tick_lower: i32,        // 4
+    pub new_tick_upper: i32,        // 4
+    pub realized_slippage_bps: u16, // 2
+    pub executed_at: i64,           // 8
+    pub rebalance_count: u64,       // 8
+    pub agent_reported: bool,       // 1
+}
+
+impl AccountState for RebalanceRecord {
+    // sha256("account:RebalanceRecord")[0..8]
+    const DISCRIMINATOR: [u8; 8] = [190, 69, 228, 114, 34, 217, 70, 102];
+    const LEN: usize = Self::V1_LEN + 1; // 139
+}
+
+impl RebalanceRecord {
+    /// Size before `agent_reported` was added.
+    pub const V1_LEN: usize = 8 + 32 + 32 + 32 + 4 + 4 + 4 + 4 + 2 + 8 + 8; // 138
+
+    pub fn find_address(config: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[RECORD_SEED, config.as_ref()], program_id)
+    }
+}
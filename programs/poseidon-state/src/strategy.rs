@@ -0,0 +1,240 @@
+use borsh::{BorshDeserialize, BorshSerialize};
+
+use crate::{
+    math::{sqrt_price_at_tick, Q64},
+    PoseidonError, Protocol,
+};
+
+/// Shape of the range the agent opens when it rebalances.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[repr(u8)]
+pub enum StrategyKind {
+    /// No stored strategy; ranges aren't checked. Configs from before
+    /// strategies were recorded load as this.
+    #[default]
+    Unspecified = 0,
+    /// Range width is `width` bps of price, centered on the current tick.
+    FixedPercent = 1,
+    /// Range width is `width` ticks (bins on Meteora), around the current tick.
+    TickCount = 2,
+    /// `width` ticks entirely above the current tick.
+    OneSidedAbove = 3,
+    /// `width` ticks entirely below the current tick.
+    OneSidedBelow = 4,
+    /// Widest range the pool allows; `width` is unused.
+    FullRange = 5,
+}
+
+impl TryFrom<u8> for StrategyKind {
+    type Error = PoseidonError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Self::Unspecified),
+            1 => Ok(Self::FixedPercent),
+            2 => Ok(Self::TickCount),
+            3 => Ok(Self::OneSidedAbove),
+            4 => Ok(Self::OneSidedBelow),
+            5 => Ok(Self::FullRange),
+            _ => Err(PoseidonError::InvalidStrategy),
+        }
+    }
+}
+
+/// Range strategy for a position.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Strategy {
+    pub kind: StrategyKind,          // 1
+    pub width: u32,                  // 4
+    /// Rebalance once the price is within this share of the range width
+    /// (in bps) of either edge; 0 waits until the price leaves the range.
+    pub recenter_threshold_bps: u16, // 2
+}
+
+impl Strategy {
+    pub const LEN: usize = 1 + 4 + 2;
+    /// Thresholds past the midpoint would allow rebalancing a centered range.
+    pub const MAX_RECENTER_THRESHOLD_BPS: u16 = 5_000;
+    /// Largest `FixedPercent` width; wider ranges should use `FullRange`.
+    pub const MAX_WIDTH_BPS: u32 = 100_000;
+    /// Tick spacing rounds percentage ranges, so widths match within this share.
+    pub const WIDTH_TOLERANCE_BPS: u64 = 2_000;
+    /// Whirlpool and Raydium CLMM tick bounds.
+    pub const MIN_TICK: i32 = -443_636;
+    pub const MAX_TICK: i32 = 443_636;
+    /// A full range rounded to the widest tick spacing (32768) still counts.
+    pub const FULL_RANGE_SLACK: i32 = 32_768;
+
+    /// Check the parameters make sense for the position's protocol.
+    pub fn validate(&self, protocol: Protocol) -> Result<(), PoseidonError> {
+        if self.recenter_threshold_bps > Self::MAX_RECENTER_THRESHOLD_BPS {
+            return Err(PoseidonError::InvalidStrategy);
+        }
+        match self.kind {
+            StrategyKind::Unspecified | StrategyKind::FullRange => Ok(()),
+            // Bin prices depend on the pool's bin step, which isn't known here
+            StrategyKind::FixedPercent if protocol == Protocol::MeteoraDlmm => {
+                Err(PoseidonError::InvalidStrategy)
+            }
+            StrategyKind::FixedPercent if self.width > Self::MAX_WIDTH_BPS => {
+                Err(PoseidonError::InvalidStrategy)
+            }
+            _ if self.width == 0 => Err(PoseidonError::InvalidStrategy),
+            _ => Ok(()),
+        }
+    }
+
+    /// Check a rebalance from `old` to `new` (lower, upper) at `current_tick`
+    /// is due under the re-center threshold and produces the stored shape.
+    pub fn check_rebalance(
+        &self,
+        old: (i32, i32),
+        new: (i32, i32),
+        current_tick: i32,
+    ) -> Result<(), PoseidonError> {
+        if self.kind == StrategyKind::Unspecified {
+            return Ok(());
+        }
+
+        // Distance from the nearest edge, negative once out of range
+        let old_width = i64::from(old.1) - i64::from(old.0);
+        let edge_distance =
+            (i64::from(current_tick) - i64::from(old.0)).min(i64::from(old.1) - i64::from(current_tick));
+        if edge_distance * 10_000 > old_width * i64::from(self.recenter_threshold_bps) {
+            return Err(PoseidonError::RebalanceNotDue);
+        }
+
+        let width = i64::from(new.1) - i64::from(new.0);
+        let contains_current = new.0 <= current_tick && current_tick < new.1;
+        let matches = match self.kind {
+            StrategyKind::Unspecified => true,
+            StrategyKind::FixedPercent => {
+                let expected = Self::ticks_for_width_bps(self.width);
+                let tolerance = (expected as u64 * Self::WIDTH_TOLERANCE_BPS / 10_000).max(1);
+                contains_current && (width - expected).unsigned_abs() <= tolerance
+            }
+            StrategyKind::TickCount => contains_current && width == i64::from(self.width),
+            StrategyKind::OneSidedAbove => new.0 > current_tick && width == i64::from(self.width),
+            StrategyKind::OneSidedBelow => new.1 <= current_tick && width == i64::from(self.width),
+            StrategyKind::FullRange => {
+                new.0 <= Self::MIN_TICK + Self::FULL_RANGE_SLACK
+                    && new.1 >= Self::MAX_TICK - Self::FULL_RANGE_SLACK
+            }
+        };
+        if !matches {
+            return Err(PoseidonError::RangeMismatch);
+        }
+        Ok(())
+    }
+
+    /// Ticks spanned by a range whose upper price is `width_bps` above its
+    /// lower, rounded to the nearest tick.
+    fn ticks_for_width_bps(width_bps: u32) -> i64 {
+        // Price ratio in Q64.64; at most 11, so it fits
+        let ratio = (10_000 + u128::from(width_bps)) * Q64 / 10_000;
+        // The price of tick t is the square root price of tick 2t, so the
+        // square root price of 2t + 1 is the log-midpoint between t and t + 1.
+        // The answer is the first t whose upper midpoint lies above the ratio.
+        let (mut low, mut high) = (0, Self::MAX_TICK / 2);
+        while low < high {
+            let mid = (low + high) / 2;
+            if sqrt_price_at_tick(2 * mid + 1) > ratio {
+                high = mid;
+            } else {
+                low = mid + 1;
+            }
+        }
+        i64::from(low)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strategy(kind: StrategyKind, width: u32, recenter_threshold_bps: u16) -> Strategy {
+        Strategy { kind, width, recenter_threshold_bps }
+    }
+
+    #[test]
+    fn unspecified_accepts_any_range() {
+        let s = Strategy::default();
+        assert_eq!(s.check_rebalance((0, 100), (-7, 3), 50), Ok(()));
+    }
+
+    #[test]
+    fn recenter_threshold_gates_rebalance() {
+        let s = strategy(StrategyKind::TickCount, 100, 2_000);
+        // 20 ticks from the lower edge of a 100-tick range is exactly 20%
+        assert_eq!(s.check_rebalance((0, 100), (-30, 70), 20), Ok(()));
+        assert_eq!(s.check_rebalance((0, 100), (-29, 71), 21), Err(PoseidonError::RebalanceNotDue));
+        assert_eq!(s.check_rebalance((0, 100), (30, 130), 80), Ok(()));
+
+        let out_only = strategy(StrategyKind::TickCount, 100, 0);
+        assert_eq!(out_only.check_rebalance((0, 100), (0, 100), 1), Err(PoseidonError::RebalanceNotDue));
+        assert_eq!(out_only.check_rebalance((0, 100), (55, 155), 105), Ok(()));
+    }
+
+    #[test]
+    fn tick_count_requires_width_around_current() {
+        let s = strategy(StrategyKind::TickCount, 100, 0);
+        assert_eq!(s.check_rebalance((0, 100), (55, 150), 105), Err(PoseidonError::RangeMismatch));
+        assert_eq!(s.check_rebalance((0, 100), (106, 206), 105), Err(PoseidonError::RangeMismatch));
+    }
+
+    #[test]
+    fn fixed_percent_allows_tick_rounding() {
+        // 10% of price is ~953 ticks, with 20% tolerance
+        let s = strategy(StrategyKind::FixedPercent, 1_000, 0);
+        assert_eq!(s.check_rebalance((0, 1_000), (-500, 453), -5), Ok(()));
+        assert_eq!(s.check_rebalance((0, 1_000), (-500, 600), -5), Ok(()));
+        assert_eq!(s.check_rebalance((0, 1_000), (-500, 700), -5), Err(PoseidonError::RangeMismatch));
+        assert_eq!(s.check_rebalance((0, 1_000), (0, 953), -5), Err(PoseidonError::RangeMismatch));
+    }
+
+    #[test]
+    fn ticks_for_width_bps_rounds_to_nearest_tick() {
+        assert_eq!(Strategy::ticks_for_width_bps(0), 0);
+        assert_eq!(Strategy::ticks_for_width_bps(1), 1);
+        // ln(1.01) / ln(1.0001) = 99.51
+        assert_eq!(Strategy::ticks_for_width_bps(100), 100);
+        assert_eq!(Strategy::ticks_for_width_bps(1_000), 953);
+        assert_eq!(Strategy::ticks_for_width_bps(10_000), 6_932);
+        assert_eq!(Strategy::ticks_for_width_bps(Strategy::MAX_WIDTH_BPS), 23_980);
+    }
+
+    #[test]
+    fn one_sided_ranges_sit_beside_current() {
+        let above = strategy(StrategyKind::OneSidedAbove, 100, 0);
+        assert_eq!(above.check_rebalance((0, 100), (0, 100), -5), Ok(()));
+        assert_eq!(above.check_rebalance((0, 100), (-10, 90), -5), Err(PoseidonError::RangeMismatch));
+
+        let below = strategy(StrategyKind::OneSidedBelow, 100, 0);
+        assert_eq!(below.check_rebalance((0, 100), (-105, -5), -5), Ok(()));
+        assert_eq!(below.check_rebalance((0, 100), (-104, -4), -5), Err(PoseidonError::RangeMismatch));
+    }
+
+    #[test]
+    fn full_range_needs_pool_bounds() {
+        let s = strategy(StrategyKind::FullRange, 0, 0);
+        let rounded = (Strategy::MIN_TICK + 20_000, Strategy::MAX_TICK - 20_000);
+        assert_eq!(s.check_rebalance((0, 100), rounded, 200), Ok(()));
+        assert_eq!(s.check_rebalance((0, 100), (-100_000, 100_000), 200), Err(PoseidonError::RangeMismatch));
+    }
+
+    #[test]
+    fn validate_rejects_bad_parameters() {
+        let s = strategy(StrategyKind::FixedPercent, 1_000, 0);
+        assert_eq!(s.validate(Protocol::OrcaWhirlpool), Ok(()));
+        assert_eq!(s.validate(Protocol::MeteoraDlmm), Err(PoseidonError::InvalidStrategy));
+        assert_eq!(
+            strategy(StrategyKind::TickCount, 0, 0).validate(Protocol::OrcaWhirlpool),
+            Err(PoseidonError::InvalidStrategy)
+        );
+        assert_eq!(
+            strategy(StrategyKind::TickCount, 10, 5_001).validate(Protocol::OrcaWhirlpool),
+            Err(PoseidonError::InvalidStrategy)
+        );
+        assert_eq!(strategy(StrategyKind::FullRange, 0, 0).validate(Protocol::MeteoraDlmm), Ok(()));
+    }
+}
@@ -0,0 +1,45 @@
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::pubkey::Pubkey;
+
+use crate::{AccountState, VAULT_SEED};
+
+/// Custody record for a position deposited into vault mode, stored at
+/// ["vault", config]. The PDA itself owns the position NFT and the token
+/// accounts liquidity moves through, and signs the DEX CPIs.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, Default, PartialEq)]
+pub struct VaultState {
+    pub version: u8,           // 1
+    pub bump: u8,              // 1
+    pub config: Pubkey,        // 32
+    pub owner: Pubkey,         // 32
+    /// NFT currently held. Starts as the config's `position_mint` and moves
+    /// to the new NFT after each rebalance.
+    pub position_mint: Pubkey, // 32
+    pub created_at: i64,       // 8
+    pub updated_at: i64,       // 8
+    // v2
+    /// The vault's token accounts for the pool's mints, set by the first
+    /// rebalance (default until then). Withdrawing must drain both.
+    pub token_a: Pubkey,       // 32
+    pub token_b: Pubkey,       // 32
+}
+
+impl AccountState for VaultState {
+    // sha256("account:VaultState")[0..8]
+    const DISCRIMINATOR: [u8; 8] = [228, 196, 82, 165, 98, 210, 235, 152];
+    const LEN: usize = Self::V1_LEN + 32 + 32; // 186
+}
+
+impl VaultState {
+    pub const VERSION: u8 = 2;
+    pub const V1_LEN: usize = 8 + 1 + 1 + 32 + 32 + 32 + 8 + 8; // 122
+
+    /// The recorded vault token accounts, if a rebalance has set them.
+    pub fn token_accounts(&self) -> Option<(Pubkey, Pubkey)> {
+        (self.token_a != Pubkey::default()).then_some((self.token_a, self.token_b))
+    }
+
+    pub fn find_address(config: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[VAULT_SEED, config.as_ref()], program_id)
+    }
+}
@@ -24,22 +24,60 @@ function disc(name: string): Buffer {
   return createHash("sha256").update(`global:${name}`).digest().subarray(0, 8);
 }
 
-function getPDA(owner: PublicKey): [PublicKey, number] {
+// The position under test: POSITION_MINT (the position account for Meteora)
+// and PROTOCOL (1 = Orca, 2 = Raydium, 3 = Meteora). The keypair must hold it.
+const POSITION_MINT = new PublicKey(process.env.POSITION_MINT ?? PublicKey.default);
+const PROTOCOL = Number(process.env.PROTOCOL ?? 1);
+
+const TOKEN_PROGRAM_ID = new PublicKey("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
+const ASSOCIATED_TOKEN_PROGRAM_ID = new PublicKey("ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL");
+const DEX_PROGRAMS: Record<number, PublicKey> = {
+  1: new PublicKey("whirLbMiicVdio4qvUfM5KAg6Ct8VwpYzGff3uctyCc"),
+  2: new PublicKey("CAMMCzo5YL8w4VFF8KVHrK22GGUsp5VTaW7grrKgrWqK"),
+};
+
+function getPDA(owner: PublicKey, positionMint: PublicKey): [PublicKey, number] {
   return PublicKey.findProgramAddressSync(
-    [Buffer.from("rebalance"), owner.toBuffer()],
+    [Buffer.from("rebalance"), owner.toBuffer(), positionMint.toBuffer()],
     PROGRAM_ID
   );
 }
 
+function getProgramConfigPDA(): PublicKey {
+  return PublicKey.findProgramAddressSync([Buffer.from("program_config")], PROGRAM_ID)[0];
+}
+
+// Orca/Raydium: the owner's NFT token account and the DEX position PDA.
+// Anchor reads the program id in an optional account's slot as None (Meteora).
+function positionAccounts(owner: PublicKey, positionMint: PublicKey): [PublicKey, PublicKey] {
+  const dex = DEX_PROGRAMS[PROTOCOL];
+  if (!dex) return [PROGRAM_ID, PROGRAM_ID];
+  const [tokenAccount] = PublicKey.findProgramAddressSync(
+    [owner.toBuffer(), TOKEN_PROGRAM_ID.toBuffer(), positionMint.toBuffer()],
+    ASSOCIATED_TOKEN_PROGRAM_ID
+  );
+  const [dexPosition] = PublicKey.findProgramAddressSync(
+    [Buffer.from("position"), positionMint.toBuffer()],
+    dex
+  );
+  return [tokenAccount, dexPosition];
+}
+
 function enableIx(pda: PublicKey, owner: PublicKey, maxSlippage: number, minYield: number): TransactionInstruction {
-  const data = Buffer.alloc(12);
+  const data = Buffer.alloc(13);
   disc("enable_rebalance").copy(data, 0);
   data.writeUInt16LE(maxSlippage, 8);
   data.writeUInt16LE(minYield, 10);
+  data.writeUInt8(PROTOCOL, 12);
+  const [positionTokenAccount, dexPosition] = positionAccounts(owner, POSITION_MINT);
   return new TransactionInstruction({
     keys: [
       { pubkey: pda, isSigner: false, isWritable: true },
       { pubkey: owner, isSigner: true, isWritable: true },
+      { pubkey: POSITION_MINT, isSigner: false, isWritable: false },
+      { pubkey: positionTokenAccount, isSigner: false, isWritable: false },
+      { pubkey: dexPosition, isSigner: false, isWritable: false },
+      { pubkey: getProgramConfigPDA(), isSigner: false, isWritable: false },
       { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
     ],
     programId: PROGRAM_ID,
@@ -52,6 +90,7 @@ function disableIx(pda: PublicKey, owner: PublicKey): TransactionInstruction {
     keys: [
       { pubkey: pda, isSigner: false, isWritable: true },
       { pubkey: owner, isSigner: true, isWritable: true },
+      { pubkey: POSITION_MINT, isSigner: false, isWritable: false },
     ],
     programId: PROGRAM_ID,
     data: disc("disable_rebalance"),
@@ -63,6 +102,7 @@ function isEnabledIx(pda: PublicKey, owner: PublicKey): TransactionInstruction {
     keys: [
       { pubkey: pda, isSigner: false, isWritable: false },
       { pubkey: owner, isSigner: false, isWritable: false },
+      { pubkey: POSITION_MINT, isSigner: false, isWritable: false },
     ],
     programId: PROGRAM_ID,
     data: disc("is_enabled"),
@@ -85,6 +125,7 @@ async function sendTx(conn: Connection, kp: Keypair, ix: TransactionInstruction)
 
 interface RebalanceConfig {
   owner: PublicKey;
+  positionMint: PublicKey;
   enabled: boolean;
   maxSlippageBps: number;
   minYieldImprovementBps: number;
@@ -95,12 +136,13 @@ interface RebalanceConfig {
 function deserialize(data: Buffer): RebalanceConfig {
   let off = 8; // skip discriminator
   const owner = new PublicKey(data.subarray(off, off + 32)); off += 32;
+  const positionMint = new PublicKey(data.subarray(off, off + 32)); off += 32;
   const enabled = data[off] === 1; off += 1;
   const maxSlippageBps = data.readUInt16LE(off); off += 2;
   const minYieldImprovementBps = data.readUInt16LE(off); off += 2;
   const createdAt = data.readBigUInt64LE(off); off += 8;
   const updatedAt = data.readBigUInt64LE(off);
-  return { owner, enabled, maxSlippageBps, minYieldImprovementBps, createdAt, updatedAt };
+  return { owner, positionMint, enabled, maxSlippageBps, minYieldImprovementBps, createdAt, updatedAt };
 }
 
 let passed = 0, failed = 0;
@@ -110,9 +152,14 @@ function fail(name: string, detail?: string) { failed++; console.log(`  ❌ ${na
 async function main() {
   const conn = new Connection(RPC, "confirmed");
   const kp = loadKeypair();
-  const [pda, bump] = getPDA(kp.publicKey);
-  console.log(`Owner:  ${kp.publicKey}`);
-  console.log(`PDA:    ${pda} (bump ${bump})\n`);
+  if (POSITION_MINT.equals(PublicKey.default)) {
+    console.log("Set POSITION_MINT (and PROTOCOL) to a position the keypair holds");
+    process.exit(1);
+  }
+  const [pda, bump] = getPDA(kp.publicKey, POSITION_MINT);
+  console.log(`Owner:    ${kp.publicKey}`);
+  console.log(`Position: ${POSITION_MINT} (protocol ${PROTOCOL})`);
+  console.log(`PDA:      ${pda} (bump ${bump})\n`);
 
   // 1. Airdrop
   console.log("1. Airdrop SOL");
@@ -140,11 +187,12 @@ async function main() {
     const acct = await conn.getAccountInfo(pda);
     if (!acct) throw new Error("PDA not found");
     const cfg = deserialize(acct.data as Buffer);
-    console.log("  Data:", { ...cfg, owner: cfg.owner.toBase58(), createdAt: cfg.createdAt.toString(), updatedAt: cfg.updatedAt.toString() });
+    console.log("  Data:", { ...cfg, owner: cfg.owner.toBase58(), positionMint: cfg.positionMint.toBase58(), createdAt: cfg.createdAt.toString(), updatedAt: cfg.updatedAt.toString() });
     cfg.enabled ? ok("enabled=true") : fail("enabled!=true");
     cfg.maxSlippageBps === 100 ? ok("maxSlippageBps=100") : fail(`maxSlippageBps=${cfg.maxSlippageBps}`);
     cfg.minYieldImprovementBps === 50 ? ok("minYieldImprovementBps=50") : fail(`minYieldImprovementBps=${cfg.minYieldImprovementBps}`);
     cfg.owner.equals(kp.publicKey) ? ok("owner matches") : fail("owner mismatch");
+    cfg.positionMint.equals(POSITION_MINT) ? ok("positionMint matches") : fail("positionMint mismatch");
   } catch (e: any) { fail("Read Config", e.message); }
 
   // 4. Update Config (200, 75)